@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+use generic_storage::codegen;
+use generic_storage::models::Person;
+use generic_storage::schema::Schema;
+
+// Usage: codegen [OUT_DIR]
+// Without OUT_DIR the TypeScript module is printed to stdout.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let schemas = vec![Person::schema()?];
+    let ts = codegen::typescript(&schemas);
+
+    let Some(out_dir) = std::env::args().nth(1).map(PathBuf::from) else {
+        print!("{}", ts);
+        return Ok(());
+    };
+
+    std::fs::create_dir_all(&out_dir)?;
+    std::fs::write(out_dir.join("models.ts"), ts)?;
+
+    // Nested structs get their own Borsh schema, referenced by name from their parents.
+    let mut borsh = Vec::new();
+    for schema in &schemas {
+        borsh.extend(schema.nested().into_iter().chain([schema]));
+    }
+    for schema in &borsh {
+        std::fs::write(
+            out_dir.join(format!("{}.borsh.json", schema.name)),
+            serde_json::to_string_pretty(&schema.to_borsh_schema())?,
+        )?;
+    }
+
+    for schema in &schemas {
+        std::fs::write(
+            out_dir.join(format!("{}.schema.json", schema.name)),
+            serde_json::to_string_pretty(&schema.to_json_schema())?,
+        )?;
+    }
+
+    println!(
+        "Wrote schemas for {} model(s) to {}",
+        schemas.len(),
+        out_dir.display()
+    );
+    Ok(())
+}
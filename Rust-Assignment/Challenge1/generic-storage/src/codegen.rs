@@ -0,0 +1,243 @@
+use std::fmt::Write;
+
+use crate::schema::{FieldType, ModelSchema};
+
+const HEADER: &str = "// Generated by generic-storage. Do not edit.\n";
+
+// Shared binary reader. Borsh prefixes strings and vectors with a u32 length,
+// bincode with a u64.
+const READER: &str = r#"
+class Reader {
+  private view: DataView;
+  private offset = 0;
+
+  constructor(private bytes: Uint8Array, private lengthPrefix: 4 | 8) {
+    this.view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  }
+
+  private advance(size: number): number {
+    const start = this.offset;
+    if (start + size > this.bytes.length) {
+      throw new RangeError("unexpected end of input");
+    }
+    this.offset += size;
+    return start;
+  }
+
+  bool(): boolean {
+    const v = this.u8();
+    if (v > 1) throw new Error(`invalid bool ${v}`);
+    return v === 1;
+  }
+  u8(): number { return this.view.getUint8(this.advance(1)); }
+  u16(): number { return this.view.getUint16(this.advance(2), true); }
+  u32(): number { return this.view.getUint32(this.advance(4), true); }
+  u64(): bigint { return this.view.getBigUint64(this.advance(8), true); }
+  i8(): number { return this.view.getInt8(this.advance(1)); }
+  i16(): number { return this.view.getInt16(this.advance(2), true); }
+  i32(): number { return this.view.getInt32(this.advance(4), true); }
+  i64(): bigint { return this.view.getBigInt64(this.advance(8), true); }
+
+  private len(): number {
+    return this.lengthPrefix === 4 ? this.u32() : Number(this.u64());
+  }
+
+  string(): string {
+    const len = this.len();
+    const start = this.advance(len);
+    return new TextDecoder().decode(this.bytes.subarray(start, start + len));
+  }
+
+  vec<T>(item: () => T): Array<T> {
+    const len = this.len();
+    const out: Array<T> = [];
+    for (let i = 0; i < len; i++) out.push(item());
+    return out;
+  }
+
+  // Both formats tag an option with one byte, 0 for none and 1 for some.
+  option<T>(item: () => T): T | null {
+    const tag = this.u8();
+    if (tag > 1) throw new Error(`invalid option tag ${tag}`);
+    return tag === 1 ? item() : null;
+  }
+
+  finish(): void {
+    if (this.offset !== this.bytes.length) {
+      throw new Error("trailing bytes after value");
+    }
+  }
+}
+"#;
+
+// 64-bit integers are written as strings by `json_string_int`, so they reach
+// `BigInt` without passing through a lossy JS number.
+const JSON_HELPERS: &str = r#"
+function bigintValue(v: unknown, name: string): bigint {
+  if (typeof v !== "string") {
+    throw new TypeError(`${name} must be a string-encoded integer`);
+  }
+  return BigInt(v);
+}
+"#;
+
+fn ts_type(ty: &FieldType) -> String {
+    match ty {
+        FieldType::Bool => "boolean".to_string(),
+        FieldType::U64 | FieldType::I64 => "bigint".to_string(),
+        FieldType::String => "string".to_string(),
+        FieldType::Vec(item) => format!("Array<{}>", ts_type(item)),
+        FieldType::Option(item) => format!("{} | null", ts_type(item)),
+        FieldType::Struct(schema) => schema.name.clone(),
+        _ => "number".to_string(),
+    }
+}
+
+/// Expression reading a `ty` from the `Reader` named `r`.
+fn read_expr(ty: &FieldType) -> String {
+    match ty {
+        FieldType::Bool => "r.bool()".to_string(),
+        FieldType::U8 => "r.u8()".to_string(),
+        FieldType::U16 => "r.u16()".to_string(),
+        FieldType::U32 => "r.u32()".to_string(),
+        FieldType::U64 => "r.u64()".to_string(),
+        FieldType::I8 => "r.i8()".to_string(),
+        FieldType::I16 => "r.i16()".to_string(),
+        FieldType::I32 => "r.i32()".to_string(),
+        FieldType::I64 => "r.i64()".to_string(),
+        FieldType::String => "r.string()".to_string(),
+        FieldType::Vec(item) => format!("r.vec(() => {})", read_expr(item)),
+        FieldType::Option(item) => format!("r.option(() => {})", read_expr(item)),
+        FieldType::Struct(schema) => format!("read{}(r)", schema.name),
+    }
+}
+
+/// Expression converting the parsed JSON value `v` into a `ty`; `path` names
+/// it in errors and `depth` keeps closure parameters distinct.
+fn json_expr(ty: &FieldType, v: &str, path: &str, depth: usize) -> String {
+    match ty {
+        FieldType::U64 | FieldType::I64 => format!("bigintValue({}, \"{}\")", v, path),
+        FieldType::Vec(item) => {
+            let param = format!("item{}", depth);
+            format!(
+                "({} as Array<unknown>).map(({}) => {})",
+                v,
+                param,
+                json_expr(item, &param, &format!("{}[]", path), depth + 1)
+            )
+        }
+        FieldType::Option(item) => format!(
+            "{} === null ? null : {}",
+            v,
+            json_expr(item, v, path, depth)
+        ),
+        FieldType::Struct(schema) => {
+            format!("fromJson{}({} as Record<string, unknown>)", schema.name, v)
+        }
+        _ => format!("{} as {}", v, ts_type(ty)),
+    }
+}
+
+fn write_interface(out: &mut String, schema: &ModelSchema) {
+    writeln!(out, "\nexport interface {} {{", schema.name).unwrap();
+    for f in &schema.fields {
+        writeln!(out, "  {}: {};", f.name, ts_type(&f.ty)).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+}
+
+fn write_reader(out: &mut String, schema: &ModelSchema) {
+    writeln!(
+        out,
+        "\nfunction read{}(r: Reader): {} {{",
+        schema.name, schema.name
+    )
+    .unwrap();
+    // Object literal properties are evaluated in order, matching the wire layout.
+    writeln!(out, "  return {{").unwrap();
+    for f in &schema.fields {
+        writeln!(out, "    {}: {},", f.name, read_expr(&f.ty)).unwrap();
+    }
+    writeln!(out, "  }};").unwrap();
+    writeln!(out, "}}").unwrap();
+}
+
+fn write_json_reader(out: &mut String, schema: &ModelSchema) {
+    writeln!(
+        out,
+        "\nfunction fromJson{}(raw: Record<string, unknown>): {} {{",
+        schema.name, schema.name
+    )
+    .unwrap();
+    writeln!(out, "  return {{").unwrap();
+    for f in &schema.fields {
+        let value = format!("raw.{}", f.name);
+        writeln!(
+            out,
+            "    {}: {},",
+            f.name,
+            json_expr(&f.ty, &value, &f.name, 0)
+        )
+        .unwrap();
+    }
+    writeln!(out, "  }};").unwrap();
+    writeln!(out, "}}").unwrap();
+}
+
+fn write_binary_decoder(out: &mut String, schema: &ModelSchema, format: &str, prefix: u8) {
+    writeln!(
+        out,
+        "\nexport function decode{}{}(bytes: Uint8Array): {} {{",
+        schema.name, format, schema.name
+    )
+    .unwrap();
+    writeln!(out, "  const r = new Reader(bytes, {});", prefix).unwrap();
+    writeln!(out, "  const value = read{}(r);", schema.name).unwrap();
+    writeln!(out, "  r.finish();").unwrap();
+    writeln!(out, "  return value;").unwrap();
+    writeln!(out, "}}").unwrap();
+}
+
+fn write_json_decoder(out: &mut String, schema: &ModelSchema) {
+    writeln!(
+        out,
+        "\nexport function decode{}Json(bytes: Uint8Array): {} {{",
+        schema.name, schema.name
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "  return fromJson{}(JSON.parse(new TextDecoder().decode(bytes)));",
+        schema.name
+    )
+    .unwrap();
+    writeln!(out, "}}").unwrap();
+}
+
+/// Emits a TypeScript module with an interface and Borsh, Bincode and JSON
+/// decoders for every model, plus an interface and readers for each struct
+/// nested in one.
+pub fn typescript(schemas: &[ModelSchema]) -> String {
+    let mut out = String::from(HEADER);
+    out.push_str(READER);
+    out.push_str(JSON_HELPERS);
+
+    let mut written: Vec<&str> = Vec::new();
+    for schema in schemas {
+        for nested in schema.nested().into_iter().chain([schema]) {
+            if written.contains(&nested.name.as_str()) {
+                continue;
+            }
+            written.push(&nested.name);
+            write_interface(&mut out, nested);
+            write_reader(&mut out, nested);
+            write_json_reader(&mut out, nested);
+        }
+
+        write_binary_decoder(&mut out, schema, "Borsh", 4);
+        write_binary_decoder(&mut out, schema, "Bincode", 8);
+        write_json_decoder(&mut out, schema);
+    }
+
+    out
+}
@@ -1,3 +1,6 @@
+pub mod codegen;
+pub mod limits;
 pub mod models;
+pub mod schema;
 pub mod serializer;
 pub mod storage;
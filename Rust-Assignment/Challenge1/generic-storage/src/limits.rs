@@ -0,0 +1,792 @@
+use std::cell::Cell;
+use std::{fmt, io};
+
+use bincode::{BincodeRead, ErrorKind};
+use borsh::schema::{BorshSchemaContainer, Declaration, Definition, Fields};
+use serde::de::{
+    self, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+
+/// Bounds applied while decoding untrusted bytes.
+///
+/// Depth counts nested containers (structs, tuples, sequences, maps, enums and
+/// options), so a flat struct has depth 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    pub max_bytes: usize,
+    pub max_collection_len: usize,
+    pub max_string_len: usize,
+    pub max_depth: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits {
+            max_bytes: 1024 * 1024,
+            max_collection_len: 65_536,
+            max_string_len: 65_536,
+            max_depth: 32,
+        }
+    }
+}
+
+impl DecodeLimits {
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn max_collection_len(mut self, max_collection_len: usize) -> Self {
+        self.max_collection_len = max_collection_len;
+        self
+    }
+
+    pub fn max_string_len(mut self, max_string_len: usize) -> Self {
+        self.max_string_len = max_string_len;
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn check_bytes(&self, len: usize) -> Result<(), LimitExceeded> {
+        check(Limit::TotalBytes, self.max_bytes, len as u64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    TotalBytes,
+    CollectionLength,
+    StringLength,
+    Depth,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Limit::TotalBytes => "total bytes",
+            Limit::CollectionLength => "collection length",
+            Limit::StringLength => "string length",
+            Limit::Depth => "nesting depth",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Returned (boxed) by every serializer when a payload exceeds its [`DecodeLimits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitExceeded {
+    pub limit: Limit,
+    pub max: usize,
+    pub actual: u64,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} limit exceeded: {} > {}",
+            self.limit, self.actual, self.max
+        )
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+fn check(limit: Limit, max: usize, actual: u64) -> Result<(), LimitExceeded> {
+    if actual > max as u64 {
+        return Err(LimitExceeded { limit, max, actual });
+    }
+    Ok(())
+}
+
+fn unexpected_eof() -> Box<dyn std::error::Error> {
+    Box::new(std::io::Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        "unexpected end of input",
+    ))
+}
+
+/// Walks borsh-encoded bytes using the type's schema, checking every length
+/// prefix against the limits before `borsh` gets a chance to allocate for it.
+pub(crate) struct BorshScan<'a> {
+    container: &'a BorshSchemaContainer,
+    bytes: &'a [u8],
+    limits: &'a DecodeLimits,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> BorshScan<'a> {
+    pub(crate) fn new(
+        container: &'a BorshSchemaContainer,
+        bytes: &'a [u8],
+        limits: &'a DecodeLimits,
+    ) -> Self {
+        BorshScan {
+            container,
+            bytes,
+            limits,
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    pub(crate) fn run(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let declaration = self.container.declaration();
+        self.walk(declaration)
+    }
+
+    fn skip(&mut self, len: u64) -> Result<(), Box<dyn std::error::Error>> {
+        let remaining = (self.bytes.len() - self.pos) as u64;
+        if len > remaining {
+            return Err(unexpected_eof());
+        }
+        self.pos += len as usize;
+        Ok(())
+    }
+
+    fn read_uint(&mut self, width: u8) -> Result<u64, Box<dyn std::error::Error>> {
+        let start = self.pos;
+        self.skip(width as u64)?;
+        let mut buf = [0u8; 8];
+        buf[..width as usize].copy_from_slice(&self.bytes[start..self.pos]);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn walk(&mut self, declaration: &Declaration) -> Result<(), Box<dyn std::error::Error>> {
+        let definition = self
+            .container
+            .get_definition(declaration)
+            .ok_or_else(|| format!("borsh schema has no definition for {}", declaration))?;
+
+        if let Definition::Primitive(size) = definition {
+            return self.skip(*size as u64);
+        }
+
+        self.depth += 1;
+        check(Limit::Depth, self.limits.max_depth, self.depth as u64)?;
+
+        match definition {
+            Definition::Primitive(_) => unreachable!(),
+            Definition::Sequence {
+                length_width,
+                length_range,
+                elements,
+            } => {
+                let len = if *length_width == 0 && length_range.start() == length_range.end() {
+                    *length_range.start()
+                } else if *length_width == 0 {
+                    return Err(format!(
+                        "cannot scan variable-length {} without a length prefix",
+                        declaration
+                    )
+                    .into());
+                } else {
+                    self.read_uint(*length_width)?
+                };
+
+                if declaration == "String" {
+                    check(Limit::StringLength, self.limits.max_string_len, len)?;
+                } else {
+                    check(Limit::CollectionLength, self.limits.max_collection_len, len)?;
+                }
+
+                match self.container.get_definition(elements) {
+                    Some(Definition::Primitive(size)) => {
+                        let total = len.checked_mul(*size as u64).ok_or_else(unexpected_eof)?;
+                        self.skip(total)?;
+                    }
+                    _ => {
+                        for _ in 0..len {
+                            self.walk(elements)?;
+                        }
+                    }
+                }
+            }
+            Definition::Tuple { elements } => {
+                for element in elements {
+                    self.walk(element)?;
+                }
+            }
+            Definition::Enum {
+                tag_width,
+                variants,
+            } => {
+                let tag = self.read_uint(*tag_width)?;
+                let (_, _, variant) = variants
+                    .iter()
+                    .find(|(discriminant, _, _)| *discriminant as u64 == tag)
+                    .ok_or_else(|| format!("invalid {} tag {}", declaration, tag))?;
+                self.walk(variant)?;
+            }
+            Definition::Struct { fields } => match fields {
+                Fields::NamedFields(fields) => {
+                    for (_, field) in fields {
+                        self.walk(field)?;
+                    }
+                }
+                Fields::UnnamedFields(fields) => {
+                    for field in fields {
+                        self.walk(field)?;
+                    }
+                }
+                Fields::Empty => {}
+            },
+        }
+
+        self.depth -= 1;
+        Ok(())
+    }
+}
+
+/// Shared state for a [`Limited`] deserializer tree. The first violation is
+/// recorded here so the caller can surface it as a [`LimitExceeded`] instead of
+/// the format's own stringly error.
+pub(crate) struct Tracker<'l> {
+    limits: &'l DecodeLimits,
+    depth: Cell<usize>,
+    exceeded: Cell<Option<LimitExceeded>>,
+}
+
+impl<'l> Tracker<'l> {
+    pub(crate) fn new(limits: &'l DecodeLimits) -> Self {
+        Tracker {
+            limits,
+            depth: Cell::new(0),
+            exceeded: Cell::new(None),
+        }
+    }
+
+    /// Returns the recorded violation, if any, in place of `err`.
+    pub(crate) fn error(&self, err: Box<dyn std::error::Error>) -> Box<dyn std::error::Error> {
+        match self.exceeded.get() {
+            Some(exceeded) => Box::new(exceeded),
+            None => err,
+        }
+    }
+
+    fn check<E: de::Error>(&self, limit: Limit, max: usize, actual: u64) -> Result<(), E> {
+        check(limit, max, actual).map_err(|exceeded| {
+            self.exceeded.set(Some(exceeded));
+            E::custom(exceeded)
+        })
+    }
+
+    fn enter<E: de::Error>(&self) -> Result<(), E> {
+        let depth = self.depth.get() + 1;
+        self.depth.set(depth);
+        self.check(Limit::Depth, self.limits.max_depth, depth as u64)
+    }
+
+    fn exit(&self) {
+        self.depth.set(self.depth.get() - 1);
+    }
+}
+
+/// Byte-slice source for bincode that checks string lengths as soon as the
+/// prefix is read, before the bytes are copied out. Byte buffers, borrowed or
+/// owned, count against `max_string_len` like strings, as they do in every
+/// other format.
+pub(crate) struct BincodeSlice<'a, 't, 'l> {
+    slice: &'a [u8],
+    tracker: &'t Tracker<'l>,
+}
+
+impl<'a, 't, 'l> BincodeSlice<'a, 't, 'l> {
+    pub(crate) fn new(slice: &'a [u8], tracker: &'t Tracker<'l>) -> Self {
+        BincodeSlice { slice, tracker }
+    }
+
+    fn take(&mut self, length: usize) -> bincode::Result<&'a [u8]> {
+        if length > self.slice.len() {
+            return Err(Box::new(ErrorKind::Io(io::ErrorKind::UnexpectedEof.into())));
+        }
+        let (taken, remaining) = self.slice.split_at(length);
+        self.slice = remaining;
+        Ok(taken)
+    }
+
+    fn check_string(&self, length: usize) -> bincode::Result<()> {
+        let max = self.tracker.limits.max_string_len;
+        self.tracker.check(Limit::StringLength, max, length as u64)
+    }
+}
+
+impl io::Read for BincodeSlice<'_, '_, '_> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let taken = self
+            .take(out.len())
+            .map_err(|_| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        out.copy_from_slice(taken);
+        Ok(out.len())
+    }
+}
+
+impl<'a> BincodeRead<'a> for BincodeSlice<'a, '_, '_> {
+    fn forward_read_str<V>(&mut self, length: usize, visitor: V) -> bincode::Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        self.check_string(length)?;
+        let s = std::str::from_utf8(self.take(length)?)
+            .map_err(|err| Box::new(ErrorKind::InvalidUtf8Encoding(err)))?;
+        visitor.visit_borrowed_str(s)
+    }
+
+    fn get_byte_buffer(&mut self, length: usize) -> bincode::Result<Vec<u8>> {
+        self.check_string(length)?;
+        Ok(self.take(length)?.to_vec())
+    }
+
+    fn forward_read_bytes<V>(&mut self, length: usize, visitor: V) -> bincode::Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        self.check_string(length)?;
+        visitor.visit_borrowed_bytes(self.take(length)?)
+    }
+}
+
+/// Deserializer adapter enforcing [`DecodeLimits`] on any serde format.
+pub(crate) struct Limited<'t, 'l, D> {
+    de: D,
+    tracker: &'t Tracker<'l>,
+}
+
+impl<'t, 'l, D> Limited<'t, 'l, D> {
+    pub(crate) fn new(de: D, tracker: &'t Tracker<'l>) -> Self {
+        Limited { de, tracker }
+    }
+}
+
+// `collection` is false for structs and tuples, whose fields may reach the
+// visitor as a sequence or map but are not collection items.
+macro_rules! forward_deserialize {
+    (collection: $collection:expr; $($method:ident($($arg:ident: $ty:ty),*)),* $(,)?) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                let visitor = LimitedVisitor { visitor, tracker: self.tracker, collection: $collection };
+                self.de.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, 't, 'l, D> Deserializer<'de> for Limited<'t, 'l, D>
+where
+    D: Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    forward_deserialize! {
+        collection: true;
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_i128(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_u128(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_option(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_seq(),
+        deserialize_map(),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+        deserialize_ignored_any(),
+    }
+
+    forward_deserialize! {
+        collection: false;
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.de.is_human_readable()
+    }
+}
+
+struct LimitedSeed<'t, 'l, S> {
+    seed: S,
+    tracker: &'t Tracker<'l>,
+}
+
+impl<'de, 't, 'l, S> DeserializeSeed<'de> for LimitedSeed<'t, 'l, S>
+where
+    S: DeserializeSeed<'de>,
+{
+    type Value = S::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.seed
+            .deserialize(Limited::new(deserializer, self.tracker))
+    }
+}
+
+struct LimitedVisitor<'t, 'l, V> {
+    visitor: V,
+    tracker: &'t Tracker<'l>,
+    /// Whether a sequence or map visited here is a collection, rather than the
+    /// fields of a struct or tuple.
+    collection: bool,
+}
+
+macro_rules! forward_visit {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method<E>(self, v: $ty) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                self.visitor.$method(v)
+            }
+        )*
+    };
+}
+
+impl<'t, 'l, V> LimitedVisitor<'t, 'l, V> {
+    fn check_string<E: de::Error>(&self, len: usize) -> Result<(), E> {
+        let limits = self.tracker.limits;
+        self.tracker
+            .check(Limit::StringLength, limits.max_string_len, len as u64)
+    }
+
+    fn check_collection<E: de::Error>(&self, len: usize) -> Result<(), E> {
+        let limits = self.tracker.limits;
+        self.tracker.check(
+            Limit::CollectionLength,
+            limits.max_collection_len,
+            len as u64,
+        )
+    }
+}
+
+impl<'de, 't, 'l, V> Visitor<'de> for LimitedVisitor<'t, 'l, V>
+where
+    V: Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.visitor.expecting(formatter)
+    }
+
+    forward_visit! {
+        visit_bool(bool),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_i64(i64),
+        visit_i128(i128),
+        visit_u8(u8),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_u64(u64),
+        visit_u128(u128),
+        visit_f32(f32),
+        visit_f64(f64),
+        visit_char(char),
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        self.check_string(v.len())?;
+        self.visitor.visit_str(v)
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        self.check_string(v.len())?;
+        self.visitor.visit_borrowed_str(v)
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        self.check_string(v.len())?;
+        self.visitor.visit_string(v)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        self.check_string(v.len())?;
+        self.visitor.visit_bytes(v)
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+        self.check_string(v.len())?;
+        self.visitor.visit_borrowed_bytes(v)
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        self.check_string(v.len())?;
+        self.visitor.visit_byte_buf(v)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.visitor.visit_none()
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.visitor.visit_unit()
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.tracker.enter()?;
+        let value = self
+            .visitor
+            .visit_some(Limited::new(deserializer, self.tracker));
+        self.tracker.exit();
+        value
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.tracker.enter()?;
+        let value = self
+            .visitor
+            .visit_newtype_struct(Limited::new(deserializer, self.tracker));
+        self.tracker.exit();
+        value
+    }
+
+    fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        self.tracker.enter()?;
+        if let Some(len) = seq.size_hint().filter(|_| self.collection) {
+            self.check_collection(len)?;
+        }
+        let value = self.visitor.visit_seq(LimitedSeq {
+            seq,
+            tracker: self.tracker,
+            count: self.collection.then_some(0),
+        });
+        self.tracker.exit();
+        value
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        self.tracker.enter()?;
+        if let Some(len) = map.size_hint().filter(|_| self.collection) {
+            self.check_collection(len)?;
+        }
+        let value = self.visitor.visit_map(LimitedMap {
+            map,
+            tracker: self.tracker,
+            count: self.collection.then_some(0),
+        });
+        self.tracker.exit();
+        value
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        self.tracker.enter()?;
+        let value = self.visitor.visit_enum(LimitedEnum {
+            data,
+            tracker: self.tracker,
+        });
+        self.tracker.exit();
+        value
+    }
+}
+
+/// `count` is `None` for struct and tuple fields, which are not counted.
+struct LimitedSeq<'t, 'l, A> {
+    seq: A,
+    tracker: &'t Tracker<'l>,
+    count: Option<usize>,
+}
+
+impl<'de, 't, 'l, A> SeqAccess<'de> for LimitedSeq<'t, 'l, A>
+where
+    A: SeqAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let element = self.seq.next_element_seed(LimitedSeed {
+            seed,
+            tracker: self.tracker,
+        })?;
+        if let (Some(_), Some(count)) = (&element, &mut self.count) {
+            *count += 1;
+            let max = self.tracker.limits.max_collection_len;
+            self.tracker
+                .check(Limit::CollectionLength, max, *count as u64)?;
+        }
+        Ok(element)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.seq.size_hint()
+    }
+}
+
+struct LimitedMap<'t, 'l, A> {
+    map: A,
+    tracker: &'t Tracker<'l>,
+    count: Option<usize>,
+}
+
+impl<'de, 't, 'l, A> MapAccess<'de> for LimitedMap<'t, 'l, A>
+where
+    A: MapAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let key = self.map.next_key_seed(LimitedSeed {
+            seed,
+            tracker: self.tracker,
+        })?;
+        if let (Some(_), Some(count)) = (&key, &mut self.count) {
+            *count += 1;
+            let max = self.tracker.limits.max_collection_len;
+            self.tracker
+                .check(Limit::CollectionLength, max, *count as u64)?;
+        }
+        Ok(key)
+    }
+
+    fn next_value_seed<S>(&mut self, seed: S) -> Result<S::Value, Self::Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        self.map.next_value_seed(LimitedSeed {
+            seed,
+            tracker: self.tracker,
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.map.size_hint()
+    }
+}
+
+struct LimitedEnum<'t, 'l, A> {
+    data: A,
+    tracker: &'t Tracker<'l>,
+}
+
+impl<'de, 't, 'l, A> EnumAccess<'de> for LimitedEnum<'t, 'l, A>
+where
+    A: EnumAccess<'de>,
+{
+    type Error = A::Error;
+    type Variant = LimitedVariant<'t, 'l, A::Variant>;
+
+    fn variant_seed<S>(self, seed: S) -> Result<(S::Value, Self::Variant), Self::Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        let (value, variant) = self.data.variant_seed(LimitedSeed {
+            seed,
+            tracker: self.tracker,
+        })?;
+        Ok((
+            value,
+            LimitedVariant {
+                variant,
+                tracker: self.tracker,
+            },
+        ))
+    }
+}
+
+struct LimitedVariant<'t, 'l, A> {
+    variant: A,
+    tracker: &'t Tracker<'l>,
+}
+
+impl<'de, 't, 'l, A> VariantAccess<'de> for LimitedVariant<'t, 'l, A>
+where
+    A: VariantAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        self.variant.unit_variant()
+    }
+
+    fn newtype_variant_seed<S>(self, seed: S) -> Result<S::Value, Self::Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        self.variant.newtype_variant_seed(LimitedSeed {
+            seed,
+            tracker: self.tracker,
+        })
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.variant.tuple_variant(
+            len,
+            LimitedVisitor {
+                visitor,
+                tracker: self.tracker,
+                collection: false,
+            },
+        )
+    }
+
+    fn struct_variant<V>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.variant.struct_variant(
+            fields,
+            LimitedVisitor {
+                visitor,
+                tracker: self.tracker,
+                collection: false,
+            },
+        )
+    }
+}
@@ -1,7 +1,10 @@
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+/// Its [`Schema`](crate::schema::Schema) comes from the `BorshSchema` derive.
+#[derive(
+    Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema, Serialize, Deserialize,
+)]
 pub struct Person {
     pub name: String,
     pub age: u32,
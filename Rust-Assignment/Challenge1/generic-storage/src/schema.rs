@@ -0,0 +1,310 @@
+use std::fmt;
+
+use borsh::schema::{BorshSchemaContainer, Declaration, Definition, Fields};
+use borsh::BorshSchema;
+use serde_json::{json, Map, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldType {
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    String,
+    Vec(Box<FieldType>),
+    Option(Box<FieldType>),
+    Struct(ModelSchema),
+}
+
+impl FieldType {
+    /// Type in the schema language of the JS `borsh` package; nested structs
+    /// are named and described by their own schema.
+    pub fn borsh_schema(&self) -> Value {
+        match self {
+            FieldType::Bool => json!("bool"),
+            FieldType::U8 => json!("u8"),
+            FieldType::U16 => json!("u16"),
+            FieldType::U32 => json!("u32"),
+            FieldType::U64 => json!("u64"),
+            FieldType::I8 => json!("i8"),
+            FieldType::I16 => json!("i16"),
+            FieldType::I32 => json!("i32"),
+            FieldType::I64 => json!("i64"),
+            FieldType::String => json!("string"),
+            FieldType::Vec(item) => json!([item.borsh_schema()]),
+            FieldType::Option(item) => json!({ "kind": "option", "type": item.borsh_schema() }),
+            FieldType::Struct(schema) => json!(schema.name),
+        }
+    }
+
+    fn json_schema(&self) -> Value {
+        match self {
+            FieldType::Bool => json!({ "type": "boolean" }),
+            FieldType::U8 => json!({ "type": "integer", "minimum": 0, "maximum": u8::MAX }),
+            FieldType::U16 => json!({ "type": "integer", "minimum": 0, "maximum": u16::MAX }),
+            FieldType::U32 => json!({ "type": "integer", "minimum": 0, "maximum": u32::MAX }),
+            // 64-bit integers travel as decimal strings; see `json_string_int`.
+            FieldType::U64 => json!({ "type": "string", "pattern": "^[0-9]+$" }),
+            FieldType::I8 => json!({ "type": "integer", "minimum": i8::MIN, "maximum": i8::MAX }),
+            FieldType::I16 => {
+                json!({ "type": "integer", "minimum": i16::MIN, "maximum": i16::MAX })
+            }
+            FieldType::I32 => {
+                json!({ "type": "integer", "minimum": i32::MIN, "maximum": i32::MAX })
+            }
+            FieldType::I64 => json!({ "type": "string", "pattern": "^-?[0-9]+$" }),
+            FieldType::String => json!({ "type": "string" }),
+            FieldType::Vec(item) => json!({ "type": "array", "items": item.json_schema() }),
+            FieldType::Option(item) => json!({ "anyOf": [item.json_schema(), { "type": "null" }] }),
+            FieldType::Struct(schema) => json!({ "$ref": format!("#/$defs/{}", schema.name) }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub ty: FieldType,
+}
+
+/// Description of a stored model, listing its fields in serialization order.
+///
+/// Built from the model's [`BorshSchema`] derive, so it cannot drift from the
+/// type definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSchema {
+    pub name: String,
+    pub fields: Vec<Field>,
+}
+
+impl ModelSchema {
+    /// Schema of the struct `T`, with named fields of the types in [`FieldType`].
+    pub fn of<T: BorshSchema>() -> Result<Self, UnsupportedType> {
+        let container = BorshSchemaContainer::for_type::<T>();
+        let model = T::declaration();
+        Self::from_container(&container, &model, &model)
+    }
+
+    fn from_container(
+        container: &BorshSchemaContainer,
+        model: &Declaration,
+        declaration: &Declaration,
+    ) -> Result<Self, UnsupportedType> {
+        let Some(Definition::Struct {
+            fields: Fields::NamedFields(fields),
+        }) = container.get_definition(declaration)
+        else {
+            return Err(UnsupportedType::new(model, declaration));
+        };
+
+        let fields = fields
+            .iter()
+            .map(|(name, ty)| {
+                Ok(Field {
+                    name: name.clone(),
+                    ty: field_type(container, model, ty)?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(ModelSchema {
+            name: declaration.clone(),
+            fields,
+        })
+    }
+
+    /// Every struct nested in this one, innermost first and each listed once.
+    pub fn nested(&self) -> Vec<&ModelSchema> {
+        fn visit<'a>(ty: &'a FieldType, out: &mut Vec<&'a ModelSchema>) {
+            match ty {
+                FieldType::Vec(item) | FieldType::Option(item) => visit(item, out),
+                FieldType::Struct(schema) => {
+                    for field in &schema.fields {
+                        visit(&field.ty, out);
+                    }
+                    if !out.iter().any(|seen| seen.name == schema.name) {
+                        out.push(schema);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut out = Vec::new();
+        for field in &self.fields {
+            visit(&field.ty, &mut out);
+        }
+        out
+    }
+
+    /// Borsh schema in the `{ kind: "struct", fields: [[name, type], ...] }` layout
+    /// understood by the JS `borsh` package.
+    pub fn to_borsh_schema(&self) -> Value {
+        let fields: Vec<Value> = self
+            .fields
+            .iter()
+            .map(|f| json!([f.name, f.ty.borsh_schema()]))
+            .collect();
+
+        json!({
+            "name": self.name,
+            "kind": "struct",
+            "fields": fields,
+        })
+    }
+
+    pub fn to_json_schema(&self) -> Value {
+        let mut schema = self.json_object();
+        let nested = self.nested();
+        if !nested.is_empty() {
+            let defs: Map<String, Value> = nested
+                .iter()
+                .map(|nested| (nested.name.clone(), nested.json_object()))
+                .collect();
+            schema["$defs"] = Value::Object(defs);
+        }
+        schema["$schema"] = json!("https://json-schema.org/draft/2020-12/schema");
+        schema
+    }
+
+    fn json_object(&self) -> Value {
+        let mut properties = Map::new();
+        for f in &self.fields {
+            properties.insert(f.name.clone(), f.ty.json_schema());
+        }
+        let required: Vec<&str> = self.fields.iter().map(|f| f.name.as_str()).collect();
+
+        json!({
+            "title": self.name,
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        })
+    }
+}
+
+fn field_type(
+    container: &BorshSchemaContainer,
+    model: &Declaration,
+    declaration: &Declaration,
+) -> Result<FieldType, UnsupportedType> {
+    let ty = match declaration.as_str() {
+        "bool" => FieldType::Bool,
+        "u8" => FieldType::U8,
+        "u16" => FieldType::U16,
+        "u32" => FieldType::U32,
+        "u64" => FieldType::U64,
+        "i8" => FieldType::I8,
+        "i16" => FieldType::I16,
+        "i32" => FieldType::I32,
+        "i64" => FieldType::I64,
+        "String" => FieldType::String,
+        _ => match container.get_definition(declaration) {
+            Some(Definition::Sequence {
+                length_width: Definition::DEFAULT_LENGTH_WIDTH,
+                elements,
+                ..
+            }) if declaration.starts_with("Vec<") => {
+                FieldType::Vec(Box::new(field_type(container, model, elements)?))
+            }
+            Some(Definition::Enum {
+                tag_width: 1,
+                variants,
+            }) if declaration.starts_with("Option<") => {
+                let item = &variants[1].2;
+                FieldType::Option(Box::new(field_type(container, model, item)?))
+            }
+            Some(Definition::Struct { .. }) => {
+                FieldType::Struct(ModelSchema::from_container(container, model, declaration)?)
+            }
+            _ => return Err(UnsupportedType::new(model, declaration)),
+        },
+    };
+    Ok(ty)
+}
+
+/// A model, or a type inside it, that the schema and codegen cannot describe,
+/// such as an enum, tuple, map or fixed-size array.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedType {
+    pub model: String,
+    pub declaration: String,
+}
+
+impl UnsupportedType {
+    fn new(model: &Declaration, declaration: &Declaration) -> Self {
+        UnsupportedType {
+            model: model.clone(),
+            declaration: declaration.clone(),
+        }
+    }
+}
+
+impl fmt::Display for UnsupportedType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: unsupported type {}; models must be structs with named fields of primitives, String, Vec, Option or such structs",
+            self.model, self.declaration
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedType {}
+
+/// Implemented by models that can describe their own layout for other
+/// languages; every [`BorshSchema`] type gets it from its derive.
+pub trait Schema {
+    fn schema() -> Result<ModelSchema, UnsupportedType>;
+}
+
+impl<T: BorshSchema> Schema for T {
+    fn schema() -> Result<ModelSchema, UnsupportedType> {
+        ModelSchema::of::<T>()
+    }
+}
+
+/// Serde adapter that writes an integer as a decimal string in human-readable
+/// formats such as JSON, and unchanged in binary ones.
+///
+/// JSON numbers above 2^53 lose precision in JavaScript, so every `u64`/`i64`
+/// field of a model must use `#[serde(with = "json_string_int")]`; the JSON
+/// schema and the generated TypeScript decoder expect strings for them.
+pub mod json_string_int {
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Display + Serialize,
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.collect_str(value)
+        } else {
+            value.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr + Deserialize<'de>,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            s.parse().map_err(de::Error::custom)
+        } else {
+            T::deserialize(deserializer)
+        }
+    }
+}
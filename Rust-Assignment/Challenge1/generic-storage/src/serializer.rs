@@ -1,66 +1,119 @@
-use borsh::{BorshDeserialize, BorshSerialize};
-use serde::{de::DeserializeOwned, Serialize};
+use bincode::Options;
+use borsh::schema::BorshSchemaContainer;
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-pub trait Serializer {
-    fn to_bytes<T>(&self, value: &T) -> Result<Vec<u8>, Box<dyn std::error::Error>>
-    where
-        T: BorshSerialize + Serialize;
+use crate::limits::{BincodeSlice, BorshScan, DecodeLimits, Limited, Tracker};
 
-    fn from_bytes<T>(&self, bytes: &[u8]) -> Result<T, Box<dyn std::error::Error>>
-    where
-        T: BorshDeserialize + DeserializeOwned;
+/// A wire format for values of type `T`.
+///
+/// Each format states the traits it needs from `T` on its own impl, so only
+/// Borsh asks for a [`BorshSchema`].
+pub trait Serializer<T> {
+    fn to_bytes(&self, value: &T) -> Result<Vec<u8>, Box<dyn std::error::Error>>;
+
+    /// Decodes `bytes` under the default [`DecodeLimits`].
+    #[allow(clippy::wrong_self_convention)]
+    fn from_bytes(&self, bytes: &[u8]) -> Result<T, Box<dyn std::error::Error>> {
+        self.from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    /// Decodes `bytes`, failing with [`LimitExceeded`](crate::limits::LimitExceeded)
+    /// as soon as the payload goes over any of `limits`.
+    #[allow(clippy::wrong_self_convention)]
+    fn from_bytes_with_limits(
+        &self,
+        bytes: &[u8],
+        limits: &DecodeLimits,
+    ) -> Result<T, Box<dyn std::error::Error>>;
+}
+
+/// Runs `T::deserialize` through [`Limited`] so every serde format shares the same checks.
+fn deserialize_limited<'de, D, T>(de: D, tracker: &Tracker) -> Result<T, Box<dyn std::error::Error>>
+where
+    D: serde::Deserializer<'de>,
+    D::Error: std::error::Error + 'static,
+    T: Deserialize<'de>,
+{
+    T::deserialize(Limited::new(de, tracker)).map_err(|err| tracker.error(Box::new(err)))
 }
 
 pub struct Borsh;
 
-impl Serializer for Borsh {
-    fn to_bytes<T>(&self, value: &T) -> Result<Vec<u8>, Box<dyn std::error::Error>>
-    where
-        T: BorshSerialize + Serialize,
-    {
+impl<T> Serializer<T> for Borsh
+where
+    T: BorshSerialize + BorshDeserialize + BorshSchema,
+{
+    fn to_bytes(&self, value: &T) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
         Ok(borsh::to_vec(value)?)
     }
 
-    fn from_bytes<T>(&self, bytes: &[u8]) -> Result<T, Box<dyn std::error::Error>>
-    where
-        T: BorshDeserialize + DeserializeOwned,
-    {
+    fn from_bytes_with_limits(
+        &self,
+        bytes: &[u8],
+        limits: &DecodeLimits,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        limits.check_bytes(bytes.len())?;
+
+        // Borsh is not self-describing, so the schema drives a pre-scan of the
+        // length prefixes before any allocation happens.
+        let container = BorshSchemaContainer::for_type::<T>();
+        BorshScan::new(&container, bytes, limits).run()?;
+
         Ok(borsh::from_slice(bytes)?)
     }
 }
 
 pub struct Bincode;
 
-impl Serializer for Bincode {
-    fn to_bytes<T>(&self, value: &T) -> Result<Vec<u8>, Box<dyn std::error::Error>>
-    where
-        T: BorshSerialize + Serialize,
-    {
+impl<T> Serializer<T> for Bincode
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn to_bytes(&self, value: &T) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
         Ok(bincode::serialize(value)?)
     }
 
-    fn from_bytes<T>(&self, bytes: &[u8]) -> Result<T, Box<dyn std::error::Error>>
-    where
-        T: BorshDeserialize + DeserializeOwned,
-    {
-        Ok(bincode::deserialize(bytes)?)
+    fn from_bytes_with_limits(
+        &self,
+        bytes: &[u8],
+        limits: &DecodeLimits,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        limits.check_bytes(bytes.len())?;
+
+        // Same encoding as `bincode::deserialize`.
+        let options = bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes();
+        let tracker = Tracker::new(limits);
+        let mut de =
+            bincode::Deserializer::with_bincode_read(BincodeSlice::new(bytes, &tracker), options);
+
+        deserialize_limited(&mut de, &tracker)
     }
 }
 
 pub struct Json;
 
-impl Serializer for Json {
-    fn to_bytes<T>(&self, value: &T) -> Result<Vec<u8>, Box<dyn std::error::Error>>
-    where
-        T: BorshSerialize + Serialize,
-    {
+impl<T> Serializer<T> for Json
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn to_bytes(&self, value: &T) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
         Ok(serde_json::to_vec(value)?)
     }
 
-    fn from_bytes<T>(&self, bytes: &[u8]) -> Result<T, Box<dyn std::error::Error>>
-    where
-        T: BorshDeserialize + DeserializeOwned,
-    {
-        Ok(serde_json::from_slice(bytes)?)
+    fn from_bytes_with_limits(
+        &self,
+        bytes: &[u8],
+        limits: &DecodeLimits,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        limits.check_bytes(bytes.len())?;
+
+        let tracker = Tracker::new(limits);
+        let mut de = serde_json::Deserializer::from_slice(bytes);
+        let value = deserialize_limited(&mut de, &tracker)?;
+        de.end()?;
+        Ok(value)
     }
 }
@@ -1,28 +1,30 @@
 use std::marker::PhantomData;
 
-use borsh::{BorshDeserialize, BorshSerialize};
-use serde::{de::DeserializeOwned, Serialize};
-
+use crate::limits::DecodeLimits;
 use crate::serializer::Serializer;
 
 pub struct Storage<T, S>
 where
-    T: BorshSerialize + BorshDeserialize + Serialize + DeserializeOwned,
-    S: Serializer,
+    S: Serializer<T>,
 {
     serializer: S,
+    limits: DecodeLimits,
     data: Option<Vec<u8>>,
     _marker: PhantomData<T>,
 }
 
 impl<T, S> Storage<T, S>
 where
-    T: BorshSerialize + BorshDeserialize + Serialize + DeserializeOwned,
-    S: Serializer,
+    S: Serializer<T>,
 {
     pub fn new(serializer: S) -> Self {
+        Self::with_limits(serializer, DecodeLimits::default())
+    }
+
+    pub fn with_limits(serializer: S, limits: DecodeLimits) -> Self {
         Storage {
             serializer,
+            limits,
             data: None,
             _marker: PhantomData,
         }
@@ -36,7 +38,7 @@ where
 
     pub fn load(&self) -> Result<T, Box<dyn std::error::Error>> {
         match &self.data {
-            Some(bytes) => self.serializer.from_bytes(bytes),
+            Some(bytes) => self.serializer.from_bytes_with_limits(bytes, &self.limits),
             None => Err("No data stored".into()),
         }
     }
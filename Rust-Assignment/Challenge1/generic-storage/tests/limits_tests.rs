@@ -0,0 +1,233 @@
+use std::fmt;
+
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use serde::de::{Deserializer, Visitor};
+use serde::{Deserialize, Serialize};
+
+use generic_storage::limits::{DecodeLimits, Limit, LimitExceeded};
+use generic_storage::models::Person;
+use generic_storage::serializer::{Bincode, Borsh, Json, Serializer};
+use generic_storage::storage::Storage;
+
+fn limit_of(err: Box<dyn std::error::Error>) -> LimitExceeded {
+    *err.downcast::<LimitExceeded>()
+        .expect("expected a LimitExceeded error")
+}
+
+#[derive(
+    Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema, Serialize, Deserialize,
+)]
+struct Team {
+    members: Vec<String>,
+}
+
+/// Bytes that bincode reads as a borrowed byte slice (`deserialize_bytes`) or,
+/// with `OWNED`, as an owned buffer (`deserialize_byte_buf`).
+#[derive(Debug, PartialEq)]
+struct Blob<const OWNED: bool>(Vec<u8>);
+
+impl<const OWNED: bool> Serialize for Blob<OWNED> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de, const OWNED: bool> Deserialize<'de> for Blob<OWNED> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BlobVisitor;
+
+        impl Visitor<'_> for BlobVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("bytes")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+                Ok(v.to_vec())
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+                Ok(v)
+            }
+        }
+
+        let bytes = if OWNED {
+            deserializer.deserialize_byte_buf(BlobVisitor)?
+        } else {
+            deserializer.deserialize_bytes(BlobVisitor)?
+        };
+        Ok(Blob(bytes))
+    }
+}
+
+fn long_person() -> Person {
+    Person {
+        name: "x".repeat(100),
+        age: 25,
+    }
+}
+
+#[test]
+fn test_borsh_forged_string_length() {
+    // u32::MAX length prefix followed by nothing
+    let bytes = [0xff, 0xff, 0xff, 0xff];
+    let err = Serializer::<Person>::from_bytes(&Borsh, &bytes).unwrap_err();
+    let exceeded = limit_of(err);
+    assert_eq!(exceeded.limit, Limit::StringLength);
+    assert_eq!(exceeded.actual, u32::MAX as u64);
+}
+
+#[test]
+fn test_bincode_forged_string_length() {
+    // u64::MAX length prefix followed by nothing
+    let bytes = [0xff; 8];
+    let err = Serializer::<Person>::from_bytes(&Bincode, &bytes).unwrap_err();
+    let exceeded = limit_of(err);
+    assert_eq!(exceeded.limit, Limit::StringLength);
+    assert_eq!(exceeded.actual, u64::MAX);
+}
+
+#[test]
+fn test_string_limit_on_every_format() {
+    let person = long_person();
+    let limits = DecodeLimits::default().max_string_len(10);
+
+    let bytes = Borsh.to_bytes(&person).unwrap();
+    let err = Serializer::<Person>::from_bytes_with_limits(&Borsh, &bytes, &limits).unwrap_err();
+    assert_eq!(limit_of(err).limit, Limit::StringLength);
+
+    let bytes = Bincode.to_bytes(&person).unwrap();
+    let err = Serializer::<Person>::from_bytes_with_limits(&Bincode, &bytes, &limits).unwrap_err();
+    assert_eq!(limit_of(err).limit, Limit::StringLength);
+
+    let bytes = Json.to_bytes(&person).unwrap();
+    let err = Serializer::<Person>::from_bytes_with_limits(&Json, &bytes, &limits).unwrap_err();
+    assert_eq!(limit_of(err).limit, Limit::StringLength);
+}
+
+#[test]
+fn test_total_bytes_limit() {
+    let person = long_person();
+    let limits = DecodeLimits::default().max_bytes(16);
+
+    let bytes = Json.to_bytes(&person).unwrap();
+    let exceeded =
+        limit_of(Serializer::<Person>::from_bytes_with_limits(&Json, &bytes, &limits).unwrap_err());
+    assert_eq!(exceeded.limit, Limit::TotalBytes);
+    assert_eq!(exceeded.max, 16);
+    assert_eq!(exceeded.actual, bytes.len() as u64);
+}
+
+#[test]
+fn test_depth_limit() {
+    let person = long_person();
+    let limits = DecodeLimits::default().max_depth(0);
+
+    let bytes = Borsh.to_bytes(&person).unwrap();
+    let err = Serializer::<Person>::from_bytes_with_limits(&Borsh, &bytes, &limits).unwrap_err();
+    assert_eq!(limit_of(err).limit, Limit::Depth);
+
+    let bytes = Json.to_bytes(&person).unwrap();
+    let err = Serializer::<Person>::from_bytes_with_limits(&Json, &bytes, &limits).unwrap_err();
+    assert_eq!(limit_of(err).limit, Limit::Depth);
+}
+
+#[test]
+fn test_collection_limit() {
+    let limits = DecodeLimits::default().max_collection_len(1);
+    let team = Team {
+        members: vec!["Alice".to_string(), "Bob".to_string()],
+    };
+
+    let bytes = Borsh.to_bytes(&team).unwrap();
+    let exceeded =
+        limit_of(Serializer::<Team>::from_bytes_with_limits(&Borsh, &bytes, &limits).unwrap_err());
+    assert_eq!(
+        (exceeded.limit, exceeded.actual),
+        (Limit::CollectionLength, 2)
+    );
+
+    let bytes = Bincode.to_bytes(&team).unwrap();
+    let exceeded = limit_of(
+        Serializer::<Team>::from_bytes_with_limits(&Bincode, &bytes, &limits).unwrap_err(),
+    );
+    assert_eq!(
+        (exceeded.limit, exceeded.actual),
+        (Limit::CollectionLength, 2)
+    );
+
+    let bytes = Json.to_bytes(&team).unwrap();
+    let exceeded =
+        limit_of(Serializer::<Team>::from_bytes_with_limits(&Json, &bytes, &limits).unwrap_err());
+    assert_eq!(
+        (exceeded.limit, exceeded.actual),
+        (Limit::CollectionLength, 2)
+    );
+}
+
+#[test]
+fn test_struct_fields_are_not_collection_items() {
+    let limits = DecodeLimits::default().max_collection_len(1);
+    let person = Person {
+        name: "Alice".to_string(),
+        age: 25,
+    };
+
+    // Two fields, whether they arrive as a JSON array, a JSON object or bincode's sequence
+    let loaded: Person =
+        Serializer::<Person>::from_bytes_with_limits(&Json, br#"["Alice", 25]"#, &limits).unwrap();
+    assert_eq!(loaded, person);
+
+    let bytes = Json.to_bytes(&person).unwrap();
+    let loaded = Serializer::<Person>::from_bytes_with_limits(&Json, &bytes, &limits).unwrap();
+    assert_eq!(loaded, person);
+
+    let bytes = Bincode.to_bytes(&person).unwrap();
+    let loaded = Serializer::<Person>::from_bytes_with_limits(&Bincode, &bytes, &limits).unwrap();
+    assert_eq!(loaded, person);
+}
+
+#[test]
+fn test_bincode_byte_buffers_use_string_limit() {
+    let limits = DecodeLimits::default()
+        .max_string_len(10)
+        .max_collection_len(1_000);
+
+    let bytes = Bincode.to_bytes(&Blob::<false>(vec![7; 100])).unwrap();
+    let err =
+        Serializer::<Blob<false>>::from_bytes_with_limits(&Bincode, &bytes, &limits).unwrap_err();
+    assert_eq!(limit_of(err).limit, Limit::StringLength);
+
+    let bytes = Bincode.to_bytes(&Blob::<true>(vec![7; 100])).unwrap();
+    let err =
+        Serializer::<Blob<true>>::from_bytes_with_limits(&Bincode, &bytes, &limits).unwrap_err();
+    assert_eq!(limit_of(err).limit, Limit::StringLength);
+
+    // Within the string limit, a buffer longer than the collection limit still decodes
+    let limits = DecodeLimits::default().max_collection_len(1);
+    let bytes = Bincode.to_bytes(&Blob::<false>(vec![7; 5])).unwrap();
+    let loaded =
+        Serializer::<Blob<false>>::from_bytes_with_limits(&Bincode, &bytes, &limits).unwrap();
+    assert_eq!(loaded, Blob(vec![7; 5]));
+
+    let loaded =
+        Serializer::<Blob<true>>::from_bytes_with_limits(&Bincode, &bytes, &limits).unwrap();
+    assert_eq!(loaded, Blob(vec![7; 5]));
+}
+
+#[test]
+fn test_storage_with_limits() {
+    let mut storage = Storage::with_limits(Bincode, DecodeLimits::default().max_string_len(10));
+
+    storage.save(&long_person()).unwrap();
+    let err = storage.load().unwrap_err();
+    assert_eq!(limit_of(err).limit, Limit::StringLength);
+
+    let short = Person {
+        name: "Alice".to_string(),
+        age: 25,
+    };
+    storage.save(&short).unwrap();
+    assert_eq!(storage.load().unwrap(), short);
+}
@@ -0,0 +1,327 @@
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use generic_storage::codegen;
+use generic_storage::models::Person;
+use generic_storage::schema::{json_string_int, FieldType, ModelSchema, Schema};
+use generic_storage::serializer::{Bincode, Borsh, Json, Serializer};
+
+#[derive(
+    Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema, Serialize, Deserialize,
+)]
+struct Ledger {
+    #[serde(with = "json_string_int")]
+    id: u64,
+    #[serde(with = "json_string_int")]
+    delta: i64,
+    memo: String,
+}
+
+#[derive(
+    Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema, Serialize, Deserialize,
+)]
+struct Account {
+    owner: Person,
+    tags: Vec<String>,
+    nickname: Option<String>,
+    history: Vec<Ledger>,
+    limit: Option<u16>,
+}
+
+// Enums have no codegen; these only exist to have their schema rejected
+#[allow(dead_code)]
+#[derive(BorshSchema)]
+enum Status {
+    Open,
+    Closed,
+}
+
+#[allow(dead_code)]
+#[derive(BorshSchema)]
+struct Tracked {
+    status: Status,
+}
+
+fn ledger() -> Ledger {
+    Ledger {
+        id: u64::MAX,
+        delta: i64::MIN,
+        memo: "refund".to_string(),
+    }
+}
+
+fn account() -> Account {
+    Account {
+        owner: Person {
+            name: "Alice".to_string(),
+            age: 25,
+        },
+        tags: vec!["vip".to_string(), "beta".to_string()],
+        nickname: None,
+        history: vec![ledger(), ledger()],
+        limit: Some(500),
+    }
+}
+
+/// Decodes binary input the way the generated TypeScript `Reader` does, into
+/// the JSON form of each value. Borsh and bincode differ only in the width of
+/// length prefixes.
+struct Walker<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    length_prefix: usize,
+}
+
+impl Walker<'_> {
+    fn take(&mut self, size: usize) -> &[u8] {
+        let chunk = &self.bytes[self.offset..self.offset + size];
+        self.offset += size;
+        chunk
+    }
+
+    fn len(&mut self) -> usize {
+        let mut len = [0u8; 8];
+        let prefix = self.length_prefix;
+        len[..prefix].copy_from_slice(self.take(prefix));
+        u64::from_le_bytes(len) as usize
+    }
+
+    fn read(&mut self, ty: &FieldType) -> Value {
+        match ty {
+            FieldType::Bool => Value::Bool(self.take(1)[0] == 1),
+            FieldType::U8 => self.take(1)[0].into(),
+            FieldType::U16 => u16::from_le_bytes(self.take(2).try_into().unwrap()).into(),
+            FieldType::U32 => u32::from_le_bytes(self.take(4).try_into().unwrap()).into(),
+            FieldType::U64 => u64::from_le_bytes(self.take(8).try_into().unwrap())
+                .to_string()
+                .into(),
+            FieldType::I8 => (self.take(1)[0] as i8).into(),
+            FieldType::I16 => i16::from_le_bytes(self.take(2).try_into().unwrap()).into(),
+            FieldType::I32 => i32::from_le_bytes(self.take(4).try_into().unwrap()).into(),
+            FieldType::I64 => i64::from_le_bytes(self.take(8).try_into().unwrap())
+                .to_string()
+                .into(),
+            FieldType::String => {
+                let len = self.len();
+                String::from_utf8(self.take(len).to_vec()).unwrap().into()
+            }
+            FieldType::Vec(item) => {
+                let len = self.len();
+                Value::Array((0..len).map(|_| self.read(item)).collect())
+            }
+            FieldType::Option(item) => match self.take(1)[0] {
+                0 => Value::Null,
+                1 => self.read(item),
+                tag => panic!("invalid option tag {}", tag),
+            },
+            FieldType::Struct(schema) => self.read_struct(schema),
+        }
+    }
+
+    fn read_struct(&mut self, schema: &ModelSchema) -> Value {
+        let fields = schema
+            .fields
+            .iter()
+            .map(|f| (f.name.clone(), self.read(&f.ty)))
+            .collect();
+        Value::Object(fields)
+    }
+}
+
+fn decode_binary(schema: &ModelSchema, bytes: &[u8], length_prefix: usize) -> Value {
+    let mut walker = Walker {
+        bytes,
+        offset: 0,
+        length_prefix,
+    };
+    let value = walker.read_struct(schema);
+    assert_eq!(
+        walker.offset,
+        bytes.len(),
+        "{} leaves trailing bytes",
+        schema.name
+    );
+    value
+}
+
+/// Checks that the schema derived for `T` describes what every serializer
+/// actually writes for `value`: decoding the Borsh and Bincode bytes by the
+/// schema yields the JSON serialization, field for field.
+fn assert_schema_matches<T>(value: &T)
+where
+    T: Schema,
+    Borsh: Serializer<T>,
+    Bincode: Serializer<T>,
+    Json: Serializer<T>,
+{
+    let schema = T::schema().unwrap();
+    let json: Value = serde_json::from_slice(&Json.to_bytes(value).unwrap()).unwrap();
+
+    assert_eq!(
+        decode_binary(&schema, &Borsh.to_bytes(value).unwrap(), 4),
+        json
+    );
+    assert_eq!(
+        decode_binary(&schema, &Bincode.to_bytes(value).unwrap(), 8),
+        json
+    );
+}
+
+#[test]
+fn test_person_schema_fields_in_order() {
+    let schema = Person::schema().unwrap();
+    assert_eq!(schema.name, "Person");
+    assert_eq!(schema.fields.len(), 2);
+    assert_eq!(schema.fields[0].name, "name");
+    assert_eq!(schema.fields[0].ty, FieldType::String);
+    assert_eq!(schema.fields[1].name, "age");
+    assert_eq!(schema.fields[1].ty, FieldType::U32);
+}
+
+#[test]
+fn test_borsh_schema() {
+    let schema = Person::schema().unwrap().to_borsh_schema();
+    assert_eq!(schema["kind"], "struct");
+    assert_eq!(schema["fields"][0][0], "name");
+    assert_eq!(schema["fields"][0][1], "string");
+    assert_eq!(schema["fields"][1][0], "age");
+    assert_eq!(schema["fields"][1][1], "u32");
+}
+
+#[test]
+fn test_json_schema() {
+    let schema = Person::schema().unwrap().to_json_schema();
+    assert_eq!(schema["title"], "Person");
+    assert_eq!(schema["properties"]["name"]["type"], "string");
+    assert_eq!(schema["properties"]["age"]["maximum"], u32::MAX);
+    assert_eq!(schema["required"], serde_json::json!(["name", "age"]));
+}
+
+#[test]
+fn test_typescript_output() {
+    let ts = codegen::typescript(&[Person::schema().unwrap()]);
+
+    assert!(ts.contains("export interface Person {\n  name: string;\n  age: number;\n}"));
+    assert!(ts.contains("export function decodePersonBorsh(bytes: Uint8Array): Person"));
+    assert!(ts.contains("export function decodePersonBincode(bytes: Uint8Array): Person"));
+    assert!(ts.contains("export function decodePersonJson(bytes: Uint8Array): Person"));
+    assert!(ts.contains("new Reader(bytes, 4)"));
+    assert!(ts.contains("new Reader(bytes, 8)"));
+    assert!(ts.contains("    name: r.string(),\n    age: r.u32(),"));
+}
+
+#[test]
+fn test_schemas_match_serialized_bytes() {
+    assert_schema_matches(&Person {
+        name: "Alice".to_string(),
+        age: 25,
+    });
+    assert_schema_matches(&ledger());
+    assert_schema_matches(&account());
+}
+
+#[test]
+fn test_schema_follows_nested_types() {
+    let schema = Account::schema().unwrap();
+    let person = Person::schema().unwrap();
+    let ledger_schema = Ledger::schema().unwrap();
+
+    let types: Vec<(&str, &FieldType)> = schema
+        .fields
+        .iter()
+        .map(|f| (f.name.as_str(), &f.ty))
+        .collect();
+    assert_eq!(
+        types,
+        [
+            ("owner", &FieldType::Struct(person.clone())),
+            ("tags", &FieldType::Vec(Box::new(FieldType::String))),
+            ("nickname", &FieldType::Option(Box::new(FieldType::String))),
+            (
+                "history",
+                &FieldType::Vec(Box::new(FieldType::Struct(ledger_schema.clone())))
+            ),
+            ("limit", &FieldType::Option(Box::new(FieldType::U16))),
+        ]
+    );
+    assert_eq!(schema.nested(), [&person, &ledger_schema]);
+
+    let borsh = schema.to_borsh_schema();
+    assert_eq!(borsh["fields"][0][1], "Person");
+    assert_eq!(borsh["fields"][1][1], serde_json::json!(["string"]));
+    assert_eq!(
+        borsh["fields"][4][1],
+        serde_json::json!({ "kind": "option", "type": "u16" })
+    );
+
+    let json = schema.to_json_schema();
+    assert_eq!(json["properties"]["owner"]["$ref"], "#/$defs/Person");
+    assert_eq!(
+        json["properties"]["history"]["items"]["$ref"],
+        "#/$defs/Ledger"
+    );
+    assert_eq!(
+        json["$defs"]["Ledger"]["properties"]["id"]["type"],
+        "string"
+    );
+}
+
+#[test]
+fn test_unsupported_types_are_rejected() {
+    let err = Tracked::schema().unwrap_err();
+    assert_eq!(
+        (err.model.as_str(), err.declaration.as_str()),
+        ("Tracked", "Status")
+    );
+    assert!(Status::schema().is_err());
+}
+
+#[test]
+fn test_typescript_nested_decoders() {
+    let ts = codegen::typescript(&[Account::schema().unwrap()]);
+
+    // Nested structs get an interface and readers once, before their parent
+    assert_eq!(ts.matches("export interface Person {").count(), 1);
+    assert!(ts.find("function readLedger(").unwrap() < ts.find("function readAccount(").unwrap());
+    assert!(ts.contains("  history: Array<Ledger>;\n"));
+    assert!(ts.contains("  nickname: string | null;\n"));
+    assert!(ts.contains(
+        "    owner: readPerson(r),\n    tags: r.vec(() => r.string()),\n    nickname: r.option(() => r.string()),\n"
+    ));
+    assert!(ts
+        .contains("    history: r.vec(() => readLedger(r)),\n    limit: r.option(() => r.u16()),"));
+    assert!(ts.contains(
+        "    history: (raw.history as Array<unknown>).map((item0) => fromJsonLedger(item0 as Record<string, unknown>)),"
+    ));
+    assert!(ts.contains("    limit: raw.limit === null ? null : raw.limit as number,"));
+    // Only models get top-level decoders
+    assert!(ts.contains("export function decodeAccountBorsh(bytes: Uint8Array): Account"));
+    assert!(!ts.contains("export function decodeLedgerBorsh"));
+}
+
+#[test]
+fn test_json_64_bit_fields_are_strings() {
+    let bytes = Json.to_bytes(&ledger()).unwrap();
+    let json: Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(json["id"], "18446744073709551615");
+    assert_eq!(json["delta"], "-9223372036854775808");
+
+    let loaded: Ledger = Json.from_bytes(&bytes).unwrap();
+    assert_eq!(loaded, ledger());
+
+    let schema = Ledger::schema().unwrap().to_json_schema();
+    assert_eq!(schema["properties"]["id"]["type"], "string");
+    assert_eq!(schema["properties"]["delta"]["pattern"], "^-?[0-9]+$");
+}
+
+#[test]
+fn test_typescript_json_decoder_keeps_64_bit_precision() {
+    let ts = codegen::typescript(&[Ledger::schema().unwrap()]);
+
+    assert!(ts.contains("function bigintValue(v: unknown, name: string): bigint"));
+    assert!(ts.contains(
+        "    id: bigintValue(raw.id, \"id\"),\n    delta: bigintValue(raw.delta, \"delta\"),"
+    ));
+    assert!(!ts.contains("BigInt(raw."));
+}
@@ -87,3 +87,24 @@ fn test_all_serializers_produce_same_result() {
     assert_eq!(from_bincode, person);
     assert_eq!(from_json, person);
 }
+
+#[test]
+fn test_serde_only_type_with_bincode_and_json() {
+    // No borsh derives: only the Borsh serializer asks for them.
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Note {
+        text: String,
+    }
+
+    let note = Note {
+        text: "hello".to_string(),
+    };
+
+    let mut bincode_storage = Storage::new(Bincode);
+    bincode_storage.save(&note).unwrap();
+    assert_eq!(bincode_storage.load().unwrap(), note);
+
+    let mut json_storage = Storage::new(Json);
+    json_storage.save(&note).unwrap();
+    assert_eq!(json_storage.load().unwrap(), note);
+}
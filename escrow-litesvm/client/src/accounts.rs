@@ -0,0 +1,100 @@
+use anchor_lang::{prelude::Pubkey, AccountDeserialize};
+
+use crate::{
+    pda,
+    state::{AssetEscrow, BundleEscrow, Config, CounterOffer, Escrow, EscrowIndex, IndexPages, ESCROWS_PER_PAGE},
+};
+
+/// Anything that can return the raw data of an account: an RPC client, a
+/// LiteSVM instance or a plain closure over either.
+pub trait AccountFetcher {
+    fn account_data(&self, address: &Pubkey) -> Option<Vec<u8>>;
+}
+
+impl<F: Fn(&Pubkey) -> Option<Vec<u8>>> AccountFetcher for F {
+    fn account_data(&self, address: &Pubkey) -> Option<Vec<u8>> {
+        self(address)
+    }
+}
+
+/// Decodes a program account from its raw data, discriminator included.
+pub fn decode<T: AccountDeserialize>(mut data: &[u8]) -> anchor_lang::Result<T> {
+    T::try_deserialize(&mut data)
+}
+
+/// Fetches and decodes `address`; `None` when the account does not exist or
+/// no longer holds a `T`, e.g. after it was closed.
+pub fn fetch<T: AccountDeserialize>(fetcher: &impl AccountFetcher, address: &Pubkey) -> Option<T> {
+    decode(&fetcher.account_data(address)?).ok()
+}
+
+pub fn fetch_escrow(fetcher: &impl AccountFetcher, maker: &Pubkey, seed: u64) -> Option<Escrow> {
+    fetch(fetcher, &pda::escrow(maker, seed))
+}
+
+pub fn fetch_bundle(fetcher: &impl AccountFetcher, maker: &Pubkey, seed: u64) -> Option<BundleEscrow> {
+    fetch(fetcher, &pda::bundle(maker, seed))
+}
+
+pub fn fetch_asset_escrow(fetcher: &impl AccountFetcher, maker: &Pubkey, seed: u64) -> Option<AssetEscrow> {
+    fetch(fetcher, &pda::asset_escrow(maker, seed))
+}
+
+pub fn fetch_counter_offer(fetcher: &impl AccountFetcher, escrow: &Pubkey, taker: &Pubkey) -> Option<CounterOffer> {
+    fetch(fetcher, &pda::counter_offer(escrow, taker))
+}
+
+pub fn fetch_config(fetcher: &impl AccountFetcher) -> Option<Config> {
+    fetch(fetcher, &pda::config())
+}
+
+/// Escrows currently listed for the `mint_a` -> `mint_b` pair, across all index pages;
+/// empty when none were ever indexed.
+pub fn fetch_pair_index(fetcher: &impl AccountFetcher, mint_a: &Pubkey, mint_b: &Pubkey) -> Vec<Pubkey> {
+    fetch_index(fetcher, |page| pda::pair_index(mint_a, mint_b, page))
+}
+
+/// Escrows currently listed for `maker`, across all index pages; empty when none were
+/// ever indexed.
+pub fn fetch_maker_index(fetcher: &impl AccountFetcher, maker: &Pubkey) -> Vec<Pubkey> {
+    fetch_index(fetcher, |page| pda::maker_index(maker, page))
+}
+
+/// Index pages are never closed and `make` fills them in order, so the first missing
+/// page ends the index.
+fn fetch_index(fetcher: &impl AccountFetcher, page_address: impl Fn(u32) -> Pubkey) -> Vec<Pubkey> {
+    (0..)
+        .map_while(|page| fetch::<EscrowIndex>(fetcher, &page_address(page)))
+        .flat_map(|index| index.escrows)
+        .collect()
+}
+
+/// First pages of the mint-pair and maker indexes with room for another escrow, to
+/// pass to [`MakeBuilder::index_pages`](crate::MakeBuilder::index_pages).
+pub fn open_index_pages(fetcher: &impl AccountFetcher, maker: &Pubkey, mint_a: &Pubkey, mint_b: &Pubkey) -> IndexPages {
+    IndexPages {
+        pair: open_page(fetcher, |page| pda::pair_index(mint_a, mint_b, page)),
+        maker: open_page(fetcher, |page| pda::maker_index(maker, page)),
+    }
+}
+
+fn open_page(fetcher: &impl AccountFetcher, page_address: impl Fn(u32) -> Pubkey) -> u32 {
+    (0..)
+        .find(|page| {
+            fetch::<EscrowIndex>(fetcher, &page_address(*page))
+                .is_none_or(|index| index.escrows.len() < ESCROWS_PER_PAGE)
+        })
+        .unwrap_or(u32::MAX)
+}
+
+/// The escrows listed on the on-chain index page at `index_page`, fetched and decoded;
+/// empty when the page does not exist. Order within a page is not stable: removals
+/// move the page's last escrow into the freed slot.
+pub fn fetch_escrow_page(fetcher: &impl AccountFetcher, index_page: &Pubkey) -> Vec<(Pubkey, Escrow)> {
+    fetch::<EscrowIndex>(fetcher, index_page)
+        .map(|index| index.escrows)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|address| Some((address, fetch(fetcher, &address)?)))
+        .collect()
+}
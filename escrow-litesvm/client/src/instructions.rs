@@ -0,0 +1,1593 @@
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::instruction::{AccountMeta, Instruction},
+    system_program, Id, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{associated_token, token, token_2022};
+use tuktuk_program::tuktuk::program::Tuktuk;
+
+use crate::{
+    pda,
+    state::{
+        AssetEscrow, AssetKind, AssetSide, BundleEscrow, BundleLeg, Config, CounterOffer, DepositChange, DutchAuction,
+        Escrow, IndexPages, RefundTask, MPL_CORE_ID, NATIVE_SOL,
+    },
+    PROGRAM_ID,
+};
+
+/// `Some(mint)` for an SPL leg, `None` for a [`NATIVE_SOL`] leg.
+fn token_leg(mint: Pubkey) -> Option<Pubkey> {
+    (mint != NATIVE_SOL).then_some(mint)
+}
+
+/// TukTuk accounts that let `take` and `refund` dequeue a scheduled auto-refund;
+/// all `None` when the escrow has none.
+struct RefundTaskAccounts {
+    task_queue: Option<Pubkey>,
+    task_queue_authority: Option<Pubkey>,
+    task: Option<Pubkey>,
+    queue_authority: Option<Pubkey>,
+    tuktuk_program: Option<Pubkey>,
+}
+
+impl RefundTaskAccounts {
+    fn new(refund_task: Option<RefundTask>) -> Self {
+        let queue_authority = pda::queue_authority();
+
+        Self {
+            task_queue: refund_task.map(|task| task.task_queue),
+            task_queue_authority: refund_task.map(|task| pda::task_queue_authority(&task.task_queue, &queue_authority)),
+            task: refund_task.map(|task| task.task),
+            queue_authority: refund_task.map(|_| queue_authority),
+            tuktuk_program: refund_task.map(|_| Tuktuk::id()),
+        }
+    }
+}
+
+fn instruction(accounts: impl ToAccountMetas, data: impl InstructionData, remaining_accounts: &[AccountMeta]) -> Instruction {
+    let mut accounts = accounts.to_account_metas(None);
+    accounts.extend_from_slice(remaining_accounts);
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data: data.data(),
+    }
+}
+
+/// Marks the given mints writable when they are Token-2022, so the program can
+/// harvest withheld transfer fees before closing a vault. Legacy mints stay
+/// read-only and are not write-locked.
+fn harvest_mints(mut instruction: Instruction, token_program: &Pubkey, mints: &[Option<Pubkey>]) -> Instruction {
+    if *token_program == token_2022::ID {
+        for meta in instruction.accounts.iter_mut() {
+            if mints.contains(&Some(meta.pubkey)) {
+                meta.is_writable = true;
+            }
+        }
+    }
+    instruction
+}
+
+/// The TukTuk task `make_with_expiry` queues alongside the escrow.
+#[derive(Clone, Copy, Debug)]
+struct AutoRefund {
+    task_queue: Pubkey,
+    task_id: u16,
+    crank_reward: u64,
+}
+
+/// Builds `make`. Both legs start out as zero [`NATIVE_SOL`]; pass an SPL
+/// mint to [`offer`](Self::offer) or [`request`](Self::request) for a token leg.
+/// With [`auto_refund`](Self::auto_refund) it builds `make_with_expiry` instead,
+/// and with [`auction`](Self::auction) `make_auction`.
+#[derive(Clone, Debug)]
+pub struct MakeBuilder {
+    maker: Pubkey,
+    seed: u64,
+    mint_a: Pubkey,
+    deposit: u64,
+    mint_b: Pubkey,
+    receive: u64,
+    unlock_at: i64,
+    expires_at: Option<i64>,
+    allowed_takers: Vec<Pubkey>,
+    auto_refund: Option<AutoRefund>,
+    auction: Option<DutchAuction>,
+    index_pages: IndexPages,
+    token_program: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl MakeBuilder {
+    pub fn new(maker: Pubkey, seed: u64) -> Self {
+        Self {
+            maker,
+            seed,
+            mint_a: NATIVE_SOL,
+            deposit: 0,
+            mint_b: NATIVE_SOL,
+            receive: 0,
+            unlock_at: 0,
+            expires_at: None,
+            allowed_takers: vec![],
+            auto_refund: None,
+            auction: None,
+            index_pages: IndexPages::default(),
+            token_program: token::ID,
+            remaining_accounts: vec![],
+        }
+    }
+
+    pub fn offer(mut self, mint_a: Pubkey, deposit: u64) -> Self {
+        self.mint_a = mint_a;
+        self.deposit = deposit;
+        self
+    }
+
+    pub fn request(mut self, mint_b: Pubkey, receive: u64) -> Self {
+        self.mint_b = mint_b;
+        self.receive = receive;
+        self
+    }
+
+    pub fn unlock_at(mut self, unlock_at: i64) -> Self {
+        self.unlock_at = unlock_at;
+        self
+    }
+
+    pub fn expires_at(mut self, expires_at: i64) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    pub fn allowed_takers(mut self, allowed_takers: Vec<Pubkey>) -> Self {
+        self.allowed_takers = allowed_takers;
+        self
+    }
+
+    /// Queues the auto-refund on `task_queue` at [`expires_at`](Self::expires_at),
+    /// which must be set, in the same instruction that creates the escrow.
+    pub fn auto_refund(mut self, task_queue: Pubkey, task_id: u16, crank_reward: u64) -> Self {
+        self.auto_refund = Some(AutoRefund { task_queue, task_id, crank_reward });
+        self
+    }
+
+    /// Prices the escrow by a Dutch auction; the amount passed to
+    /// [`request`](Self::request) is ignored.
+    pub fn auction(mut self, auction: DutchAuction) -> Self {
+        self.auction = Some(auction);
+        self
+    }
+
+    /// Pages of the mint-pair and maker indexes to list the escrow on, by default
+    /// the first; each must have room, see [`open_index_pages`](crate::open_index_pages).
+    pub fn index_pages(mut self, index_pages: IndexPages) -> Self {
+        self.index_pages = index_pages;
+        self
+    }
+
+    pub fn token_program(mut self, token_program: Pubkey) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    /// Transfer-hook extra accounts for mint_a.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn escrow(&self) -> Pubkey {
+        pda::escrow(&self.maker, self.seed)
+    }
+
+    pub fn vault(&self) -> Option<Pubkey> {
+        token_leg(self.mint_a).map(|mint_a| pda::ata(&self.escrow(), &mint_a, &self.token_program))
+    }
+
+    /// The TukTuk task queued by [`auto_refund`](Self::auto_refund), if any.
+    pub fn task(&self) -> Option<Pubkey> {
+        self.auto_refund.map(|auto_refund| pda::task(&auto_refund.task_queue, auto_refund.task_id))
+    }
+
+    fn make_accounts(&self) -> anchor_escrow::accounts::Make {
+        let mint_a = token_leg(self.mint_a);
+
+        anchor_escrow::accounts::Make {
+            maker: self.maker,
+            mint_a,
+            mint_b: token_leg(self.mint_b),
+            maker_ata_a: mint_a.map(|mint_a| pda::ata(&self.maker, &mint_a, &self.token_program)),
+            escrow: self.escrow(),
+            vault: self.vault(),
+            config: pda::config(),
+            pair_index: pda::pair_index(&self.mint_a, &self.mint_b, self.index_pages.pair),
+            maker_index: pda::maker_index(&self.maker, self.index_pages.maker),
+            associated_token_program: associated_token::ID,
+            token_program: self.token_program,
+            system_program: system_program::ID,
+        }
+    }
+
+    /// # Panics
+    ///
+    /// If [`auto_refund`](Self::auto_refund) is set without [`expires_at`](Self::expires_at),
+    /// or together with [`auction`](Self::auction).
+    pub fn instruction(&self) -> Instruction {
+        if let Some(auto_refund) = self.auto_refund {
+            assert!(self.auction.is_none(), "make_with_expiry does not take an auction");
+            let queue_authority = pda::queue_authority();
+
+            return instruction(
+                anchor_escrow::accounts::MakeWithExpiry {
+                    make: self.make_accounts(),
+                    task_queue: auto_refund.task_queue,
+                    task_queue_authority: pda::task_queue_authority(&auto_refund.task_queue, &queue_authority),
+                    task: pda::task(&auto_refund.task_queue, auto_refund.task_id),
+                    queue_authority,
+                    tuktuk_program: Tuktuk::id(),
+                },
+                anchor_escrow::instruction::MakeWithExpiry {
+                    seed: self.seed,
+                    index_pages: self.index_pages,
+                    deposit: self.deposit,
+                    receive: self.receive,
+                    unlock_at: self.unlock_at,
+                    expires_at: self.expires_at.expect("auto_refund needs expires_at"),
+                    allowed_takers: self.allowed_takers.clone(),
+                    task_id: auto_refund.task_id,
+                    crank_reward: auto_refund.crank_reward,
+                },
+                &self.remaining_accounts,
+            );
+        }
+
+        if let Some(auction) = self.auction {
+            return instruction(
+                self.make_accounts(),
+                anchor_escrow::instruction::MakeAuction {
+                    seed: self.seed,
+                    index_pages: self.index_pages,
+                    deposit: self.deposit,
+                    auction,
+                    unlock_at: self.unlock_at,
+                    expires_at: self.expires_at,
+                    allowed_takers: self.allowed_takers.clone(),
+                },
+                &self.remaining_accounts,
+            );
+        }
+
+        instruction(
+            self.make_accounts(),
+            anchor_escrow::instruction::Make {
+                seed: self.seed,
+                index_pages: self.index_pages,
+                deposit: self.deposit,
+                receive: self.receive,
+                unlock_at: self.unlock_at,
+                expires_at: self.expires_at,
+                allowed_takers: self.allowed_takers.clone(),
+            },
+            &self.remaining_accounts,
+        )
+    }
+}
+
+/// Builds `take` against a fetched escrow and config. Fills everything still
+/// owed unless [`amount`](Self::amount) is set, adds the fee recipient's ATAs
+/// whenever the protocol charges a fee, and passes the scheduled auto-refund
+/// task, if any, so a completing fill dequeues it.
+#[derive(Clone, Debug)]
+pub struct TakeBuilder {
+    taker: Pubkey,
+    maker: Pubkey,
+    seed: u64,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    index_pages: IndexPages,
+    amount: u64,
+    fee_recipient: Pubkey,
+    charges_fee: bool,
+    refund_task: Option<RefundTask>,
+    token_program: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl TakeBuilder {
+    /// Before `init_config` has run pass [`Config::unset`]; the taker then
+    /// stands in as the fee recipient, as no fee is charged.
+    pub fn new(taker: Pubkey, escrow: &Escrow, config: &Config) -> Self {
+        Self {
+            taker,
+            maker: escrow.maker,
+            seed: escrow.seed,
+            mint_a: escrow.mint_a,
+            mint_b: escrow.mint_b,
+            index_pages: escrow.index_pages,
+            amount: escrow.receive,
+            fee_recipient: if config.is_initialized() { config.fee_recipient } else { taker },
+            charges_fee: config.fee_bps > 0,
+            refund_task: escrow.refund_task,
+            token_program: token::ID,
+            remaining_accounts: vec![],
+        }
+    }
+
+    /// Amount of mint_b to pay, for a partial fill. For an auction escrow, the
+    /// most the taker will pay; the current price is charged.
+    pub fn amount(mut self, amount: u64) -> Self {
+        self.amount = amount;
+        self
+    }
+
+    pub fn token_program(mut self, token_program: Pubkey) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    /// Transfer-hook extra accounts for either mint.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let escrow = pda::escrow(&self.maker, self.seed);
+        let mint_a = token_leg(self.mint_a);
+        let mint_b = token_leg(self.mint_b);
+        let ata = |owner: &Pubkey, mint: Option<Pubkey>| mint.map(|mint| pda::ata(owner, &mint, &self.token_program));
+        let treasury_ata = |mint: Option<Pubkey>| ata(&self.fee_recipient, mint).filter(|_| self.charges_fee);
+        let refund_task = RefundTaskAccounts::new(self.refund_task);
+
+        harvest_mints(
+            instruction(
+                anchor_escrow::accounts::Take {
+                    taker: self.taker,
+                    maker: self.maker,
+                    mint_a,
+                    mint_b,
+                    taker_ata_a: ata(&self.taker, mint_a),
+                    taker_ata_b: ata(&self.taker, mint_b),
+                    maker_ata_b: ata(&self.maker, mint_b),
+                    escrow,
+                    vault: ata(&escrow, mint_a),
+                    config: pda::config(),
+                    fee_recipient: self.fee_recipient,
+                    treasury_ata_a: treasury_ata(mint_a),
+                    treasury_ata_b: treasury_ata(mint_b),
+                    task_queue: refund_task.task_queue,
+                    task_queue_authority: refund_task.task_queue_authority,
+                    task: refund_task.task,
+                    queue_authority: refund_task.queue_authority,
+                    tuktuk_program: refund_task.tuktuk_program,
+                    pair_index: pda::pair_index(&self.mint_a, &self.mint_b, self.index_pages.pair),
+                    maker_index: pda::maker_index(&self.maker, self.index_pages.maker),
+                    associated_token_program: associated_token::ID,
+                    token_program: self.token_program,
+                    system_program: system_program::ID,
+                },
+                anchor_escrow::instruction::Take { amount: self.amount },
+                &self.remaining_accounts,
+            ),
+            &self.token_program,
+            &[mint_a],
+        )
+    }
+}
+
+/// Builds `refund` for the maker of a fetched escrow, dequeuing its scheduled
+/// auto-refund task if it has one.
+#[derive(Clone, Debug)]
+pub struct RefundBuilder {
+    maker: Pubkey,
+    seed: u64,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    index_pages: IndexPages,
+    refund_task: Option<RefundTask>,
+    token_program: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl RefundBuilder {
+    pub fn new(escrow: &Escrow) -> Self {
+        Self {
+            maker: escrow.maker,
+            seed: escrow.seed,
+            mint_a: escrow.mint_a,
+            mint_b: escrow.mint_b,
+            index_pages: escrow.index_pages,
+            refund_task: escrow.refund_task,
+            token_program: token::ID,
+            remaining_accounts: vec![],
+        }
+    }
+
+    pub fn token_program(mut self, token_program: Pubkey) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    /// Transfer-hook extra accounts for mint_a.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let escrow = pda::escrow(&self.maker, self.seed);
+        let mint_a = token_leg(self.mint_a);
+        let refund_task = RefundTaskAccounts::new(self.refund_task);
+
+        harvest_mints(
+            instruction(
+                anchor_escrow::accounts::Refund {
+                    maker: self.maker,
+                    mint_a,
+                    maker_ata_a: mint_a.map(|mint_a| pda::ata(&self.maker, &mint_a, &self.token_program)),
+                    escrow,
+                    vault: mint_a.map(|mint_a| pda::ata(&escrow, &mint_a, &self.token_program)),
+                    task_queue: refund_task.task_queue,
+                    task_queue_authority: refund_task.task_queue_authority,
+                    task: refund_task.task,
+                    queue_authority: refund_task.queue_authority,
+                    tuktuk_program: refund_task.tuktuk_program,
+                    pair_index: pda::pair_index(&self.mint_a, &self.mint_b, self.index_pages.pair),
+                    maker_index: pda::maker_index(&self.maker, self.index_pages.maker),
+                    token_program: self.token_program,
+                    system_program: system_program::ID,
+                },
+                anchor_escrow::instruction::Refund {},
+                &self.remaining_accounts,
+            ),
+            &self.token_program,
+            &[mint_a],
+        )
+    }
+}
+
+/// Builds `update_escrow` for the maker of a fetched escrow. Only the terms
+/// that are set change.
+#[derive(Clone, Debug)]
+pub struct UpdateEscrowBuilder {
+    maker: Pubkey,
+    seed: u64,
+    mint_a: Pubkey,
+    deposit_change: Option<DepositChange>,
+    receive: Option<u64>,
+    expires_at: Option<i64>,
+    token_program: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl UpdateEscrowBuilder {
+    pub fn new(escrow: &Escrow) -> Self {
+        Self {
+            maker: escrow.maker,
+            seed: escrow.seed,
+            mint_a: escrow.mint_a,
+            deposit_change: None,
+            receive: None,
+            expires_at: None,
+            token_program: token::ID,
+            remaining_accounts: vec![],
+        }
+    }
+
+    pub fn top_up(mut self, amount: u64) -> Self {
+        self.deposit_change = Some(DepositChange::TopUp(amount));
+        self
+    }
+
+    pub fn withdraw(mut self, amount: u64) -> Self {
+        self.deposit_change = Some(DepositChange::Withdraw(amount));
+        self
+    }
+
+    pub fn receive(mut self, receive: u64) -> Self {
+        self.receive = Some(receive);
+        self
+    }
+
+    /// Rejected while an auto-refund is scheduled for the escrow.
+    pub fn expires_at(mut self, expires_at: i64) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    pub fn token_program(mut self, token_program: Pubkey) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    /// Transfer-hook extra accounts for mint_a.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let escrow = pda::escrow(&self.maker, self.seed);
+        let mint_a = token_leg(self.mint_a);
+
+        instruction(
+            anchor_escrow::accounts::UpdateEscrow {
+                maker: self.maker,
+                mint_a,
+                maker_ata_a: mint_a.map(|mint_a| pda::ata(&self.maker, &mint_a, &self.token_program)),
+                escrow,
+                vault: mint_a.map(|mint_a| pda::ata(&escrow, &mint_a, &self.token_program)),
+                token_program: self.token_program,
+                system_program: system_program::ID,
+            },
+            anchor_escrow::instruction::UpdateEscrow {
+                deposit_change: self.deposit_change,
+                receive: self.receive,
+                expires_at: self.expires_at,
+            },
+            &self.remaining_accounts,
+        )
+    }
+}
+
+/// Builds `make_counter_offer`, locking `amount` of a fetched escrow's mint_b
+/// as the taker's proposal for the whole deposit.
+#[derive(Clone, Debug)]
+pub struct MakeCounterOfferBuilder {
+    taker: Pubkey,
+    maker: Pubkey,
+    seed: u64,
+    mint_b: Pubkey,
+    amount: u64,
+    token_program: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl MakeCounterOfferBuilder {
+    pub fn new(taker: Pubkey, escrow: &Escrow, amount: u64) -> Self {
+        Self {
+            taker,
+            maker: escrow.maker,
+            seed: escrow.seed,
+            mint_b: escrow.mint_b,
+            amount,
+            token_program: token::ID,
+            remaining_accounts: vec![],
+        }
+    }
+
+    pub fn token_program(mut self, token_program: Pubkey) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    /// Transfer-hook extra accounts for mint_b.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn counter_offer(&self) -> Pubkey {
+        pda::counter_offer(&pda::escrow(&self.maker, self.seed), &self.taker)
+    }
+
+    pub fn counter_vault(&self) -> Option<Pubkey> {
+        token_leg(self.mint_b).map(|mint_b| pda::ata(&self.counter_offer(), &mint_b, &self.token_program))
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let mint_b = token_leg(self.mint_b);
+
+        instruction(
+            anchor_escrow::accounts::MakeCounterOffer {
+                taker: self.taker,
+                mint_b,
+                taker_ata_b: mint_b.map(|mint_b| pda::ata(&self.taker, &mint_b, &self.token_program)),
+                escrow: pda::escrow(&self.maker, self.seed),
+                counter_offer: self.counter_offer(),
+                counter_vault: self.counter_vault(),
+                config: pda::config(),
+                associated_token_program: associated_token::ID,
+                token_program: self.token_program,
+                system_program: system_program::ID,
+            },
+            anchor_escrow::instruction::MakeCounterOffer { amount: self.amount },
+            &self.remaining_accounts,
+        )
+    }
+}
+
+/// Builds `accept_counter_offer` for the maker of a fetched escrow. Like
+/// [`TakeBuilder`], it adds the fee recipient's ATAs whenever the protocol
+/// charges a fee and dequeues the scheduled auto-refund task, if any.
+#[derive(Clone, Debug)]
+pub struct AcceptCounterOfferBuilder {
+    maker: Pubkey,
+    seed: u64,
+    taker: Pubkey,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    index_pages: IndexPages,
+    fee_recipient: Pubkey,
+    charges_fee: bool,
+    refund_task: Option<RefundTask>,
+    token_program: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl AcceptCounterOfferBuilder {
+    pub fn new(escrow: &Escrow, counter_offer: &CounterOffer, config: &Config) -> Self {
+        Self {
+            maker: escrow.maker,
+            seed: escrow.seed,
+            taker: counter_offer.taker,
+            mint_a: escrow.mint_a,
+            mint_b: escrow.mint_b,
+            index_pages: escrow.index_pages,
+            fee_recipient: config.fee_recipient,
+            charges_fee: config.fee_bps > 0,
+            refund_task: escrow.refund_task,
+            token_program: token::ID,
+            remaining_accounts: vec![],
+        }
+    }
+
+    pub fn token_program(mut self, token_program: Pubkey) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    /// Transfer-hook extra accounts for either mint.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let escrow = pda::escrow(&self.maker, self.seed);
+        let counter_offer = pda::counter_offer(&escrow, &self.taker);
+        let mint_a = token_leg(self.mint_a);
+        let mint_b = token_leg(self.mint_b);
+        let ata = |owner: &Pubkey, mint: Option<Pubkey>| mint.map(|mint| pda::ata(owner, &mint, &self.token_program));
+        let treasury_ata = |mint: Option<Pubkey>| ata(&self.fee_recipient, mint).filter(|_| self.charges_fee);
+        let refund_task = RefundTaskAccounts::new(self.refund_task);
+
+        harvest_mints(
+            instruction(
+                anchor_escrow::accounts::AcceptCounterOffer {
+                    maker: self.maker,
+                    taker: self.taker,
+                    mint_a,
+                    mint_b,
+                    taker_ata_a: ata(&self.taker, mint_a),
+                    maker_ata_b: ata(&self.maker, mint_b),
+                    escrow,
+                    vault: ata(&escrow, mint_a),
+                    counter_offer,
+                    counter_vault: ata(&counter_offer, mint_b),
+                    config: pda::config(),
+                    fee_recipient: self.fee_recipient,
+                    treasury_ata_a: treasury_ata(mint_a),
+                    treasury_ata_b: treasury_ata(mint_b),
+                    task_queue: refund_task.task_queue,
+                    task_queue_authority: refund_task.task_queue_authority,
+                    task: refund_task.task,
+                    queue_authority: refund_task.queue_authority,
+                    tuktuk_program: refund_task.tuktuk_program,
+                    pair_index: pda::pair_index(&self.mint_a, &self.mint_b, self.index_pages.pair),
+                    maker_index: pda::maker_index(&self.maker, self.index_pages.maker),
+                    associated_token_program: associated_token::ID,
+                    token_program: self.token_program,
+                    system_program: system_program::ID,
+                },
+                anchor_escrow::instruction::AcceptCounterOffer {},
+                &self.remaining_accounts,
+            ),
+            &self.token_program,
+            &[mint_a, mint_b],
+        )
+    }
+}
+
+/// Builds `reject_counter_offer` for a fetched counter-offer, signed by
+/// `authority`: its escrow's maker rejecting it or its taker withdrawing it.
+#[derive(Clone, Debug)]
+pub struct RejectCounterOfferBuilder {
+    authority: Pubkey,
+    escrow: Pubkey,
+    taker: Pubkey,
+    mint_b: Pubkey,
+    token_program: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl RejectCounterOfferBuilder {
+    pub fn new(authority: Pubkey, counter_offer: &CounterOffer) -> Self {
+        Self {
+            authority,
+            escrow: counter_offer.escrow,
+            taker: counter_offer.taker,
+            mint_b: counter_offer.mint_b,
+            token_program: token::ID,
+            remaining_accounts: vec![],
+        }
+    }
+
+    pub fn token_program(mut self, token_program: Pubkey) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    /// Transfer-hook extra accounts for mint_b.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let counter_offer = pda::counter_offer(&self.escrow, &self.taker);
+        let mint_b = token_leg(self.mint_b);
+
+        harvest_mints(
+            instruction(
+                anchor_escrow::accounts::RejectCounterOffer {
+                    authority: self.authority,
+                    taker: self.taker,
+                    mint_b,
+                    taker_ata_b: mint_b.map(|mint_b| pda::ata(&self.taker, &mint_b, &self.token_program)),
+                    counter_offer,
+                    counter_vault: mint_b.map(|mint_b| pda::ata(&counter_offer, &mint_b, &self.token_program)),
+                    token_program: self.token_program,
+                    system_program: system_program::ID,
+                },
+                anchor_escrow::instruction::RejectCounterOffer {},
+                &self.remaining_accounts,
+            ),
+            &self.token_program,
+            &[mint_b],
+        )
+    }
+}
+
+/// Builds `auto_refund`, which anyone may send once the escrow has expired.
+#[derive(Clone, Debug)]
+pub struct AutoRefundBuilder {
+    maker: Pubkey,
+    seed: u64,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    index_pages: IndexPages,
+    token_program: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl AutoRefundBuilder {
+    pub fn new(escrow: &Escrow) -> Self {
+        Self {
+            maker: escrow.maker,
+            seed: escrow.seed,
+            mint_a: escrow.mint_a,
+            mint_b: escrow.mint_b,
+            index_pages: escrow.index_pages,
+            token_program: token::ID,
+            remaining_accounts: vec![],
+        }
+    }
+
+    pub fn token_program(mut self, token_program: Pubkey) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    /// Transfer-hook extra accounts for mint_a.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let escrow = pda::escrow(&self.maker, self.seed);
+        let mint_a = token_leg(self.mint_a);
+
+        harvest_mints(
+            instruction(
+                anchor_escrow::accounts::AutoRefund {
+                    maker: self.maker,
+                    mint_a,
+                    maker_ata_a: mint_a.map(|mint_a| pda::ata(&self.maker, &mint_a, &self.token_program)),
+                    escrow,
+                    vault: mint_a.map(|mint_a| pda::ata(&escrow, &mint_a, &self.token_program)),
+                    pair_index: pda::pair_index(&self.mint_a, &self.mint_b, self.index_pages.pair),
+                    maker_index: pda::maker_index(&self.maker, self.index_pages.maker),
+                    token_program: self.token_program,
+                    system_program: system_program::ID,
+                },
+                anchor_escrow::instruction::AutoRefund { seed: self.seed },
+                &self.remaining_accounts,
+            ),
+            &self.token_program,
+            &[mint_a],
+        )
+    }
+}
+
+/// Builds `schedule_refund`, queuing an `auto_refund` of a fetched escrow as
+/// task `task_id` on a TukTuk `task_queue` the program's queue authority has
+/// been added to and the config allows. `expiry_timestamp` must be the escrow's
+/// `expires_at`, and becomes it if the escrow had none. The crank reward defaults to zero and
+/// must fall within the config's bounds.
+#[derive(Clone, Debug)]
+pub struct ScheduleRefundBuilder {
+    maker: Pubkey,
+    seed: u64,
+    task_queue: Pubkey,
+    task_id: u16,
+    expiry_timestamp: i64,
+    crank_reward: u64,
+    token_program: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl ScheduleRefundBuilder {
+    pub fn new(escrow: &Escrow, task_queue: Pubkey, task_id: u16, expiry_timestamp: i64) -> Self {
+        Self {
+            maker: escrow.maker,
+            seed: escrow.seed,
+            task_queue,
+            task_id,
+            expiry_timestamp,
+            crank_reward: 0,
+            token_program: token::ID,
+            remaining_accounts: vec![],
+        }
+    }
+
+    pub fn token_program(mut self, token_program: Pubkey) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    /// Lamports paid to whoever runs the task.
+    pub fn crank_reward(mut self, crank_reward: u64) -> Self {
+        self.crank_reward = crank_reward;
+        self
+    }
+
+    /// Transfer-hook extra accounts for mint_a, forwarded to the queued auto_refund.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn task(&self) -> Pubkey {
+        pda::task(&self.task_queue, self.task_id)
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let queue_authority = pda::queue_authority();
+
+        instruction(
+            anchor_escrow::accounts::ScheduleRefund {
+                maker: self.maker,
+                escrow: pda::escrow(&self.maker, self.seed),
+                token_program: self.token_program,
+                config: pda::config(),
+                task_queue: self.task_queue,
+                task_queue_authority: pda::task_queue_authority(&self.task_queue, &queue_authority),
+                task: self.task(),
+                queue_authority,
+                system_program: system_program::ID,
+                tuktuk_program: Tuktuk::id(),
+            },
+            anchor_escrow::instruction::ScheduleRefund {
+                seed: self.seed,
+                task_id: self.task_id,
+                expiry_timestamp: self.expiry_timestamp,
+                crank_reward: self.crank_reward,
+            },
+            &self.remaining_accounts,
+        )
+    }
+}
+
+/// Token programs of the mints of a bundle, which each leg may settle through
+/// its own; any mint not listed is a legacy SPL mint.
+#[derive(Clone, Debug, Default)]
+struct BundleTokenPrograms(Vec<(Pubkey, Pubkey)>);
+
+impl BundleTokenPrograms {
+    fn set(&mut self, mint: Pubkey, token_program: Pubkey) {
+        self.0.retain(|(other, _)| *other != mint);
+        self.0.push((mint, token_program));
+    }
+
+    fn get(&self, mint: &Pubkey) -> Pubkey {
+        self.0
+            .iter()
+            .find(|(other, _)| other == mint)
+            .map_or(token::ID, |(_, token_program)| *token_program)
+    }
+
+    fn ata(&self, owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        pda::ata(owner, mint, &self.get(mint))
+    }
+
+    /// Token-2022 mints, which a closing vault needs writable to harvest withheld fees.
+    fn mint_meta(&self, mint: &Pubkey) -> AccountMeta {
+        if self.get(mint) == token_2022::ID {
+            AccountMeta::new(*mint, false)
+        } else {
+            AccountMeta::new_readonly(*mint, false)
+        }
+    }
+
+    /// [token program, mint, vault, maker token account] per offered leg, as
+    /// `refund_bundle` and `auto_refund_bundle` expect them.
+    fn refund_accounts(&self, bundle: &BundleEscrow) -> Vec<AccountMeta> {
+        let bundle_key = pda::bundle(&bundle.maker, bundle.seed);
+
+        bundle
+            .offered
+            .iter()
+            .flat_map(|leg| {
+                [
+                    AccountMeta::new_readonly(self.get(&leg.mint), false),
+                    self.mint_meta(&leg.mint),
+                    AccountMeta::new(self.ata(&bundle_key, &leg.mint), false),
+                    AccountMeta::new(self.ata(&bundle.maker, &leg.mint), false),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Builds `make_bundle`, depositing every [`offer`](Self::offer)ed leg from the
+/// maker's ATAs. Each mint settles through the legacy token program unless
+/// [`token_program`](Self::token_program) says otherwise.
+#[derive(Clone, Debug)]
+pub struct MakeBundleBuilder {
+    maker: Pubkey,
+    seed: u64,
+    offered: Vec<BundleLeg>,
+    requested: Vec<BundleLeg>,
+    expires_at: Option<i64>,
+    token_programs: BundleTokenPrograms,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl MakeBundleBuilder {
+    pub fn new(maker: Pubkey, seed: u64) -> Self {
+        Self {
+            maker,
+            seed,
+            offered: vec![],
+            requested: vec![],
+            expires_at: None,
+            token_programs: BundleTokenPrograms::default(),
+            remaining_accounts: vec![],
+        }
+    }
+
+    pub fn offer(mut self, mint: Pubkey, amount: u64) -> Self {
+        self.offered.push(BundleLeg { mint, amount });
+        self
+    }
+
+    pub fn request(mut self, mint: Pubkey, amount: u64) -> Self {
+        self.requested.push(BundleLeg { mint, amount });
+        self
+    }
+
+    /// After this anyone may `auto_refund_bundle`; without it only the maker can refund.
+    pub fn expires_at(mut self, expires_at: i64) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    pub fn token_program(mut self, mint: Pubkey, token_program: Pubkey) -> Self {
+        self.token_programs.set(mint, token_program);
+        self
+    }
+
+    /// Transfer-hook extra accounts for the offered mints.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn bundle(&self) -> Pubkey {
+        pda::bundle(&self.maker, self.seed)
+    }
+
+    pub fn vault(&self, mint: &Pubkey) -> Pubkey {
+        self.token_programs.ata(&self.bundle(), mint)
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let mut remaining_accounts = vec![];
+        for leg in &self.offered {
+            remaining_accounts.extend([
+                AccountMeta::new_readonly(self.token_programs.get(&leg.mint), false),
+                AccountMeta::new_readonly(leg.mint, false),
+                AccountMeta::new(self.token_programs.ata(&self.maker, &leg.mint), false),
+                AccountMeta::new(self.vault(&leg.mint), false),
+            ]);
+        }
+        remaining_accounts.extend(self.requested.iter().map(|leg| AccountMeta::new_readonly(leg.mint, false)));
+        remaining_accounts.extend_from_slice(&self.remaining_accounts);
+
+        instruction(
+            anchor_escrow::accounts::MakeBundle {
+                maker: self.maker,
+                bundle: self.bundle(),
+                config: pda::config(),
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            },
+            anchor_escrow::instruction::MakeBundle {
+                seed: self.seed,
+                offered: self.offered.clone(),
+                requested: self.requested.clone(),
+                expires_at: self.expires_at,
+            },
+            &remaining_accounts,
+        )
+    }
+}
+
+/// Builds `take_bundle` against a fetched bundle and config, passing the fee
+/// recipient's ATA for every leg.
+#[derive(Clone, Debug)]
+pub struct TakeBundleBuilder {
+    taker: Pubkey,
+    bundle: BundleEscrow,
+    fee_recipient: Pubkey,
+    token_programs: BundleTokenPrograms,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl TakeBundleBuilder {
+    pub fn new(taker: Pubkey, bundle: &BundleEscrow, config: &Config) -> Self {
+        Self {
+            taker,
+            bundle: bundle.clone(),
+            fee_recipient: config.fee_recipient,
+            token_programs: BundleTokenPrograms::default(),
+            remaining_accounts: vec![],
+        }
+    }
+
+    pub fn token_program(mut self, mint: Pubkey, token_program: Pubkey) -> Self {
+        self.token_programs.set(mint, token_program);
+        self
+    }
+
+    /// Transfer-hook extra accounts for any of the mints.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let maker = self.bundle.maker;
+        let bundle = pda::bundle(&maker, self.bundle.seed);
+        let programs = &self.token_programs;
+
+        let mut remaining_accounts = vec![];
+        for leg in &self.bundle.requested {
+            remaining_accounts.extend([
+                AccountMeta::new_readonly(programs.get(&leg.mint), false),
+                AccountMeta::new_readonly(leg.mint, false),
+                AccountMeta::new(programs.ata(&self.taker, &leg.mint), false),
+                AccountMeta::new(programs.ata(&maker, &leg.mint), false),
+                AccountMeta::new(programs.ata(&self.fee_recipient, &leg.mint), false),
+            ]);
+        }
+        for leg in &self.bundle.offered {
+            remaining_accounts.extend([
+                AccountMeta::new_readonly(programs.get(&leg.mint), false),
+                programs.mint_meta(&leg.mint),
+                AccountMeta::new(programs.ata(&bundle, &leg.mint), false),
+                AccountMeta::new(programs.ata(&self.taker, &leg.mint), false),
+                AccountMeta::new(programs.ata(&self.fee_recipient, &leg.mint), false),
+            ]);
+        }
+        remaining_accounts.extend_from_slice(&self.remaining_accounts);
+
+        instruction(
+            anchor_escrow::accounts::TakeBundle {
+                taker: self.taker,
+                maker,
+                bundle,
+                config: pda::config(),
+                fee_recipient: self.fee_recipient,
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            },
+            anchor_escrow::instruction::TakeBundle {},
+            &remaining_accounts,
+        )
+    }
+}
+
+/// Builds `refund_bundle` for the maker of a fetched bundle.
+#[derive(Clone, Debug)]
+pub struct RefundBundleBuilder {
+    bundle: BundleEscrow,
+    token_programs: BundleTokenPrograms,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl RefundBundleBuilder {
+    pub fn new(bundle: &BundleEscrow) -> Self {
+        Self {
+            bundle: bundle.clone(),
+            token_programs: BundleTokenPrograms::default(),
+            remaining_accounts: vec![],
+        }
+    }
+
+    pub fn token_program(mut self, mint: Pubkey, token_program: Pubkey) -> Self {
+        self.token_programs.set(mint, token_program);
+        self
+    }
+
+    /// Transfer-hook extra accounts for the offered mints.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let mut remaining_accounts = self.token_programs.refund_accounts(&self.bundle);
+        remaining_accounts.extend_from_slice(&self.remaining_accounts);
+
+        instruction(
+            anchor_escrow::accounts::RefundBundle {
+                maker: self.bundle.maker,
+                bundle: pda::bundle(&self.bundle.maker, self.bundle.seed),
+                system_program: system_program::ID,
+            },
+            anchor_escrow::instruction::RefundBundle {},
+            &remaining_accounts,
+        )
+    }
+}
+
+/// Builds `auto_refund_bundle`, which `cranker` may send once the bundle has expired.
+#[derive(Clone, Debug)]
+pub struct AutoRefundBundleBuilder {
+    cranker: Pubkey,
+    bundle: BundleEscrow,
+    token_programs: BundleTokenPrograms,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl AutoRefundBundleBuilder {
+    pub fn new(cranker: Pubkey, bundle: &BundleEscrow) -> Self {
+        Self {
+            cranker,
+            bundle: bundle.clone(),
+            token_programs: BundleTokenPrograms::default(),
+            remaining_accounts: vec![],
+        }
+    }
+
+    pub fn token_program(mut self, mint: Pubkey, token_program: Pubkey) -> Self {
+        self.token_programs.set(mint, token_program);
+        self
+    }
+
+    /// Transfer-hook extra accounts for the offered mints.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let mut remaining_accounts = self.token_programs.refund_accounts(&self.bundle);
+        remaining_accounts.extend_from_slice(&self.remaining_accounts);
+
+        instruction(
+            anchor_escrow::accounts::AutoRefundBundle {
+                cranker: self.cranker,
+                maker: self.bundle.maker,
+                bundle: pda::bundle(&self.bundle.maker, self.bundle.seed),
+                system_program: system_program::ID,
+            },
+            anchor_escrow::instruction::AutoRefundBundle { _seed: self.bundle.seed },
+            &remaining_accounts,
+        )
+    }
+}
+
+/// Accounts naming the asset of an asset escrow; the rest are `None`. A Core
+/// asset passes its collection, which Core needs to move it, and an SPL NFT
+/// its Token Metadata account when a collection is involved.
+struct AssetAccounts {
+    asset: Option<Pubkey>,
+    collection: Option<Pubkey>,
+    core_program: Option<Pubkey>,
+    nft_mint: Option<Pubkey>,
+    nft_metadata: Option<Pubkey>,
+}
+
+impl AssetAccounts {
+    fn new(kind: AssetKind, asset: Option<Pubkey>, collection: Option<Pubkey>) -> Self {
+        match kind {
+            AssetKind::Core => Self {
+                asset,
+                collection,
+                core_program: Some(MPL_CORE_ID),
+                nft_mint: None,
+                nft_metadata: None,
+            },
+            AssetKind::SplNft => Self {
+                asset: None,
+                collection: None,
+                core_program: None,
+                nft_mint: asset,
+                nft_metadata: asset.filter(|_| collection.is_some()).map(|mint| pda::nft_metadata(&mint)),
+            },
+        }
+    }
+}
+
+/// Builds `make_asset`. Set the fungible leg with [`price`](Self::price) and
+/// the asset with [`offer_asset`](Self::offer_asset) or [`request_asset`](Self::request_asset).
+#[derive(Clone, Debug)]
+pub struct MakeAssetBuilder {
+    maker: Pubkey,
+    seed: u64,
+    kind: AssetKind,
+    side: AssetSide,
+    asset: Option<Pubkey>,
+    collection: Option<Pubkey>,
+    mint: Pubkey,
+    amount: u64,
+    token_program: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl MakeAssetBuilder {
+    pub fn new(maker: Pubkey, seed: u64) -> Self {
+        Self {
+            maker,
+            seed,
+            kind: AssetKind::Core,
+            side: AssetSide::Offered,
+            asset: None,
+            collection: None,
+            mint: Pubkey::default(),
+            amount: 0,
+            token_program: token::ID,
+            remaining_accounts: vec![],
+        }
+    }
+
+    /// `amount` of `mint` asked for an offered asset, or deposited for a requested one.
+    pub fn price(mut self, mint: Pubkey, amount: u64) -> Self {
+        self.mint = mint;
+        self.amount = amount;
+        self
+    }
+
+    /// Offers `asset`: a Core asset with its collection, if it has one, or an
+    /// SPL NFT mint. For an SPL NFT, `Some` collection records its verified
+    /// Metaplex collection.
+    pub fn offer_asset(mut self, kind: AssetKind, asset: Pubkey, collection: Option<Pubkey>) -> Self {
+        self.kind = kind;
+        self.side = AssetSide::Offered;
+        self.asset = Some(asset);
+        self.collection = collection;
+        self
+    }
+
+    /// Asks for `asset`, any asset of `collection`, or an asset matching both.
+    pub fn request_asset(mut self, kind: AssetKind, asset: Option<Pubkey>, collection: Option<Pubkey>) -> Self {
+        self.kind = kind;
+        self.side = AssetSide::Requested;
+        self.asset = asset;
+        self.collection = collection;
+        self
+    }
+
+    pub fn token_program(mut self, token_program: Pubkey) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    /// Transfer-hook extra accounts for the fungible mint.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn escrow(&self) -> Pubkey {
+        pda::asset_escrow(&self.maker, self.seed)
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let escrow = self.escrow();
+        let offered = self.side == AssetSide::Offered;
+        let requested = |account: Pubkey| Some(account).filter(|_| !offered);
+        let assets = if offered {
+            AssetAccounts::new(self.kind, self.asset, self.collection)
+        } else {
+            AssetAccounts::new(self.kind, None, None)
+        };
+        let nft_ata = |owner: &Pubkey| assets.nft_mint.map(|nft_mint| pda::ata(owner, &nft_mint, &self.token_program));
+
+        instruction(
+            anchor_escrow::accounts::MakeAsset {
+                maker: self.maker,
+                mint: self.mint,
+                maker_ata: requested(pda::ata(&self.maker, &self.mint, &self.token_program)),
+                escrow,
+                vault: requested(pda::ata(&escrow, &self.mint, &self.token_program)),
+                asset: assets.asset,
+                collection: assets.collection,
+                nft_mint: assets.nft_mint,
+                maker_nft_ata: nft_ata(&self.maker),
+                nft_vault: nft_ata(&escrow),
+                nft_metadata: assets.nft_metadata,
+                config: pda::config(),
+                core_program: assets.core_program,
+                associated_token_program: associated_token::ID,
+                token_program: self.token_program,
+                system_program: system_program::ID,
+            },
+            anchor_escrow::instruction::MakeAsset {
+                seed: self.seed,
+                kind: self.kind,
+                side: self.side,
+                asset: self.asset,
+                collection: self.collection,
+                amount: self.amount,
+            },
+            &self.remaining_accounts,
+        )
+    }
+}
+
+/// Builds `take_asset` against a fetched asset escrow and config. When the
+/// escrow asks for any asset of a collection, name the one delivered with
+/// [`asset`](Self::asset).
+#[derive(Clone, Debug)]
+pub struct TakeAssetBuilder {
+    taker: Pubkey,
+    maker: Pubkey,
+    seed: u64,
+    kind: AssetKind,
+    side: AssetSide,
+    asset: Option<Pubkey>,
+    collection: Option<Pubkey>,
+    mint: Pubkey,
+    fee_recipient: Pubkey,
+    charges_fee: bool,
+    token_program: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl TakeAssetBuilder {
+    pub fn new(taker: Pubkey, escrow: &AssetEscrow, config: &Config) -> Self {
+        Self {
+            taker,
+            maker: escrow.maker,
+            seed: escrow.seed,
+            kind: escrow.kind,
+            side: escrow.side,
+            asset: escrow.asset,
+            collection: escrow.collection,
+            mint: escrow.mint,
+            fee_recipient: config.fee_recipient,
+            charges_fee: config.fee_bps > 0,
+            token_program: token::ID,
+            remaining_accounts: vec![],
+        }
+    }
+
+    /// The asset the taker delivers to a requesting escrow, and its collection.
+    pub fn asset(mut self, asset: Pubkey, collection: Option<Pubkey>) -> Self {
+        self.asset = Some(asset);
+        self.collection = collection;
+        self
+    }
+
+    pub fn token_program(mut self, token_program: Pubkey) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    /// Transfer-hook extra accounts for the fungible mint.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let escrow = pda::asset_escrow(&self.maker, self.seed);
+        let offered = self.side == AssetSide::Offered;
+        let assets = AssetAccounts::new(self.kind, self.asset, self.collection);
+        let ata = |owner: &Pubkey| pda::ata(owner, &self.mint, &self.token_program);
+        let nft_ata = |owner: &Pubkey| assets.nft_mint.map(|nft_mint| pda::ata(owner, &nft_mint, &self.token_program));
+
+        harvest_mints(
+            instruction(
+                anchor_escrow::accounts::TakeAsset {
+                    taker: self.taker,
+                    maker: self.maker,
+                    mint: self.mint,
+                    taker_ata: Some(ata(&self.taker)),
+                    maker_ata: Some(ata(&self.maker)).filter(|_| offered),
+                    escrow,
+                    vault: Some(ata(&escrow)).filter(|_| !offered),
+                    asset: assets.asset,
+                    collection: assets.collection,
+                    nft_mint: assets.nft_mint,
+                    taker_nft_ata: nft_ata(&self.taker),
+                    maker_nft_ata: nft_ata(&self.maker),
+                    nft_vault: nft_ata(&escrow).filter(|_| offered),
+                    // The collection of an offered SPL NFT was verified on make
+                    nft_metadata: assets.nft_metadata.filter(|_| !offered),
+                    config: pda::config(),
+                    fee_recipient: self.fee_recipient,
+                    treasury_ata: Some(ata(&self.fee_recipient)).filter(|_| self.charges_fee),
+                    core_program: assets.core_program,
+                    associated_token_program: associated_token::ID,
+                    token_program: self.token_program,
+                    system_program: system_program::ID,
+                },
+                anchor_escrow::instruction::TakeAsset {},
+                &self.remaining_accounts,
+            ),
+            &self.token_program,
+            &[Some(self.mint), assets.nft_mint],
+        )
+    }
+}
+
+/// Builds `refund_asset` for the maker of a fetched asset escrow.
+#[derive(Clone, Debug)]
+pub struct RefundAssetBuilder {
+    maker: Pubkey,
+    seed: u64,
+    kind: AssetKind,
+    side: AssetSide,
+    asset: Option<Pubkey>,
+    collection: Option<Pubkey>,
+    mint: Pubkey,
+    token_program: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+}
+
+impl RefundAssetBuilder {
+    pub fn new(escrow: &AssetEscrow) -> Self {
+        Self {
+            maker: escrow.maker,
+            seed: escrow.seed,
+            kind: escrow.kind,
+            side: escrow.side,
+            asset: escrow.asset,
+            collection: escrow.collection,
+            mint: escrow.mint,
+            token_program: token::ID,
+            remaining_accounts: vec![],
+        }
+    }
+
+    pub fn token_program(mut self, token_program: Pubkey) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    /// Transfer-hook extra accounts for the fungible mint.
+    pub fn remaining_accounts(mut self, remaining_accounts: Vec<AccountMeta>) -> Self {
+        self.remaining_accounts = remaining_accounts;
+        self
+    }
+
+    pub fn instruction(&self) -> Instruction {
+        let escrow = pda::asset_escrow(&self.maker, self.seed);
+        let offered = self.side == AssetSide::Offered;
+        let requested = |account: Pubkey| Some(account).filter(|_| !offered);
+        let assets = if offered {
+            AssetAccounts::new(self.kind, self.asset, self.collection)
+        } else {
+            AssetAccounts::new(self.kind, None, None)
+        };
+        let nft_ata = |owner: &Pubkey| assets.nft_mint.map(|nft_mint| pda::ata(owner, &nft_mint, &self.token_program));
+
+        harvest_mints(
+            instruction(
+                anchor_escrow::accounts::RefundAsset {
+                    maker: self.maker,
+                    mint: self.mint,
+                    maker_ata: requested(pda::ata(&self.maker, &self.mint, &self.token_program)),
+                    escrow,
+                    vault: requested(pda::ata(&escrow, &self.mint, &self.token_program)),
+                    asset: assets.asset,
+                    collection: assets.collection,
+                    nft_mint: assets.nft_mint,
+                    maker_nft_ata: nft_ata(&self.maker),
+                    nft_vault: nft_ata(&escrow),
+                    core_program: assets.core_program,
+                    token_program: self.token_program,
+                    system_program: system_program::ID,
+                },
+                anchor_escrow::instruction::RefundAsset {},
+                &self.remaining_accounts,
+            ),
+            &self.token_program,
+            &[Some(self.mint), assets.nft_mint],
+        )
+    }
+}
+
+/// `admin` must be the program's upgrade authority.
+pub fn init_config(admin: Pubkey, fee_bps: u16, fee_recipient: Pubkey) -> Instruction {
+    instruction(
+        anchor_escrow::accounts::InitConfig {
+            admin,
+            program: PROGRAM_ID,
+            program_data: pda::program_data(),
+            config: pda::config(),
+            system_program: system_program::ID,
+        },
+        anchor_escrow::instruction::InitConfig { fee_bps, fee_recipient },
+        &[],
+    )
+}
+
+pub fn update_config(admin: Pubkey, fee_bps: Option<u16>, fee_recipient: Option<Pubkey>, paused: Option<bool>) -> Instruction {
+    instruction(
+        anchor_escrow::accounts::UpdateConfig {
+            admin,
+            config: pda::config(),
+        },
+        anchor_escrow::instruction::UpdateConfig { fee_bps, fee_recipient, paused },
+        &[],
+    )
+}
+
+pub fn update_scheduling(
+    admin: Pubkey,
+    min_crank_reward: Option<u64>,
+    max_crank_reward: Option<u64>,
+    task_queues: Option<Vec<Pubkey>>,
+) -> Instruction {
+    instruction(
+        anchor_escrow::accounts::UpdateConfig {
+            admin,
+            config: pda::config(),
+        },
+        anchor_escrow::instruction::UpdateScheduling { min_crank_reward, max_crank_reward, task_queues },
+        &[],
+    )
+}
+
+/// Proposes `new_admin` for the config, or withdraws the proposal with `None`.
+pub fn transfer_admin(admin: Pubkey, new_admin: Option<Pubkey>) -> Instruction {
+    instruction(
+        anchor_escrow::accounts::UpdateConfig {
+            admin,
+            config: pda::config(),
+        },
+        anchor_escrow::instruction::TransferAdmin { new_admin },
+        &[],
+    )
+}
+
+/// Signed by the admin proposed with [`transfer_admin`] to take over the config.
+pub fn accept_admin(pending_admin: Pubkey) -> Instruction {
+    instruction(
+        anchor_escrow::accounts::AcceptAdmin {
+            pending_admin,
+            config: pda::config(),
+        },
+        anchor_escrow::instruction::AcceptAdmin {},
+        &[],
+    )
+}
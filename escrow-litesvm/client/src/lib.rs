@@ -0,0 +1,14 @@
+//! Off-chain client for the anchor-escrow program.
+//!
+//! Derives the program's PDAs and associated token accounts, builds its
+//! instructions from a handful of typed inputs and decodes its accounts, so
+//! callers never have to assemble account lists by hand.
+
+pub mod accounts;
+pub mod instructions;
+pub mod pda;
+
+pub use accounts::*;
+pub use instructions::*;
+
+pub use anchor_escrow::{state, ID as PROGRAM_ID};
@@ -0,0 +1,80 @@
+use anchor_lang::{prelude::{ProgramData, Pubkey}, Id, Owner};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use tuktuk_program::tuktuk::program::Tuktuk;
+
+use crate::{state::TOKEN_METADATA_ID, PROGRAM_ID};
+
+pub fn escrow(maker: &Pubkey, seed: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"escrow", maker.as_ref(), &seed.to_le_bytes()], &PROGRAM_ID).0
+}
+
+pub fn bundle(maker: &Pubkey, seed: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"bundle", maker.as_ref(), &seed.to_le_bytes()], &PROGRAM_ID).0
+}
+
+pub fn asset_escrow(maker: &Pubkey, seed: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"asset_escrow", maker.as_ref(), &seed.to_le_bytes()], &PROGRAM_ID).0
+}
+
+/// Counter-offer of `taker` on `escrow`.
+pub fn counter_offer(escrow: &Pubkey, taker: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"counter_offer", escrow.as_ref(), taker.as_ref()], &PROGRAM_ID).0
+}
+
+/// Page `page` of the index of the open escrows offering `mint_a` for `mint_b`;
+/// either may be [`NATIVE_SOL`](crate::state::NATIVE_SOL).
+pub fn pair_index(mint_a: &Pubkey, mint_b: &Pubkey, page: u32) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"pair_index", mint_a.as_ref(), mint_b.as_ref(), &page.to_le_bytes()],
+        &PROGRAM_ID,
+    )
+    .0
+}
+
+/// Page `page` of the index of the open escrows of `maker`.
+pub fn maker_index(maker: &Pubkey, page: u32) -> Pubkey {
+    Pubkey::find_program_address(&[b"maker_index", maker.as_ref(), &page.to_le_bytes()], &PROGRAM_ID).0
+}
+
+pub fn config() -> Pubkey {
+    Pubkey::find_program_address(&[b"config"], &PROGRAM_ID).0
+}
+
+/// Upgradeable-loader account holding the program's upgrade authority.
+pub fn program_data() -> Pubkey {
+    Pubkey::find_program_address(&[PROGRAM_ID.as_ref()], &ProgramData::owner()).0
+}
+
+/// PDA that signs the program's CPIs into TukTuk.
+pub fn queue_authority() -> Pubkey {
+    Pubkey::find_program_address(&[b"queue_authority"], &PROGRAM_ID).0
+}
+
+/// Associated token account of `owner` for `mint` under `token_program`;
+/// vaults are the escrow PDA's ATA for mint_a.
+pub fn ata(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
+
+/// Token Metadata account of the SPL NFT `mint`.
+pub fn nft_metadata(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"metadata", TOKEN_METADATA_ID.as_ref(), mint.as_ref()], &TOKEN_METADATA_ID).0
+}
+
+/// TukTuk's record that `queue_authority` may queue tasks on `task_queue`.
+pub fn task_queue_authority(task_queue: &Pubkey, queue_authority: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"task_queue_authority", task_queue.as_ref(), queue_authority.as_ref()],
+        &Tuktuk::id(),
+    )
+    .0
+}
+
+/// TukTuk task account for slot `task_id` of `task_queue`.
+pub fn task(task_queue: &Pubkey, task_id: u16) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"task", task_queue.as_ref(), &task_id.to_le_bytes()],
+        &Tuktuk::id(),
+    )
+    .0
+}
@@ -0,0 +1,108 @@
+use anchor_lang::prelude::*;
+
+/// Emitted by `update_escrow` with the terms before and after the change.
+#[event]
+pub struct EscrowUpdated {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub old_deposited: u64,
+    pub new_deposited: u64,
+    pub old_receive: u64,
+    pub new_receive: u64,
+    pub old_expires_at: Option<i64>,
+    pub new_expires_at: Option<i64>,
+}
+
+#[event]
+pub struct EscrowCreated {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    /// Net amount of mint_a held by the escrow.
+    pub deposited: u64,
+    pub receive: u64,
+    pub unlock_at: i64,
+    pub expires_at: Option<i64>,
+    pub created_at: i64,
+}
+
+/// Emitted for every fill, partial or full.
+#[event]
+pub struct EscrowTaken {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    /// Amount of mint_b paid by the taker, including the protocol fee.
+    pub amount: u64,
+    /// Amount of mint_a released from the escrow, including the protocol fee.
+    pub payout: u64,
+    pub fee_a: u64,
+    pub fee_b: u64,
+    /// Amount of mint_b still owed; zero once the escrow is closed.
+    pub remaining_receive: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EscrowRefunded {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub mint_a: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AutoRefundExecuted {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub mint_a: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RefundScheduled {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub task: Pubkey,
+    pub task_id: u16,
+    pub expiry_timestamp: i64,
+    pub crank_reward: u64,
+}
+
+#[event]
+pub struct CounterOfferMade {
+    pub escrow: Pubkey,
+    pub counter_offer: Pubkey,
+    pub taker: Pubkey,
+    /// Net amount of mint_b locked by the taker.
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CounterOfferAccepted {
+    pub escrow: Pubkey,
+    pub counter_offer: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    /// Amount of mint_b paid to the maker, including the protocol fee.
+    pub amount: u64,
+    /// Amount of mint_a released to the taker, including the protocol fee.
+    pub payout: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CounterOfferRejected {
+    pub escrow: Pubkey,
+    pub counter_offer: Pubkey,
+    pub taker: Pubkey,
+    /// Amount of mint_b returned to the taker.
+    pub amount: u64,
+    pub timestamp: i64,
+}
@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+
+use crate::state::Config;
+use crate::EscrowError;
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub pending_admin: Signer<'info>,
+    #[account(
+        mut,
+        constraint = config.pending_admin == Some(pending_admin.key()) @ EscrowError::NotPendingAdmin,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> AcceptAdmin<'info> {
+    /// Completes the transfer started by `transfer_admin`.
+    pub fn accept_admin(&mut self) -> Result<()> {
+        self.config.admin = self.pending_admin.key();
+        self.config.pending_admin = None;
+        Ok(())
+    }
+}
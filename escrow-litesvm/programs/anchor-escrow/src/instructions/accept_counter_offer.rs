@@ -0,0 +1,271 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked}};
+
+use tuktuk_program::tuktuk::program::Tuktuk;
+
+use crate::state::{Config, CounterOffer, Escrow, EscrowIndex};
+use crate::utils::{cancel_refund_task, check_native_fee, drain_vault, mint_key, required, transfer_checked, unindex_escrow};
+use crate::events::CounterOfferAccepted;
+use crate::EscrowError;
+
+/// Settles an escrow at a taker's counter-offer: the locked mint_b goes to the
+/// maker and everything left in the escrow to the taker, with the protocol fee
+/// taken from both legs as in `take`. Both the escrow and the counter-offer close.
+#[derive(Accounts)]
+pub struct AcceptCounterOffer<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(mut)]
+    pub taker: SystemAccount<'info>,
+    /// None when the offered leg is native SOL. Passed writable only for a
+    /// Token-2022 mint, whose withheld transfer fees are harvested before the vault closes
+    pub mint_a: Option<InterfaceAccount<'info, Mint>>,
+    /// None when the requested leg is native SOL. Likewise writable only for
+    /// Token-2022, for the counter vault
+    pub mint_b: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_a,
+        associated_token::authority = taker,
+    )]
+    pub taker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_b,
+        associated_token::authority = maker,
+    )]
+    pub maker_ata_b: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        constraint = escrow.mint_a == mint_key(&mint_a) @ EscrowError::InvalidMint,
+        constraint = escrow.mint_b == mint_key(&mint_b) @ EscrowError::InvalidMint,
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+    )]
+    pub escrow: Account<'info, Escrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        close = taker,
+        has_one = escrow,
+        has_one = taker,
+        constraint = counter_offer.deposited == escrow.deposited @ EscrowError::StaleCounterOffer,
+        seeds = [b"counter_offer", escrow.key().as_ref(), taker.key().as_ref()],
+        bump = counter_offer.bump,
+    )]
+    pub counter_offer: Account<'info, CounterOffer>,
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = counter_offer,
+    )]
+    pub counter_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: read by Config::load, which allows it to be uninitialized
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: Only receives fees; checked against the config by Config::check_fee_recipient
+    #[account(mut)]
+    pub fee_recipient: UncheckedAccount<'info>,
+    /// Treasury for the mint_a fee; only needed when that fee is non-zero
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_a,
+        associated_token::authority = fee_recipient,
+    )]
+    pub treasury_ata_a: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// Treasury for the mint_b fee; only needed when that fee is non-zero
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_b,
+        associated_token::authority = fee_recipient,
+    )]
+    pub treasury_ata_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// TukTuk accounts of the auto-refund queued by schedule_refund. Pass them to
+    /// dequeue the task and return its rent and crank reward to the maker
+    /// CHECK: validated against escrow.refund_task and by TukTuk
+    #[account(mut)]
+    pub task_queue: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by TukTuk
+    pub task_queue_authority: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated against escrow.refund_task
+    #[account(mut)]
+    pub task: Option<UncheckedAccount<'info>>,
+    /// CHECK: our PDA that signs the TukTuk CPI; checked when dequeuing
+    pub queue_authority: Option<UncheckedAccount<'info>>,
+    pub tuktuk_program: Option<Program<'info, Tuktuk>>,
+    /// Page of the mint-pair index the escrow is dropped from once it closes
+    #[account(
+        mut,
+        seeds = [
+            b"pair_index",
+            escrow.mint_a.as_ref(),
+            escrow.mint_b.as_ref(),
+            escrow.index_pages.pair.to_le_bytes().as_ref(),
+        ],
+        bump = pair_index.bump,
+    )]
+    pub pair_index: Account<'info, EscrowIndex>,
+    /// Page of the maker index the escrow is dropped from once it closes
+    #[account(
+        mut,
+        seeds = [b"maker_index", escrow.maker.as_ref(), escrow.index_pages.maker.to_le_bytes().as_ref()],
+        bump = maker_index.bump,
+    )]
+    pub maker_index: Account<'info, EscrowIndex>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> AcceptCounterOffer<'info> {
+    /// `remaining_accounts` carries any transfer-hook extra accounts for either mint.
+    pub fn settle(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let config = Config::load_for_fill(&self.config, self.fee_recipient.key)?;
+        let amount = self.pay_maker(&config, remaining_accounts)?;
+        let payout = self.release_to_taker(&config, remaining_accounts)?;
+
+        cancel_refund_task(
+            self.escrow.refund_task,
+            self.maker.to_account_info(),
+            &self.task_queue,
+            &self.task_queue_authority,
+            &self.task,
+            &self.queue_authority,
+            &self.tuktuk_program,
+        )?;
+        unindex_escrow(
+            &self.escrow.key(),
+            &self.maker.to_account_info(),
+            &mut self.pair_index,
+            &mut self.maker_index,
+        )?;
+
+        emit!(CounterOfferAccepted {
+            escrow: self.escrow.key(),
+            counter_offer: self.counter_offer.key(),
+            maker: self.maker.key(),
+            taker: self.taker.key(),
+            amount,
+            payout,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Moves the locked mint_b to the maker, minus the protocol fee, and returns the amount moved.
+    fn pay_maker(&mut self, config: &Config, remaining_accounts: &[AccountInfo<'info>]) -> Result<u64> {
+        let amount = self.counter_offer.amount;
+        let fee = config.fee_for(amount);
+
+        // Native SOL sits in the counter-offer PDA, whose rent goes back via `close = taker`
+        let Some(mint_b) = &self.mint_b else {
+            self.counter_offer.sub_lamports(amount)?;
+            self.maker.add_lamports(amount - fee)?;
+            if fee > 0 {
+                check_native_fee(&self.fee_recipient, fee)?;
+                self.fee_recipient.add_lamports(fee)?;
+            }
+            return Ok(amount);
+        };
+
+        let escrow = self.escrow.key();
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"counter_offer",
+            escrow.as_ref(),
+            self.taker.key.as_ref(),
+            &[self.counter_offer.bump],
+        ]];
+
+        let counter_vault = self.counter_vault.as_mut().ok_or(EscrowError::MissingTokenAccount)?;
+        if fee > 0 {
+            let cpi_accounts = TransferChecked {
+                from: counter_vault.to_account_info(),
+                to: required(&self.treasury_ata_b)?.to_account_info(),
+                mint: mint_b.to_account_info(),
+                authority: self.counter_offer.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds)
+                .with_remaining_accounts(remaining_accounts.to_vec());
+            transfer_checked(cpi_ctx, fee, mint_b.decimals)?;
+            counter_vault.reload()?;
+        }
+
+        drain_vault(
+            &self.token_program.to_account_info(),
+            mint_b,
+            counter_vault,
+            required(&self.maker_ata_b)?.to_account_info(),
+            self.counter_offer.to_account_info(),
+            self.taker.to_account_info(),
+            &signer_seeds,
+            remaining_accounts,
+        )?;
+
+        Ok(amount)
+    }
+
+    /// Moves everything left in the escrow to the taker, minus the protocol fee,
+    /// and returns the amount released.
+    fn release_to_taker(&mut self, config: &Config, remaining_accounts: &[AccountInfo<'info>]) -> Result<u64> {
+        // Native SOL sits in the escrow PDA, whose rent goes back via `close = maker`
+        let Some(mint_a) = &self.mint_a else {
+            let payout = self.escrow.deposited;
+            let fee = config.fee_for(payout);
+            self.escrow.sub_lamports(payout)?;
+            self.taker.add_lamports(payout - fee)?;
+            if fee > 0 {
+                check_native_fee(&self.fee_recipient, fee)?;
+                self.fee_recipient.add_lamports(fee)?;
+            }
+            return Ok(payout);
+        };
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            self.maker.key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+
+        let vault = self.vault.as_mut().ok_or(EscrowError::MissingTokenAccount)?;
+        let payout = vault.amount;
+        let fee = config.fee_for(payout);
+        if fee > 0 {
+            let cpi_accounts = TransferChecked {
+                from: vault.to_account_info(),
+                to: required(&self.treasury_ata_a)?.to_account_info(),
+                mint: mint_a.to_account_info(),
+                authority: self.escrow.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds)
+                .with_remaining_accounts(remaining_accounts.to_vec());
+            transfer_checked(cpi_ctx, fee, mint_a.decimals)?;
+            vault.reload()?;
+        }
+
+        drain_vault(
+            &self.token_program.to_account_info(),
+            mint_a,
+            vault,
+            required(&self.taker_ata_a)?.to_account_info(),
+            self.escrow.to_account_info(),
+            self.maker.to_account_info(),
+            &signer_seeds,
+            remaining_accounts,
+        )?;
+
+        Ok(payout)
+    }
+}
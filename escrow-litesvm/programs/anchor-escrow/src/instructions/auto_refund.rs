@@ -1,11 +1,16 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token_interface::{
-    transfer_checked, close_account,
+    close_account,
     Mint, TokenAccount, TokenInterface,
     TransferChecked, CloseAccount,
 };
 
-use crate::state::Escrow;
+use crate::state::{Escrow, EscrowIndex, NATIVE_SOL};
+use crate::events::AutoRefundExecuted;
+use crate::utils::{
+    ata_address, close_account_info, harvest_withheld_fees, mint_key, required,
+    token_account_amount, transfer_checked, unindex_escrow,
+};
 
 #[derive(Accounts)]
 #[instruction(seed: u64)]
@@ -14,14 +19,15 @@ pub struct AutoRefund<'info> {
     #[account(mut)]
     pub maker: UncheckedAccount<'info>,
 
-    pub mint_a: InterfaceAccount<'info, Mint>,
+    /// None when the offered leg is native SOL; writable only for Token-2022, see `Take`
+    pub mint_a: Option<InterfaceAccount<'info, Mint>>,
 
     #[account(
         mut,
         associated_token::mint = mint_a,
         associated_token::authority = maker,
     )]
-    pub maker_ata_a: InterfaceAccount<'info, TokenAccount>,
+    pub maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>,
 
     /// CHECK: Escrow PDA — we manually deserialize after checking if account is empty
     #[account(
@@ -31,9 +37,16 @@ pub struct AutoRefund<'info> {
     )]
     pub escrow: AccountInfo<'info>,
 
-    /// CHECK: Vault ATA of escrow for mint_a — may already be closed
+    /// CHECK: Vault ATA of escrow for mint_a — may already be closed, so its address
+    /// and token state are checked by hand; None for native SOL
+    #[account(mut)]
+    pub vault: Option<AccountInfo<'info>>,
+    /// Pages of the mint-pair and maker indexes the escrow is dropped from once it
+    /// closes; checked against the escrow's recorded pages once it is deserialized
     #[account(mut)]
-    pub vault: AccountInfo<'info>,
+    pub pair_index: Account<'info, EscrowIndex>,
+    #[account(mut)]
+    pub maker_index: Account<'info, EscrowIndex>,
 
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
@@ -44,6 +57,7 @@ impl<'info> AutoRefund<'info> {
         &mut self,
         seed: u64,
         escrow_bump: u8,
+        remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
         // Graceful no-op if escrow is already closed (taken or manually refunded)
         if self.escrow.data_is_empty() || self.escrow.lamports() == 0 {
@@ -60,12 +74,60 @@ impl<'info> AutoRefund<'info> {
             self.maker.key(),
             crate::EscrowError::InvalidMaker
         );
-        require_keys_eq!(escrow_data.mint_a, self.mint_a.key());
+        require_keys_eq!(
+            escrow_data.mint_a,
+            mint_key(&self.mint_a),
+            crate::EscrowError::InvalidMint
+        );
+
+        // Permissionless only once expired; before that only the maker can refund
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            escrow_data.expires_at.is_some_and(|expires_at| now >= expires_at),
+            crate::EscrowError::EscrowNotExpired
+        );
+
+        // Every path below closes the escrow
+        let pair_seeds: [&[u8]; 3] = [b"pair_index", escrow_data.mint_a.as_ref(), escrow_data.mint_b.as_ref()];
+        self.pair_index.require_page(&self.pair_index.key(), &pair_seeds, escrow_data.index_pages.pair)?;
+        let maker_seeds: [&[u8]; 2] = [b"maker_index", escrow_data.maker.as_ref()];
+        self.maker_index.require_page(&self.maker_index.key(), &maker_seeds, escrow_data.index_pages.maker)?;
+        unindex_escrow(
+            &self.escrow.key(),
+            &self.maker.to_account_info(),
+            &mut self.pair_index,
+            &mut self.maker_index,
+        )?;
+
+        // Native SOL deposits are held by the escrow PDA, so closing it refunds the maker
+        if escrow_data.mint_a == NATIVE_SOL {
+            let amount = escrow_data.deposited;
+            close_account_info(&self.escrow, &self.maker.to_account_info())?;
+            self.emit_executed(&escrow_data, amount)?;
+            msg!("AutoRefund: native escrow refunded successfully");
+            return Ok(());
+        }
+        let mint_a = required(&self.mint_a)?;
+        let vault = required(&self.vault)?;
+        let token_program_key = self.token_program.key();
+
+        // The vault must be the escrow's ATA for mint_a under this token program
+        require_keys_eq!(
+            *mint_a.to_account_info().owner,
+            token_program_key,
+            crate::EscrowError::InvalidMint
+        );
+        require_keys_eq!(
+            vault.key(),
+            ata_address(&self.escrow.key(), &mint_a.key(), &token_program_key),
+            crate::EscrowError::InvalidTokenAccount
+        );
 
         // Graceful no-op if vault is already closed
-        if self.vault.data_is_empty() || self.vault.lamports() == 0 {
+        if vault.data_is_empty() || vault.lamports() == 0 {
             msg!("AutoRefund: vault already closed, closing escrow state.");
             close_account_info(&self.escrow, &self.maker.to_account_info())?;
+            self.emit_executed(&escrow_data, 0)?;
             return Ok(());
         }
 
@@ -76,29 +138,36 @@ impl<'info> AutoRefund<'info> {
             &[escrow_bump],
         ]];
 
-        // Read vault amount from raw account data (amount is at offset 64 in SPL token account)
-        let vault_data = self.vault.try_borrow_data()?;
-        let vault_amount = u64::from_le_bytes(
-            vault_data[64..72].try_into().unwrap()
-        );
-        drop(vault_data);
+        let vault_amount = token_account_amount(
+            vault,
+            &mint_a.key(),
+            &self.escrow.key(),
+            &token_program_key,
+        )?;
 
         let cpi_accounts = TransferChecked {
-            from: self.vault.to_account_info(),
-            to: self.maker_ata_a.to_account_info(),
-            mint: self.mint_a.to_account_info(),
+            from: vault.to_account_info(),
+            to: required(&self.maker_ata_a)?.to_account_info(),
+            mint: mint_a.to_account_info(),
             authority: self.escrow.to_account_info(),
         };
         let cpi_ctx = CpiContext::new_with_signer(
             self.token_program.to_account_info(),
             cpi_accounts,
             &signer_seeds,
-        );
-        transfer_checked(cpi_ctx, vault_amount, self.mint_a.decimals)?;
+        )
+        .with_remaining_accounts(remaining_accounts.to_vec());
+        transfer_checked(cpi_ctx, vault_amount, mint_a.decimals)?;
+
+        harvest_withheld_fees(
+            self.token_program.to_account_info(),
+            mint_a.to_account_info(),
+            vault.clone(),
+        )?;
 
         // Close the vault, sending lamports to maker
         let cpi_accounts = CloseAccount {
-            account: self.vault.to_account_info(),
+            account: vault.to_account_info(),
             destination: self.maker.to_account_info(),
             authority: self.escrow.to_account_info(),
         };
@@ -111,24 +180,20 @@ impl<'info> AutoRefund<'info> {
 
         // Close the escrow account, returning rent to maker
         close_account_info(&self.escrow, &self.maker.to_account_info())?;
+        self.emit_executed(&escrow_data, vault_amount)?;
 
         msg!("AutoRefund: escrow refunded successfully");
         Ok(())
     }
-}
 
-/// Manually close an AccountInfo by transferring lamports and zeroing data.
-fn close_account_info(account: &AccountInfo, destination: &AccountInfo) -> Result<()> {
-    let dest_starting_lamports = destination.lamports();
-    **destination.lamports.borrow_mut() = dest_starting_lamports
-        .checked_add(account.lamports())
-        .unwrap();
-    **account.lamports.borrow_mut() = 0;
-
-    let mut data = account.try_borrow_mut_data()?;
-    for byte in data.iter_mut() {
-        *byte = 0;
+    fn emit_executed(&self, escrow: &Escrow, amount: u64) -> Result<()> {
+        emit!(AutoRefundExecuted {
+            escrow: self.escrow.key(),
+            maker: escrow.maker,
+            mint_a: escrow.mint_a,
+            amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
     }
-
-    Ok(())
 }
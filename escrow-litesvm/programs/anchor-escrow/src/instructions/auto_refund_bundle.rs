@@ -0,0 +1,60 @@
+use anchor_lang::prelude::*;
+
+use crate::instructions::return_vaults;
+use crate::state::BundleEscrow;
+use crate::utils::close_account_info;
+use crate::EscrowError;
+
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct AutoRefundBundle<'info> {
+    /// Anyone may crank the refund once the bundle has expired
+    pub cranker: Signer<'info>,
+
+    /// CHECK: We verify this matches bundle.maker after deserialization
+    #[account(mut)]
+    pub maker: UncheckedAccount<'info>,
+
+    /// CHECK: Bundle PDA — we manually deserialize after checking if account is empty
+    #[account(
+        mut,
+        seeds = [b"bundle", maker.key().as_ref(), seed.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub bundle: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> AutoRefundBundle<'info> {
+    /// Permissionless version of `refund_bundle` once the bundle has expired,
+    /// with the same remaining accounts.
+    pub fn auto_refund_bundle(&mut self, remaining_accounts: &'info [AccountInfo<'info>]) -> Result<()> {
+        // Graceful no-op if the bundle is already closed (taken or manually refunded)
+        if self.bundle.data_is_empty() || self.bundle.lamports() == 0 {
+            msg!("AutoRefundBundle: bundle already closed, nothing to do.");
+            return Ok(());
+        }
+
+        let bundle = BundleEscrow::try_deserialize(&mut self.bundle.data.borrow().as_ref())?;
+        require_keys_eq!(bundle.maker, self.maker.key(), EscrowError::InvalidMaker);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            bundle.expires_at.is_some_and(|expires_at| now >= expires_at),
+            EscrowError::EscrowNotExpired
+        );
+
+        return_vaults(
+            &bundle,
+            &self.bundle,
+            &self.maker.to_account_info(),
+            remaining_accounts,
+        )?;
+
+        close_account_info(&self.bundle, &self.maker.to_account_info())?;
+
+        msg!("AutoRefundBundle: bundle refunded successfully");
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use anchor_lang::prelude::*;
+
+use crate::program::AnchorEscrow;
+use crate::state::{Config, DEFAULT_MAX_CRANK_REWARD, MAX_FEE_BPS};
+use crate::EscrowError;
+
+/// Only the program's upgrade authority can create the config, so nobody can
+/// front-run the deployer and make themselves admin.
+#[derive(Accounts)]
+pub struct InitConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ EscrowError::Unauthorized)]
+    pub program: Program<'info, AnchorEscrow>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ EscrowError::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"config"],
+        bump,
+        space = 8 + Config::INIT_SPACE,
+    )]
+    pub config: Account<'info, Config>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitConfig<'info> {
+    pub fn init_config(&mut self, fee_bps: u16, fee_recipient: Pubkey, bumps: &InitConfigBumps) -> Result<()> {
+        require!(fee_bps <= MAX_FEE_BPS, EscrowError::InvalidFee);
+
+        self.config.set_inner(Config {
+            admin: self.admin.key(),
+            fee_bps,
+            fee_recipient,
+            paused: false,
+            bump: bumps.config,
+            min_crank_reward: 0,
+            max_crank_reward: DEFAULT_MAX_CRANK_REWARD,
+            task_queues: vec![],
+            pending_admin: None,
+        });
+
+        Ok(())
+    }
+}
@@ -1,21 +1,27 @@
 use anchor_lang::prelude::*;
-use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}};
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked}};
 
-use crate::state::Escrow;
+use crate::state::{Config, DutchAuction, Escrow, EscrowIndex, IndexPages, MAX_ALLOWED_TAKERS};
+use crate::utils::{check_mint_extensions, mint_key, required, transfer_checked};
+use crate::events::EscrowCreated;
+use crate::EscrowError;
 
 #[derive(Accounts)]
-#[instruction(seed: u64)]
+#[instruction(seed: u64, index_pages: IndexPages)]
 pub struct Make<'info> {
     #[account(mut)]
     pub maker: Signer<'info>,
-    pub mint_a: InterfaceAccount<'info, Mint>,
-    pub mint_b: InterfaceAccount<'info, Mint>,
+    /// None to offer native SOL held by the escrow PDA
+    pub mint_a: Option<InterfaceAccount<'info, Mint>>,
+    /// None to ask for native SOL
+    pub mint_b: Option<InterfaceAccount<'info, Mint>>,
     #[account(
         mut,
         associated_token::mint = mint_a,
         associated_token::authority = maker,
     )]
-    pub maker_ata_a: InterfaceAccount<'info, TokenAccount>,
+    pub maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         init,
         payer = maker,
@@ -30,41 +36,159 @@ pub struct Make<'info> {
         associated_token::mint = mint_a,
         associated_token::authority = escrow,
     )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: read by Config::load, which allows it to be uninitialized
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// Page `index_pages.pair` of the open escrows for this mint pair; created on first use
+    #[account(
+        init_if_needed,
+        payer = maker,
+        space = EscrowIndex::space(0),
+        seeds = [
+            b"pair_index",
+            mint_key(&mint_a).as_ref(),
+            mint_key(&mint_b).as_ref(),
+            index_pages.pair.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub pair_index: Account<'info, EscrowIndex>,
+    /// Page `index_pages.maker` of the open escrows of this maker; created on first use
+    #[account(
+        init_if_needed,
+        payer = maker,
+        space = EscrowIndex::space(0),
+        seeds = [b"maker_index", maker.key().as_ref(), index_pages.maker.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub maker_index: Account<'info, EscrowIndex>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Make<'info> {
-    pub fn init_escrow(&mut self, seed: u64, receive: u64, bumps: &MakeBumps) -> Result<()> {
+    pub fn init_escrow(
+        &mut self,
+        seed: u64,
+        receive: u64,
+        unlock_at: i64,
+        expires_at: Option<i64>,
+        allowed_takers: Vec<Pubkey>,
+        bumps: &MakeBumps,
+    ) -> Result<()> {
+        require!(!Config::load(&self.config)?.paused, EscrowError::ProtocolPaused);
+        if let Some(expires_at) = expires_at {
+            require!(expires_at > unlock_at, EscrowError::InvalidTimeWindow);
+        }
+        require!(
+            allowed_takers.len() <= MAX_ALLOWED_TAKERS,
+            EscrowError::TooManyAllowedTakers
+        );
+        require!(
+            self.mint_a.is_some() || self.mint_b.is_some(),
+            EscrowError::BothLegsNative
+        );
+        for mint in [&self.mint_a, &self.mint_b].into_iter().flatten() {
+            check_mint_extensions(mint)?;
+        }
+
         self.escrow.set_inner(Escrow {
             seed,
             maker: self.maker.key(),
-            mint_a: self.mint_a.key(),
-            mint_b: self.mint_b.key(),
+            mint_a: mint_key(&self.mint_a),
+            mint_b: mint_key(&self.mint_b),
             receive,
+            deposited: 0,
             bump: bumps.escrow,
             created_at: Clock::get()?.unix_timestamp,
+            unlock_at,
+            expires_at,
+            allowed_takers,
+            refund_task: None,
+            auction: None,
+            index_pages: IndexPages::default(),
         });
 
         Ok(())
     }
 
-    pub fn deposit(&mut self, deposit: u64) -> Result<()> {
+    /// Lists the new escrow on its mint-pair and maker index pages, which must
+    /// have room for it, and records the pages on the escrow.
+    pub fn index_escrow(&mut self, index_pages: IndexPages, bumps: &MakeBumps) -> Result<()> {
+        let escrow = self.escrow.key();
+        self.escrow.index_pages = index_pages;
+        let indexes = [
+            (&mut self.pair_index, bumps.pair_index, index_pages.pair),
+            (&mut self.maker_index, bumps.maker_index, index_pages.maker),
+        ];
+
+        for (index, bump, page) in indexes {
+            index.bump = bump;
+            index.page = page;
+            EscrowIndex::push(index, escrow, self.maker.to_account_info(), self.system_program.to_account_info())?;
+        }
+
+        Ok(())
+    }
+
+    /// Prices the escrow by `auction` instead of a fixed `receive`.
+    pub fn set_auction(&mut self, auction: DutchAuction) -> Result<()> {
+        require!(auction.is_valid(), EscrowError::InvalidAuction);
+        self.escrow.receive = auction.start_price;
+        self.escrow.auction = Some(auction);
+        Ok(())
+    }
+
+    /// `remaining_accounts` carries any transfer-hook extra accounts for mint_a.
+    pub fn deposit(&mut self, deposit: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let Some(mint_a) = &self.mint_a else {
+            // Native SOL: the escrow PDA itself holds the lamports
+            let cpi_accounts = Transfer {
+                from: self.maker.to_account_info(),
+                to: self.escrow.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+            transfer(cpi_ctx, deposit)?;
+            self.escrow.deposited = deposit;
+            return Ok(());
+        };
+
         let cpi_program = self.token_program.to_account_info();
 
         let cpi_accounts = TransferChecked {
-            from: self.maker_ata_a.to_account_info(),
-            to: self.vault.to_account_info(),
+            from: required(&self.maker_ata_a)?.to_account_info(),
+            to: required(&self.vault)?.to_account_info(),
             authority: self.maker.to_account_info(),
-            mint: self.mint_a.to_account_info(),
+            mint: mint_a.to_account_info(),
         };
 
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
+
+        transfer_checked(cpi_ctx, deposit, mint_a.decimals)?;
 
-        transfer_checked(cpi_ctx, deposit, self.mint_a.decimals)?;
+        // A transfer fee means the vault holds less than was sent
+        let vault = self.vault.as_mut().ok_or(EscrowError::MissingTokenAccount)?;
+        vault.reload()?;
+        self.escrow.deposited = vault.amount;
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    pub fn emit_created(&self) -> Result<()> {
+        emit!(EscrowCreated {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            mint_a: self.escrow.mint_a,
+            mint_b: self.escrow.mint_b,
+            deposited: self.escrow.deposited,
+            receive: self.escrow.receive,
+            unlock_at: self.escrow.unlock_at,
+            expires_at: self.escrow.expires_at,
+            created_at: self.escrow.created_at,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked}};
+
+use crate::state::{AssetEscrow, AssetKind, AssetSide, Config, MPL_CORE_ID};
+use crate::utils::{check_mint_extensions, core_asset, required, spl_nft_collection, transfer_checked, transfer_core_asset};
+use crate::EscrowError;
+
+/// Opens an [`AssetEscrow`]. An offered asset moves into the escrow's custody;
+/// for a requested asset the maker deposits the fungible price instead.
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct MakeAsset<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    /// Mint of the fungible leg
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Funds the vault; only when the asset is requested
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = maker,
+    )]
+    pub maker_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        init,
+        payer = maker,
+        seeds = [b"asset_escrow", maker.key().as_ref(), seed.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + AssetEscrow::INIT_SPACE,
+    )]
+    pub escrow: Box<Account<'info, AssetEscrow>>,
+    /// Holds the fungible deposit; only when the asset is requested
+    #[account(
+        init,
+        payer = maker,
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+    )]
+    pub vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// Offered Core asset
+    /// CHECK: parsed by core_asset and validated by Core
+    #[account(mut)]
+    pub asset: Option<UncheckedAccount<'info>>,
+    /// Core collection of the offered asset, which Core requires to move it
+    /// CHECK: validated by Core against the asset
+    pub collection: Option<UncheckedAccount<'info>>,
+    /// Offered SPL NFT
+    pub nft_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+    #[account(
+        mut,
+        associated_token::mint = nft_mint,
+        associated_token::authority = maker,
+    )]
+    pub maker_nft_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        init,
+        payer = maker,
+        associated_token::mint = nft_mint,
+        associated_token::authority = escrow,
+    )]
+    pub nft_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// Token Metadata of the offered SPL NFT; pass it to record its verified collection
+    /// CHECK: address, owner and layout checked by spl_nft_collection
+    pub nft_metadata: Option<UncheckedAccount<'info>>,
+    /// CHECK: read by Config::load, which allows it to be uninitialized
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: Metaplex Core; only when a Core asset is offered
+    #[account(address = MPL_CORE_ID)]
+    pub core_program: Option<UncheckedAccount<'info>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MakeAsset<'info> {
+    /// `asset` and `collection` describe a requested asset. An offered asset
+    /// records its own key and collection instead.
+    #[allow(clippy::too_many_arguments)]
+    pub fn init_escrow(
+        &mut self,
+        seed: u64,
+        kind: AssetKind,
+        side: AssetSide,
+        asset: Option<Pubkey>,
+        collection: Option<Pubkey>,
+        amount: u64,
+        bumps: &MakeAssetBumps,
+    ) -> Result<()> {
+        require!(!Config::load(&self.config)?.paused, EscrowError::ProtocolPaused);
+        require!(amount > 0, EscrowError::InvalidFillAmount);
+        check_mint_extensions(&self.mint)?;
+
+        let (asset, collection) = match side {
+            AssetSide::Offered => self.offered_asset(kind)?,
+            AssetSide::Requested => {
+                require!(asset.is_some() || collection.is_some(), EscrowError::InvalidAsset);
+                (asset, collection)
+            }
+        };
+
+        self.escrow.set_inner(AssetEscrow {
+            seed,
+            maker: self.maker.key(),
+            kind,
+            side,
+            asset,
+            collection,
+            mint: self.mint.key(),
+            amount,
+            bump: bumps.escrow,
+            created_at: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Key and collection of the asset the maker offers, checked to be theirs to offer.
+    fn offered_asset(&self, kind: AssetKind) -> Result<(Option<Pubkey>, Option<Pubkey>)> {
+        match kind {
+            AssetKind::Core => {
+                let asset = self.asset.as_ref().ok_or(EscrowError::InvalidAsset)?;
+                let (owner, collection) = core_asset(asset, self.collection.as_deref())?;
+                require_keys_eq!(owner, self.maker.key(), EscrowError::InvalidAsset);
+                Ok((Some(asset.key()), collection))
+            }
+            AssetKind::SplNft => {
+                let nft_mint = self.nft_mint.as_ref().ok_or(EscrowError::InvalidAsset)?;
+                check_mint_extensions(nft_mint)?;
+                let collection = spl_nft_collection(nft_mint, self.nft_metadata.as_deref())?;
+                Ok((Some(nft_mint.key()), collection))
+            }
+        }
+    }
+
+    /// Moves the maker's side into the escrow. `remaining_accounts` carries any
+    /// transfer-hook extra accounts for the fungible mint.
+    pub fn deposit(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        match (self.escrow.side, self.escrow.kind) {
+            (AssetSide::Offered, AssetKind::Core) => transfer_core_asset(
+                required(&self.core_program)?,
+                self.asset.as_ref().ok_or(EscrowError::InvalidAsset)?,
+                self.collection.as_deref(),
+                &self.maker.to_account_info(),
+                &self.maker.to_account_info(),
+                &self.escrow.to_account_info(),
+                &[],
+            ),
+            (AssetSide::Offered, AssetKind::SplNft) => {
+                let nft_mint = required(&self.nft_mint)?;
+                let cpi_accounts = TransferChecked {
+                    from: required(&self.maker_nft_ata)?.to_account_info(),
+                    to: required(&self.nft_vault)?.to_account_info(),
+                    mint: nft_mint.to_account_info(),
+                    authority: self.maker.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+                transfer_checked(cpi_ctx, 1, 0)
+            }
+            (AssetSide::Requested, _) => {
+                let cpi_accounts = TransferChecked {
+                    from: required(&self.maker_ata)?.to_account_info(),
+                    to: required(&self.vault)?.to_account_info(),
+                    mint: self.mint.to_account_info(),
+                    authority: self.maker.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+                    .with_remaining_accounts(remaining_accounts.to_vec());
+                transfer_checked(cpi_ctx, self.escrow.amount, self.mint.decimals)?;
+
+                // A transfer fee leaves the vault with less than was sent
+                let vault = self.vault.as_mut().ok_or(EscrowError::MissingTokenAccount)?;
+                vault.reload()?;
+                self.escrow.amount = vault.amount;
+                Ok(())
+            }
+        }
+    }
+}
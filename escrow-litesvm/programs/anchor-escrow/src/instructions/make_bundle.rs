@@ -0,0 +1,132 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{create, AssociatedToken, Create},
+    token_interface::{Mint, TokenAccount, TransferChecked},
+};
+
+use crate::state::{BundleEscrow, BundleLeg, Config};
+use crate::utils::{ata_address, check_mint_extensions, load_leg, transfer_checked};
+use crate::EscrowError;
+
+/// Remaining accounts per offered leg: token program, mint, maker token account, vault.
+const OFFERED_LEG_ACCOUNTS: usize = 4;
+
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct MakeBundle<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(
+        init,
+        payer = maker,
+        seeds = [b"bundle", maker.key().as_ref(), seed.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + BundleEscrow::INIT_SPACE,
+    )]
+    pub bundle: Account<'info, BundleEscrow>,
+    /// CHECK: read by Config::load, which allows it to be uninitialized
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MakeBundle<'info> {
+    pub fn init_bundle(
+        &mut self,
+        seed: u64,
+        offered: &[BundleLeg],
+        requested: Vec<BundleLeg>,
+        expires_at: Option<i64>,
+        bumps: &MakeBundleBumps,
+    ) -> Result<()> {
+        require!(!Config::load(&self.config)?.paused, EscrowError::ProtocolPaused);
+        BundleEscrow::validate_legs(offered)?;
+        BundleEscrow::validate_legs(&requested)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        if let Some(expires_at) = expires_at {
+            require!(expires_at > now, EscrowError::InvalidTimeWindow);
+        }
+
+        self.bundle.set_inner(BundleEscrow {
+            seed,
+            maker: self.maker.key(),
+            offered: Vec::with_capacity(offered.len()),
+            requested,
+            bump: bumps.bundle,
+            created_at: now,
+            expires_at,
+        });
+
+        Ok(())
+    }
+
+    /// Creates a vault per offered leg and fills it from the maker.
+    ///
+    /// `remaining_accounts` holds one [token program, mint, maker token account, vault]
+    /// group per offered leg, then one mint per requested leg, then any transfer-hook
+    /// extra accounts. Each leg's token program must own its mint.
+    pub fn deposit_legs(
+        &mut self,
+        offered: &[BundleLeg],
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<()> {
+        let offered_len = offered.len() * OFFERED_LEG_ACCOUNTS;
+        let requested_len = self.bundle.requested.len();
+        require!(
+            remaining_accounts.len() >= offered_len + requested_len,
+            EscrowError::InvalidRemainingAccounts
+        );
+        let (offered_accounts, rest) = remaining_accounts.split_at(offered_len);
+        let (requested_mints, hook_accounts) = rest.split_at(requested_len);
+
+        for (leg, mint_info) in self.bundle.requested.iter().zip(requested_mints) {
+            let mint = InterfaceAccount::<Mint>::try_from(mint_info)?;
+            require_keys_eq!(mint.key(), leg.mint, EscrowError::InvalidMint);
+            check_mint_extensions(&mint)?;
+        }
+
+        for (leg, accounts) in offered.iter().zip(offered_accounts.chunks(OFFERED_LEG_ACCOUNTS)) {
+            let [token_program, mint_info, maker_ata, vault] = accounts else {
+                return err!(EscrowError::InvalidRemainingAccounts);
+            };
+            let (token_program, mint) = load_leg(token_program, mint_info, &leg.mint)?;
+            check_mint_extensions(&mint)?;
+            require_keys_eq!(
+                vault.key(),
+                ata_address(&self.bundle.key(), &leg.mint, token_program.key),
+                EscrowError::InvalidTokenAccount
+            );
+
+            let cpi_accounts = Create {
+                payer: self.maker.to_account_info(),
+                associated_token: vault.clone(),
+                authority: self.bundle.to_account_info(),
+                mint: mint_info.clone(),
+                system_program: self.system_program.to_account_info(),
+                token_program: token_program.to_account_info(),
+            };
+            create(CpiContext::new(self.associated_token_program.to_account_info(), cpi_accounts))?;
+
+            let cpi_accounts = TransferChecked {
+                from: maker_ata.clone(),
+                to: vault.clone(),
+                authority: self.maker.to_account_info(),
+                mint: mint_info.clone(),
+            };
+            let cpi_ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts)
+                .with_remaining_accounts(hook_accounts.to_vec());
+            transfer_checked(cpi_ctx, leg.amount, mint.decimals)?;
+
+            // Record what actually landed in the vault, net of any transfer fee
+            let deposited = InterfaceAccount::<TokenAccount>::try_from(vault)?.amount;
+            self.bundle.offered.push(BundleLeg {
+                mint: leg.mint,
+                amount: deposited,
+            });
+        }
+
+        Ok(())
+    }
+}
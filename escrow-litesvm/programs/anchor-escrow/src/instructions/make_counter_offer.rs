@@ -0,0 +1,132 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked}};
+
+use crate::state::{Config, CounterOffer, Escrow};
+use crate::utils::{mint_key, required, transfer_checked};
+use crate::events::CounterOfferMade;
+use crate::EscrowError;
+
+#[derive(Accounts)]
+pub struct MakeCounterOffer<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+    /// None when the requested leg is native SOL
+    pub mint_b: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = taker,
+    )]
+    pub taker_ata_b: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        constraint = escrow.mint_b == mint_key(&mint_b) @ EscrowError::InvalidMint,
+        seeds = [b"escrow", escrow.maker.as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+    )]
+    pub escrow: Account<'info, Escrow>,
+    #[account(
+        init,
+        payer = taker,
+        seeds = [b"counter_offer", escrow.key().as_ref(), taker.key().as_ref()],
+        bump,
+        space = 8 + CounterOffer::INIT_SPACE,
+    )]
+    pub counter_offer: Account<'info, CounterOffer>,
+    /// Holds the proposed mint_b; None when it is native SOL, which the
+    /// counter-offer PDA holds itself
+    #[account(
+        init,
+        payer = taker,
+        associated_token::mint = mint_b,
+        associated_token::authority = counter_offer,
+    )]
+    pub counter_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: read by Config::load, which allows it to be uninitialized
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MakeCounterOffer<'info> {
+    /// Takers that could not `take` the escrow cannot bid on it either.
+    pub fn check_escrow(&self) -> Result<()> {
+        require!(!Config::load(&self.config)?.paused, EscrowError::ProtocolPaused);
+        let allowed = &self.escrow.allowed_takers;
+        require!(
+            allowed.is_empty() || allowed.contains(self.taker.key),
+            EscrowError::TakerNotAllowed
+        );
+        if let Some(expires_at) = self.escrow.expires_at {
+            require!(
+                Clock::get()?.unix_timestamp < expires_at,
+                EscrowError::EscrowExpired
+            );
+        }
+        Ok(())
+    }
+
+    pub fn init_counter_offer(&mut self, bumps: &MakeCounterOfferBumps) -> Result<()> {
+        self.counter_offer.set_inner(CounterOffer {
+            escrow: self.escrow.key(),
+            maker: self.escrow.maker,
+            taker: self.taker.key(),
+            mint_b: self.escrow.mint_b,
+            amount: 0,
+            deposited: self.escrow.deposited,
+            bump: bumps.counter_offer,
+            created_at: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Locks `amount` of mint_b. `remaining_accounts` carries any transfer-hook
+    /// extra accounts for mint_b.
+    pub fn lock(&mut self, amount: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        require!(amount > 0, EscrowError::InvalidFillAmount);
+
+        let Some(mint_b) = &self.mint_b else {
+            // Native SOL: the counter-offer PDA itself holds the lamports
+            let cpi_accounts = Transfer {
+                from: self.taker.to_account_info(),
+                to: self.counter_offer.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+            transfer(cpi_ctx, amount)?;
+            self.counter_offer.amount = amount;
+            return self.emit_made();
+        };
+
+        let cpi_accounts = TransferChecked {
+            from: required(&self.taker_ata_b)?.to_account_info(),
+            to: required(&self.counter_vault)?.to_account_info(),
+            authority: self.taker.to_account_info(),
+            mint: mint_b.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
+
+        transfer_checked(cpi_ctx, amount, mint_b.decimals)?;
+
+        // A transfer fee means the counter vault holds less than was sent
+        let counter_vault = self.counter_vault.as_mut().ok_or(EscrowError::MissingTokenAccount)?;
+        counter_vault.reload()?;
+        self.counter_offer.amount = counter_vault.amount;
+
+        self.emit_made()
+    }
+
+    fn emit_made(&self) -> Result<()> {
+        emit!(CounterOfferMade {
+            escrow: self.escrow.key(),
+            counter_offer: self.counter_offer.key(),
+            taker: self.taker.key(),
+            amount: self.counter_offer.amount,
+            timestamp: self.counter_offer.created_at,
+        });
+        Ok(())
+    }
+}
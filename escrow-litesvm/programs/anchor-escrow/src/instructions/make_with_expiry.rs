@@ -0,0 +1,65 @@
+use anchor_lang::prelude::*;
+use tuktuk_program::tuktuk::program::Tuktuk;
+
+use crate::instructions::make::*;
+use crate::instructions::RefundQueue;
+use crate::state::Config;
+
+#[derive(Accounts)]
+pub struct MakeWithExpiry<'info> {
+    pub make: Make<'info>,
+
+    /// CHECK: TukTuk task queue (pre-created off-chain), restricted to the config allowlist
+    #[account(mut)]
+    pub task_queue: UncheckedAccount<'info>,
+
+    /// CHECK: TukTuk task queue authority PDA
+    pub task_queue_authority: UncheckedAccount<'info>,
+
+    /// CHECK: TukTuk task account (initialized by CPI)
+    #[account(mut)]
+    pub task: UncheckedAccount<'info>,
+
+    /// CHECK: Our program's PDA that signs TukTuk CPI
+    #[account(
+        mut,
+        seeds = [b"queue_authority"],
+        bump,
+    )]
+    pub queue_authority: AccountInfo<'info>,
+
+    pub tuktuk_program: Program<'info, Tuktuk>,
+}
+
+impl<'info> MakeWithExpiry<'info> {
+    /// Queues the auto-refund for the escrow just created by `make`, due at its `expires_at`.
+    pub fn schedule_refund(
+        &mut self,
+        seed: u64,
+        expires_at: i64,
+        task_id: u16,
+        crank_reward: u64,
+        bumps: &MakeWithExpiryBumps,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let config = Config::load(&self.make.config)?;
+        require!(
+            config.task_queues.contains(self.task_queue.key),
+            crate::EscrowError::TaskQueueNotAllowed
+        );
+
+        RefundQueue {
+            maker: &self.make.maker,
+            token_program: self.make.token_program.key(),
+            config: &config,
+            task_queue: &self.task_queue,
+            task_queue_authority: &self.task_queue_authority,
+            task: &self.task,
+            queue_authority: &self.queue_authority,
+            queue_authority_bump: bumps.queue_authority,
+            system_program: &self.make.system_program,
+            tuktuk_program: &self.tuktuk_program,
+        }
+        .queue(&mut self.make.escrow, seed, task_id, expires_at, crank_reward, remaining_accounts)
+    }
+}
@@ -3,9 +3,39 @@ pub mod refund;
 pub mod take;
 pub mod auto_refund;
 pub mod schedule_refund;
+pub mod init_config;
+pub mod update_config;
+pub mod accept_admin;
+pub mod make_bundle;
+pub mod take_bundle;
+pub mod refund_bundle;
+pub mod auto_refund_bundle;
+pub mod update_escrow;
+pub mod make_with_expiry;
+pub mod make_counter_offer;
+pub mod accept_counter_offer;
+pub mod reject_counter_offer;
+pub mod make_asset;
+pub mod take_asset;
+pub mod refund_asset;
 
 pub use make::*;
 pub use refund::*;
 pub use take::*;
 pub use auto_refund::*;
-pub use schedule_refund::*;
\ No newline at end of file
+pub use schedule_refund::*;
+pub use init_config::*;
+pub use update_config::*;
+pub use accept_admin::*;
+pub use make_bundle::*;
+pub use take_bundle::*;
+pub use refund_bundle::*;
+pub use auto_refund_bundle::*;
+pub use update_escrow::*;
+pub use make_with_expiry::*;
+pub use make_counter_offer::*;
+pub use accept_counter_offer::*;
+pub use reject_counter_offer::*;
+pub use make_asset::*;
+pub use take_asset::*;
+pub use refund_asset::*;
\ No newline at end of file
@@ -1,23 +1,29 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked, CloseAccount, close_account};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, CloseAccount, close_account};
 
-use crate::state::Escrow;
+use tuktuk_program::tuktuk::program::Tuktuk;
+
+use crate::state::{Escrow, EscrowIndex};
+use crate::utils::{cancel_refund_task, harvest_withheld_fees, mint_key, required, transfer_checked, unindex_escrow};
+use crate::events::EscrowRefunded;
+use crate::EscrowError;
 
 #[derive(Accounts)]
 pub struct Refund<'info> {
     #[account(mut)]
     maker: Signer<'info>,
-    mint_a: InterfaceAccount<'info, Mint>,
+    /// None when the offered leg is native SOL; writable only for Token-2022, see `Take`
+    mint_a: Option<InterfaceAccount<'info, Mint>>,
     #[account(
         mut,
         associated_token::mint = mint_a,
         associated_token::authority = maker,
     )]
-    maker_ata_a: InterfaceAccount<'info, TokenAccount>,
+    maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         close = maker,
-        has_one = mint_a,
+        constraint = escrow.mint_a == mint_key(&mint_a) @ EscrowError::InvalidMint,
         has_one = maker,
         seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
         bump = escrow.bump,
@@ -28,13 +34,70 @@ pub struct Refund<'info> {
         associated_token::mint = mint_a,
         associated_token::authority = escrow,
     )]
-    vault: InterfaceAccount<'info, TokenAccount>,
+    vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// TukTuk accounts of the auto-refund queued by schedule_refund. Pass them to
+    /// dequeue the task and return its rent and crank reward to the maker
+    /// CHECK: validated against escrow.refund_task and by TukTuk
+    #[account(mut)]
+    task_queue: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by TukTuk
+    task_queue_authority: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated against escrow.refund_task
+    #[account(mut)]
+    task: Option<UncheckedAccount<'info>>,
+    /// CHECK: our PDA that signs the TukTuk CPI; checked when dequeuing
+    queue_authority: Option<UncheckedAccount<'info>>,
+    tuktuk_program: Option<Program<'info, Tuktuk>>,
+    /// Page of the mint-pair index the escrow is dropped from once it closes
+    #[account(
+        mut,
+        seeds = [
+            b"pair_index",
+            escrow.mint_a.as_ref(),
+            escrow.mint_b.as_ref(),
+            escrow.index_pages.pair.to_le_bytes().as_ref(),
+        ],
+        bump = pair_index.bump,
+    )]
+    pair_index: Account<'info, EscrowIndex>,
+    /// Page of the maker index the escrow is dropped from once it closes
+    #[account(
+        mut,
+        seeds = [b"maker_index", escrow.maker.as_ref(), escrow.index_pages.maker.to_le_bytes().as_ref()],
+        bump = maker_index.bump,
+    )]
+    maker_index: Account<'info, EscrowIndex>,
     token_program: Interface<'info, TokenInterface>,
     system_program: Program<'info, System>,
 }
 
 impl<'info> Refund<'info> {
-    pub fn refund_and_close_vault(&mut self) -> Result<()> {
+    /// `remaining_accounts` carries any transfer-hook extra accounts for mint_a.
+    pub fn refund_and_close_vault(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        cancel_refund_task(
+            self.escrow.refund_task,
+            self.maker.to_account_info(),
+            &self.task_queue,
+            &self.task_queue_authority,
+            &self.task,
+            &self.queue_authority,
+            &self.tuktuk_program,
+        )?;
+        unindex_escrow(
+            &self.escrow.key(),
+            &self.maker.to_account_info(),
+            &mut self.pair_index,
+            &mut self.maker_index,
+        )?;
+
+        // Native SOL deposits live in the escrow PDA itself and are returned by `close = maker`
+        let Some(mint_a) = &self.mint_a else {
+            let amount = self.escrow.deposited;
+            return self.emit_refunded(amount);
+        };
+        let vault = required(&self.vault)?;
+        let amount = vault.amount;
+
         let signer_seeds: [&[&[u8]]; 1] = [&[
             b"escrow",
             self.maker.key.as_ref(),
@@ -45,20 +108,27 @@ impl<'info> Refund<'info> {
         let cpi_program = self.token_program.to_account_info();
 
         let cpi_accounts = TransferChecked {
-            from: self.vault.to_account_info(),
-            to: self.maker_ata_a.to_account_info(),
-            mint: self.mint_a.to_account_info(),
+            from: vault.to_account_info(),
+            to: required(&self.maker_ata_a)?.to_account_info(),
+            mint: mint_a.to_account_info(),
             authority: self.escrow.to_account_info(),
         };
 
-        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds)
+            .with_remaining_accounts(remaining_accounts.to_vec());
+
+        transfer_checked(cpi_context, amount, mint_a.decimals)?;
 
-        transfer_checked(cpi_context, self.vault.amount, self.mint_a.decimals)?;
+        harvest_withheld_fees(
+            self.token_program.to_account_info(),
+            mint_a.to_account_info(),
+            vault.to_account_info(),
+        )?;
 
         let cpi_program = self.token_program.to_account_info();
 
         let cpi_accounts = CloseAccount {
-            account: self.vault.to_account_info(),
+            account: vault.to_account_info(),
             destination: self.maker.to_account_info(),
             authority: self.escrow.to_account_info(),
         };
@@ -66,7 +136,18 @@ impl<'info> Refund<'info> {
         let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
 
         close_account(cpi_context)?;
-        
+
+        self.emit_refunded(amount)
+    }
+
+    fn emit_refunded(&self, amount: u64) -> Result<()> {
+        emit!(EscrowRefunded {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            mint_a: self.escrow.mint_a,
+            amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
         Ok(())
     }
 }
\ No newline at end of file
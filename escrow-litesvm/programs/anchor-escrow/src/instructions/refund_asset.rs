@@ -0,0 +1,111 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::state::{AssetEscrow, AssetKind, AssetSide, MPL_CORE_ID};
+use crate::utils::{drain_vault, required, transfer_core_asset};
+use crate::EscrowError;
+
+/// Returns whatever the maker put into an [`AssetEscrow`] and closes it.
+#[derive(Accounts)]
+pub struct RefundAsset<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    /// Writable only for Token-2022, see `TakeAsset`
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = maker,
+    )]
+    pub maker_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        has_one = mint @ EscrowError::InvalidMint,
+        seeds = [b"asset_escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+    )]
+    pub escrow: Box<Account<'info, AssetEscrow>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+    )]
+    pub vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// CHECK: checked against the escrow and validated by Core
+    #[account(mut)]
+    pub asset: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by Core against the asset
+    pub collection: Option<UncheckedAccount<'info>>,
+    pub nft_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+    #[account(
+        mut,
+        associated_token::mint = nft_mint,
+        associated_token::authority = maker,
+    )]
+    pub maker_nft_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        associated_token::mint = nft_mint,
+        associated_token::authority = escrow,
+    )]
+    pub nft_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// CHECK: Metaplex Core; only for an offered Core asset
+    #[account(address = MPL_CORE_ID)]
+    pub core_program: Option<UncheckedAccount<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> RefundAsset<'info> {
+    /// `remaining_accounts` carries any transfer-hook extra accounts for the fungible mint.
+    pub fn refund(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"asset_escrow",
+            self.maker.key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+
+        match (self.escrow.side, self.escrow.kind) {
+            (AssetSide::Offered, AssetKind::Core) => {
+                let asset = self.asset.as_ref().ok_or(EscrowError::InvalidAsset)?;
+                require!(self.escrow.asset == Some(asset.key()), EscrowError::InvalidAsset);
+                transfer_core_asset(
+                    required(&self.core_program)?,
+                    asset,
+                    self.collection.as_deref(),
+                    &self.maker.to_account_info(),
+                    &self.escrow.to_account_info(),
+                    &self.maker.to_account_info(),
+                    &signer_seeds,
+                )
+            }
+            (AssetSide::Offered, AssetKind::SplNft) => {
+                let nft_mint = self.nft_mint.as_ref().ok_or(EscrowError::InvalidAsset)?;
+                require!(self.escrow.asset == Some(nft_mint.key()), EscrowError::InvalidAsset);
+                drain_vault(
+                    &self.token_program.to_account_info(),
+                    nft_mint,
+                    required(&self.nft_vault)?,
+                    required(&self.maker_nft_ata)?.to_account_info(),
+                    self.escrow.to_account_info(),
+                    self.maker.to_account_info(),
+                    &signer_seeds,
+                    &[],
+                )
+            }
+            (AssetSide::Requested, _) => drain_vault(
+                &self.token_program.to_account_info(),
+                &self.mint,
+                required(&self.vault)?,
+                required(&self.maker_ata)?.to_account_info(),
+                self.escrow.to_account_info(),
+                self.maker.to_account_info(),
+                &signer_seeds,
+                remaining_accounts,
+            ),
+        }
+    }
+}
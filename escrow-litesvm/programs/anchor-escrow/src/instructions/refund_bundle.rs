@@ -0,0 +1,90 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::TokenAccount;
+
+use crate::state::BundleEscrow;
+use crate::utils::{ata_address, drain_vault, load_leg};
+use crate::EscrowError;
+
+/// Remaining accounts per offered leg: token program, mint, vault, maker token account.
+const REFUND_LEG_ACCOUNTS: usize = 4;
+
+#[derive(Accounts)]
+pub struct RefundBundle<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        seeds = [b"bundle", maker.key().as_ref(), bundle.seed.to_le_bytes().as_ref()],
+        bump = bundle.bump,
+    )]
+    pub bundle: Account<'info, BundleEscrow>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> RefundBundle<'info> {
+    pub fn refund_bundle(&mut self, remaining_accounts: &'info [AccountInfo<'info>]) -> Result<()> {
+        return_vaults(
+            &self.bundle,
+            &self.bundle.to_account_info(),
+            &self.maker.to_account_info(),
+            remaining_accounts,
+        )
+    }
+}
+
+/// Empties every offered vault back to the maker and closes it.
+///
+/// `remaining_accounts` holds one [token program, mint, vault, maker token account]
+/// group per offered leg, then any transfer-hook extra accounts.
+pub fn return_vaults<'info>(
+    bundle: &BundleEscrow,
+    bundle_info: &AccountInfo<'info>,
+    maker: &AccountInfo<'info>,
+    remaining_accounts: &'info [AccountInfo<'info>],
+) -> Result<()> {
+    let groups_len = bundle.offered.len() * REFUND_LEG_ACCOUNTS;
+    require!(
+        remaining_accounts.len() >= groups_len,
+        EscrowError::InvalidRemainingAccounts
+    );
+    let (groups, hook_accounts) = remaining_accounts.split_at(groups_len);
+
+    let signer_seeds: [&[&[u8]]; 1] = [&[
+        b"bundle",
+        maker.key.as_ref(),
+        &bundle.seed.to_le_bytes()[..],
+        &[bundle.bump],
+    ]];
+
+    for (leg, accounts) in bundle.offered.iter().zip(groups.chunks(REFUND_LEG_ACCOUNTS)) {
+        let [token_program, mint_info, vault, maker_ata] = accounts else {
+            return err!(EscrowError::InvalidRemainingAccounts);
+        };
+        let (token_program, mint) = load_leg(token_program, mint_info, &leg.mint)?;
+        require_keys_eq!(
+            vault.key(),
+            ata_address(bundle_info.key, &leg.mint, token_program.key),
+            EscrowError::InvalidTokenAccount
+        );
+        require_keys_eq!(
+            maker_ata.key(),
+            ata_address(maker.key, &leg.mint, token_program.key),
+            EscrowError::InvalidTokenAccount
+        );
+
+        drain_vault(
+            &token_program.to_account_info(),
+            &mint,
+            &InterfaceAccount::<TokenAccount>::try_from(vault)?,
+            maker_ata.clone(),
+            bundle_info.clone(),
+            maker.clone(),
+            &signer_seeds,
+            hook_accounts,
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::state::CounterOffer;
+use crate::utils::{drain_vault, mint_key, required};
+use crate::events::CounterOfferRejected;
+use crate::EscrowError;
+
+/// Either the maker (rejecting) or the taker (withdrawing) can unwind a
+/// counter-offer; it does not need the escrow, so it works after the escrow
+/// has been taken or refunded.
+#[derive(Accounts)]
+pub struct RejectCounterOffer<'info> {
+    #[account(
+        constraint = authority.key() == counter_offer.maker
+            || authority.key() == counter_offer.taker @ EscrowError::Unauthorized,
+    )]
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub taker: SystemAccount<'info>,
+    /// None when the requested leg is native SOL
+    pub mint_b: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = taker,
+    )]
+    pub taker_ata_b: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        close = taker,
+        has_one = taker,
+        constraint = counter_offer.mint_b == mint_key(&mint_b) @ EscrowError::InvalidMint,
+        seeds = [b"counter_offer", counter_offer.escrow.as_ref(), taker.key().as_ref()],
+        bump = counter_offer.bump,
+    )]
+    pub counter_offer: Account<'info, CounterOffer>,
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = counter_offer,
+    )]
+    pub counter_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> RejectCounterOffer<'info> {
+    /// Returns the locked mint_b to the taker. `remaining_accounts` carries any
+    /// transfer-hook extra accounts for mint_b.
+    pub fn refund_taker(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        // Native SOL is held by the counter-offer PDA and returned by `close = taker`
+        let Some(mint_b) = &self.mint_b else {
+            let amount = self.counter_offer.amount;
+            return self.emit_rejected(amount);
+        };
+        let counter_vault = required(&self.counter_vault)?;
+        let amount = counter_vault.amount;
+
+        let escrow = self.counter_offer.escrow;
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"counter_offer",
+            escrow.as_ref(),
+            self.taker.key.as_ref(),
+            &[self.counter_offer.bump],
+        ]];
+
+        drain_vault(
+            &self.token_program.to_account_info(),
+            mint_b,
+            counter_vault,
+            required(&self.taker_ata_b)?.to_account_info(),
+            self.counter_offer.to_account_info(),
+            self.taker.to_account_info(),
+            &signer_seeds,
+            remaining_accounts,
+        )?;
+
+        self.emit_rejected(amount)
+    }
+
+    fn emit_rejected(&self, amount: u64) -> Result<()> {
+        emit!(CounterOfferRejected {
+            escrow: self.counter_offer.escrow,
+            counter_offer: self.counter_offer.key(),
+            taker: self.taker.key(),
+            amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+}
@@ -14,24 +14,31 @@ use tuktuk_program::{
     types::QueueTaskArgsV0,
 };
 
-use crate::state::Escrow;
+use crate::events::RefundScheduled;
+use crate::state::{Config, Escrow, RefundTask, NATIVE_SOL};
 
 #[derive(Accounts)]
-#[instruction(seed: u64, task_id: u16, expiry_timestamp: i64)]
+#[instruction(seed: u64, task_id: u16, expiry_timestamp: i64, crank_reward: u64)]
 pub struct ScheduleRefund<'info> {
     #[account(mut)]
     pub maker: Signer<'info>,
 
     #[account(
+        mut,
         seeds = [b"escrow", maker.key().as_ref(), seed.to_le_bytes().as_ref()],
         bump = escrow.bump,
         has_one = maker,
+        constraint = escrow.refund_task.is_none() @ crate::EscrowError::RefundAlreadyScheduled,
     )]
     pub escrow: Account<'info, Escrow>,
 
     pub token_program: Interface<'info, TokenInterface>,
 
-    /// CHECK: TukTuk task queue (pre-created off-chain)
+    /// CHECK: read by Config::load, which allows it to be uninitialized
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+
+    /// CHECK: TukTuk task queue (pre-created off-chain), restricted to the config allowlist
     #[account(mut)]
     pub task_queue: UncheckedAccount<'info>,
 
@@ -60,32 +67,122 @@ impl<'info> ScheduleRefund<'info> {
         seed: u64,
         task_id: u16,
         expiry_timestamp: i64,
+        crank_reward: u64,
         bumps: &ScheduleRefundBumps,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let config = Config::load(&self.config)?;
+        require!(
+            config.task_queues.contains(self.task_queue.key),
+            crate::EscrowError::TaskQueueNotAllowed
+        );
+
+        RefundQueue {
+            maker: &self.maker,
+            token_program: self.token_program.key(),
+            config: &config,
+            task_queue: &self.task_queue,
+            task_queue_authority: &self.task_queue_authority,
+            task: &self.task,
+            queue_authority: &self.queue_authority,
+            queue_authority_bump: bumps.queue_authority,
+            system_program: &self.system_program,
+            tuktuk_program: &self.tuktuk_program,
+        }
+        .queue(&mut self.escrow, seed, task_id, expiry_timestamp, crank_reward, remaining_accounts)
+    }
+}
+
+/// The TukTuk accounts needed to queue an escrow's auto-refund, shared by
+/// `schedule_refund` and `make_with_expiry`.
+pub struct RefundQueue<'a, 'info> {
+    pub maker: &'a Signer<'info>,
+    pub token_program: Pubkey,
+    pub config: &'a Config,
+    pub task_queue: &'a UncheckedAccount<'info>,
+    pub task_queue_authority: &'a UncheckedAccount<'info>,
+    pub task: &'a UncheckedAccount<'info>,
+    pub queue_authority: &'a AccountInfo<'info>,
+    pub queue_authority_bump: u8,
+    pub system_program: &'a Program<'info, System>,
+    pub tuktuk_program: &'a Program<'info, Tuktuk>,
+}
+
+impl<'info> RefundQueue<'_, 'info> {
+    /// Queues `auto_refund` for `escrow` to run at `expiry_timestamp` and records the task on it.
+    /// `auto_refund` only runs once the escrow has expired, so the trigger must be its
+    /// `expires_at`, which is set here if the escrow had none.
+    ///
+    /// `remaining_accounts` carries any transfer-hook extra accounts for mint_a.
+    pub fn queue(
+        &self,
+        escrow: &mut Account<'info, Escrow>,
+        seed: u64,
+        task_id: u16,
+        expiry_timestamp: i64,
+        crank_reward: u64,
+        remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
+        require!(
+            (self.config.min_crank_reward..=self.config.max_crank_reward).contains(&crank_reward),
+            crate::EscrowError::InvalidCrankReward
+        );
+
+        match escrow.expires_at {
+            Some(expires_at) => require!(
+                expiry_timestamp == expires_at,
+                crate::EscrowError::InvalidTimeWindow
+            ),
+            None => {
+                require!(
+                    expiry_timestamp > escrow.unlock_at,
+                    crate::EscrowError::InvalidTimeWindow
+                );
+                escrow.expires_at = Some(expiry_timestamp);
+            }
+        }
+
         let maker_key = self.maker.key();
-        let mint_a = self.escrow.mint_a;
-        let token_program_key = self.token_program.key();
+        let mint_a = escrow.mint_a;
+        let token_program_key = self.token_program;
+        let escrow_pda = escrow.key();
 
-        // Derive all accounts that auto_refund will need
-        let (escrow_pda, _) = Pubkey::find_program_address(
-            &[b"escrow", maker_key.as_ref(), &seed.to_le_bytes()],
+        // Passed even if never created; auto_refund skips index pages that do not exist
+        let (pair_index, _) = Pubkey::find_program_address(
+            &[
+                b"pair_index",
+                escrow.mint_a.as_ref(),
+                escrow.mint_b.as_ref(),
+                &escrow.index_pages.pair.to_le_bytes(),
+            ],
             &crate::ID,
         );
-
-        let maker_ata_a = associated_token::get_associated_token_address_with_program_id(
-            &maker_key,
-            &mint_a,
-            &token_program_key,
+        let (maker_index, _) = Pubkey::find_program_address(
+            &[b"maker_index", maker_key.as_ref(), &escrow.index_pages.maker.to_le_bytes()],
+            &crate::ID,
         );
 
-        let vault = associated_token::get_associated_token_address_with_program_id(
-            &escrow_pda,
-            &mint_a,
-            &token_program_key,
-        );
+        // Native SOL escrows have no mint or token accounts to pass
+        let (mint_a, maker_ata_a, vault) = if mint_a == NATIVE_SOL {
+            (None, None, None)
+        } else {
+            let maker_ata_a = associated_token::get_associated_token_address_with_program_id(
+                &maker_key,
+                &mint_a,
+                &token_program_key,
+            );
+
+            let vault = associated_token::get_associated_token_address_with_program_id(
+                &escrow_pda,
+                &mint_a,
+                &token_program_key,
+            );
+
+            (Some(mint_a), Some(maker_ata_a), Some(vault))
+        };
 
         // Build the auto_refund instruction for TukTuk to execute later
-        let auto_refund_ix = Instruction {
+        let mut auto_refund_ix = Instruction {
             program_id: crate::ID,
             accounts: crate::accounts::AutoRefund {
                 maker: maker_key,
@@ -93,6 +190,8 @@ impl<'info> ScheduleRefund<'info> {
                 maker_ata_a,
                 escrow: escrow_pda,
                 vault,
+                pair_index,
+                maker_index,
                 token_program: token_program_key,
                 system_program: anchor_lang::system_program::ID,
             }
@@ -100,6 +199,26 @@ impl<'info> ScheduleRefund<'info> {
             data: crate::instruction::AutoRefund { seed }.data(),
         };
 
+        // Only a Token-2022 mint can hold withheld fees that need harvesting
+        if let Some(mint_a) = mint_a.filter(|_| token_program_key == anchor_spl::token_2022::ID) {
+            if let Some(meta) = auto_refund_ix
+                .accounts
+                .iter_mut()
+                .find(|meta| meta.pubkey == mint_a)
+            {
+                meta.is_writable = true;
+            }
+        }
+
+        // Transfer-hook extra accounts for mint_a are forwarded as given
+        auto_refund_ix
+            .accounts
+            .extend(remaining_accounts.iter().map(|account| AccountMeta {
+                pubkey: account.key(),
+                is_signer: false,
+                is_writable: account.is_writable,
+            }));
+
         // Compile to TukTuk's transaction format
         let (compiled_tx, _) = compile_transaction(vec![auto_refund_ix], vec![])
             .map_err(|_| error!(crate::EscrowError::CompileTransactionFailed))?;
@@ -107,7 +226,7 @@ impl<'info> ScheduleRefund<'info> {
         // CPI into TukTuk to register the task
         let signer_seeds: &[&[&[u8]]] = &[&[
             b"queue_authority",
-            &[bumps.queue_authority],
+            &[self.queue_authority_bump],
         ]];
 
         let cpi_accounts = QueueTaskV0 {
@@ -130,13 +249,29 @@ impl<'info> ScheduleRefund<'info> {
             QueueTaskArgsV0 {
                 trigger: TriggerV0::Timestamp(expiry_timestamp),
                 transaction: TransactionSourceV0::CompiledV0(compiled_tx),
-                crank_reward: Some(1_000_001),
-                free_tasks: 1,
+                crank_reward: Some(crank_reward),
+                // auto_refund never queues follow-up tasks
+                free_tasks: 0,
                 id: task_id,
                 description: format!("auto-refund-{}", seed),
             },
         )?;
 
+        escrow.refund_task = Some(RefundTask {
+            task_queue: self.task_queue.key(),
+            task: self.task.key(),
+            task_id,
+        });
+
+        emit!(RefundScheduled {
+            escrow: escrow_pda,
+            maker: maker_key,
+            task: self.task.key(),
+            task_id,
+            expiry_timestamp,
+            crank_reward,
+        });
+
         msg!(
             "Scheduled auto-refund for escrow at timestamp {}",
             expiry_timestamp
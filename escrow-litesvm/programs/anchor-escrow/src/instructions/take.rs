@@ -1,7 +1,12 @@
 use anchor_lang::prelude::*;
-use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked, CloseAccount, close_account}};
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, CloseAccount, close_account}};
 
-use crate::state::Escrow;
+use tuktuk_program::tuktuk::program::Tuktuk;
+
+use crate::state::{Config, Escrow, EscrowIndex, NATIVE_SOL};
+use crate::utils::{cancel_refund_task, check_native_fee, harvest_withheld_fees, mint_key, required, transfer_checked, unindex_escrow};
+use crate::events::EscrowTaken;
 use crate::EscrowError;
 
 //Create context
@@ -11,34 +16,36 @@ pub struct Take<'info> {
     pub taker: Signer<'info>,
     #[account(mut)]
     pub maker: SystemAccount<'info>,
-    pub mint_a: InterfaceAccount<'info, Mint>,
-    pub mint_b: InterfaceAccount<'info, Mint>,
+    /// None when the offered leg is native SOL. Passed writable only for a
+    /// Token-2022 mint, whose withheld transfer fees are harvested before the vault closes
+    pub mint_a: Option<InterfaceAccount<'info, Mint>>,
+    /// None when the requested leg is native SOL
+    pub mint_b: Option<InterfaceAccount<'info, Mint>>,
     #[account(
         init_if_needed,
         payer = taker,
         associated_token::mint = mint_a,
         associated_token::authority = taker,
     )]
-    pub taker_ata_a: InterfaceAccount<'info, TokenAccount>,
+    pub taker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         associated_token::mint = mint_b,
         associated_token::authority = taker,
     )]
-    pub taker_ata_b: InterfaceAccount<'info, TokenAccount>,
+    pub taker_ata_b: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         init_if_needed,
         payer = taker,
         associated_token::mint = mint_b,
         associated_token::authority = maker,
     )]
-    pub maker_ata_b: InterfaceAccount<'info, TokenAccount>,
+    pub maker_ata_b: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
-        close = maker,
         has_one = maker,
-        has_one = mint_a,
-        has_one = mint_b,
+        constraint = escrow.mint_a == mint_key(&mint_a) @ EscrowError::InvalidMint,
+        constraint = escrow.mint_b == mint_key(&mint_b) @ EscrowError::InvalidMint,
         seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
         bump = escrow.bump,
     )]
@@ -48,42 +55,205 @@ pub struct Take<'info> {
         associated_token::mint = mint_a,
         associated_token::authority = escrow,
     )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: read by Config::load, which allows it to be uninitialized
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: Only receives fees; checked against the config by Config::check_fee_recipient
+    #[account(mut)]
+    pub fee_recipient: UncheckedAccount<'info>,
+    /// Treasury for the mint_a fee; only needed when that fee is non-zero
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_a,
+        associated_token::authority = fee_recipient,
+    )]
+    pub treasury_ata_a: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// Treasury for the mint_b fee; only needed when that fee is non-zero
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_b,
+        associated_token::authority = fee_recipient,
+    )]
+    pub treasury_ata_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// TukTuk accounts of the auto-refund queued by schedule_refund. Pass them to
+    /// dequeue the task and return its rent and crank reward to the maker
+    /// CHECK: validated against escrow.refund_task and by TukTuk
+    #[account(mut)]
+    pub task_queue: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated by TukTuk
+    pub task_queue_authority: Option<UncheckedAccount<'info>>,
+    /// CHECK: validated against escrow.refund_task
+    #[account(mut)]
+    pub task: Option<UncheckedAccount<'info>>,
+    /// CHECK: our PDA that signs the TukTuk CPI; checked when dequeuing
+    pub queue_authority: Option<UncheckedAccount<'info>>,
+    pub tuktuk_program: Option<Program<'info, Tuktuk>>,
+    /// Page of the mint-pair index the escrow is dropped from once it closes
+    #[account(
+        mut,
+        seeds = [
+            b"pair_index",
+            escrow.mint_a.as_ref(),
+            escrow.mint_b.as_ref(),
+            escrow.index_pages.pair.to_le_bytes().as_ref(),
+        ],
+        bump = pair_index.bump,
+    )]
+    pub pair_index: Account<'info, EscrowIndex>,
+    /// Page of the maker index the escrow is dropped from once it closes
+    #[account(
+        mut,
+        seeds = [b"maker_index", escrow.maker.as_ref(), escrow.index_pages.maker.to_le_bytes().as_ref()],
+        bump = maker_index.bump,
+    )]
+    pub maker_index: Account<'info, EscrowIndex>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 //Deposit tokens from taker to maker
-//Transfer tokens from vault to taker
-//Close vault account
+//Transfer the pro-rata share of the vault to taker
+//Close vault and escrow once fully filled
 impl<'info> Take<'info> {
-    pub fn check_time_lock(&self) -> Result<()> {
+    /// Loads the protocol config, failing while it is paused.
+    pub fn load_config(&self) -> Result<Config> {
+        Config::load_for_fill(&self.config, self.fee_recipient.key)
+    }
+
+    pub fn check_time_window(&self) -> Result<()> {
         let clock = Clock::get()?;
-        let five_days: i64 = 5 * 24 * 60 * 60;
         require!(
-            clock.unix_timestamp >= self.escrow.created_at + five_days,
+            clock.unix_timestamp >= self.escrow.unlock_at,
             EscrowError::TimeLockNotExpired
         );
+        if let Some(expires_at) = self.escrow.expires_at {
+            require!(
+                clock.unix_timestamp < expires_at,
+                EscrowError::EscrowExpired
+            );
+        }
+        Ok(())
+    }
+
+    pub fn check_taker(&self) -> Result<()> {
+        let allowed = &self.escrow.allowed_takers;
+        require!(
+            allowed.is_empty() || allowed.contains(self.taker.key),
+            EscrowError::TakerNotAllowed
+        );
+        Ok(())
+    }
+
+    /// For an auction escrow, checks `amount` covers the current price and
+    /// returns that price as the amount to pay for the whole deposit.
+    /// Fixed-price escrows pay `amount` as given.
+    pub fn auction_price(&mut self, amount: u64) -> Result<u64> {
+        let Some(auction) = self.escrow.auction else {
+            return Ok(amount);
+        };
+
+        let price = auction.price_at(Clock::get()?.unix_timestamp);
+        require!(amount >= price, EscrowError::AuctionPriceNotMet);
+        self.escrow.receive = price;
+
+        Ok(price)
+    }
+
+    fn offered_balance(&self) -> Result<u64> {
+        if self.escrow.mint_a == NATIVE_SOL {
+            return Ok(self.escrow.deposited);
+        }
+        Ok(required(&self.vault)?.amount)
+    }
+
+    /// Amount of mint_a owed for paying `amount` of mint_b. Partial fills round
+    /// down so the vault never pays out more than its pro-rata share; the fill
+    /// that completes the escrow takes whatever is left in the vault.
+    pub fn payout_for(&self, amount: u64) -> Result<u64> {
+        require!(
+            amount > 0 && amount <= self.escrow.receive,
+            EscrowError::InvalidFillAmount
+        );
+
+        let offered = self.offered_balance()?;
+        if amount == self.escrow.receive {
+            return Ok(offered);
+        }
+
+        let payout = (offered as u128)
+            .checked_mul(amount as u128)
+            .and_then(|v| v.checked_div(self.escrow.receive as u128))
+            .ok_or(EscrowError::MathOverflow)? as u64;
+        require!(payout > 0, EscrowError::FillTooSmall);
+
+        Ok(payout)
+    }
+
+    /// Pays `amount` of mint_b to the maker, minus the protocol fee which goes to the treasury.
+    /// `remaining_accounts` carries any transfer-hook extra accounts for either mint.
+    pub fn deposit(&mut self, config: &Config, amount: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let fee = config.fee_for(amount);
+
+        let Some(mint_b) = &self.mint_b else {
+            self.send_lamports(self.maker.to_account_info(), amount - fee)?;
+            if fee > 0 {
+                check_native_fee(&self.fee_recipient, fee)?;
+                self.send_lamports(self.fee_recipient.to_account_info(), fee)?;
+            }
+            return Ok(());
+        };
+
+        self.send_tokens(mint_b, required(&self.maker_ata_b)?.to_account_info(), amount - fee, remaining_accounts)?;
+        if fee > 0 {
+            self.send_tokens(mint_b, required(&self.treasury_ata_b)?.to_account_info(), fee, remaining_accounts)?;
+        }
+
         Ok(())
     }
 
-    pub fn deposit(&mut self) -> Result<()> {
+    fn send_lamports(&self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
+        let cpi_accounts = Transfer {
+            from: self.taker.to_account_info(),
+            to,
+        };
+        let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+        transfer(cpi_ctx, amount)
+    }
+
+    fn send_tokens(
+        &self,
+        mint_b: &InterfaceAccount<'info, Mint>,
+        to: AccountInfo<'info>,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
         let cpi_program = self.token_program.to_account_info();
 
         let cpi_accounts = TransferChecked {
-            from: self.taker_ata_b.to_account_info(),
-            to: self.maker_ata_b.to_account_info(),
+            from: required(&self.taker_ata_b)?.to_account_info(),
+            to,
             authority: self.taker.to_account_info(),
-            mint: self.mint_b.to_account_info(),
+            mint: mint_b.to_account_info(),
         };
 
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
 
-        transfer_checked(cpi_ctx, self.escrow.receive, self.mint_b.decimals)
+        transfer_checked(cpi_ctx, amount, mint_b.decimals)
     }
 
-    pub fn withdraw_and_close_vault(&mut self) -> Result<()> {
+    /// Releases `payout` of mint_a to the taker, minus the protocol fee which goes to the treasury.
+    pub fn withdraw(
+        &mut self,
+        config: &Config,
+        amount: u64,
+        payout: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
         let signer_seeds: [&[&[u8]]; 1] = [&[
             b"escrow",
             self.maker.key.as_ref(),
@@ -91,29 +261,112 @@ impl<'info> Take<'info> {
             &[self.escrow.bump]
         ]];
 
-        let cpi_program = self.token_program.to_account_info();
+        let fee = config.fee_for(payout);
 
-        let cpi_accounts = TransferChecked {
-            from: self.vault.to_account_info(),
-            to: self.taker_ata_a.to_account_info(),
-            authority: self.escrow.to_account_info(),
-            mint: self.mint_a.to_account_info(),
-        };
+        match &self.mint_a {
+            Some(mint_a) => {
+                let taker_ata_a = required(&self.taker_ata_a)?.to_account_info();
+                self.release_tokens(mint_a, taker_ata_a, payout - fee, &signer_seeds, remaining_accounts)?;
+                if fee > 0 {
+                    let treasury_ata_a = required(&self.treasury_ata_a)?.to_account_info();
+                    self.release_tokens(mint_a, treasury_ata_a, fee, &signer_seeds, remaining_accounts)?;
+                }
+            }
+            None => {
+                // The escrow PDA is program-owned, so lamports can be moved directly
+                self.escrow.sub_lamports(payout)?;
+                self.taker.add_lamports(payout - fee)?;
+                if fee > 0 {
+                    check_native_fee(&self.fee_recipient, fee)?;
+                    self.fee_recipient.add_lamports(fee)?;
+                }
+            }
+        }
+
+        self.escrow.receive -= amount;
+        self.escrow.deposited = self.escrow.deposited.saturating_sub(payout);
+
+        emit!(EscrowTaken {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            taker: self.taker.key(),
+            mint_a: self.escrow.mint_a,
+            mint_b: self.escrow.mint_b,
+            amount,
+            payout,
+            fee_a: fee,
+            fee_b: config.fee_for(amount),
+            remaining_receive: self.escrow.receive,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        if self.escrow.receive > 0 {
+            msg!("Partial fill: {} of mint_b remaining", self.escrow.receive);
+            return Ok(());
+        }
+
+        if let Some(vault) = &self.vault {
+            harvest_withheld_fees(
+                self.token_program.to_account_info(),
+                required(&self.mint_a)?.to_account_info(),
+                vault.to_account_info(),
+            )?;
 
-        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+            let cpi_program = self.token_program.to_account_info();
 
-        transfer_checked(cpi_context, self.vault.amount, self.mint_a.decimals)?;
+            let cpi_accounts = CloseAccount {
+                account: vault.to_account_info(),
+                destination: self.maker.to_account_info(),
+                authority: self.escrow.to_account_info(),
+            };
 
+            let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+            close_account(cpi_context)?;
+        }
+
+        self.cancel_refund_task()?;
+        unindex_escrow(
+            &self.escrow.key(),
+            &self.maker.to_account_info(),
+            &mut self.pair_index,
+            &mut self.maker_index,
+        )?;
+
+        self.escrow.close(self.maker.to_account_info())
+    }
+
+    fn cancel_refund_task(&self) -> Result<()> {
+        cancel_refund_task(
+            self.escrow.refund_task,
+            self.maker.to_account_info(),
+            &self.task_queue,
+            &self.task_queue_authority,
+            &self.task,
+            &self.queue_authority,
+            &self.tuktuk_program,
+        )
+    }
+
+    fn release_tokens(
+        &self,
+        mint_a: &InterfaceAccount<'info, Mint>,
+        to: AccountInfo<'info>,
+        amount: u64,
+        signer_seeds: &[&[&[u8]]],
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
         let cpi_program = self.token_program.to_account_info();
 
-        let cpi_accounts = CloseAccount {
-            account: self.vault.to_account_info(),
-            destination: self.maker.to_account_info(),
+        let cpi_accounts = TransferChecked {
+            from: required(&self.vault)?.to_account_info(),
+            to,
             authority: self.escrow.to_account_info(),
+            mint: mint_a.to_account_info(),
         };
 
-        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds)
+            .with_remaining_accounts(remaining_accounts.to_vec());
 
-        close_account(cpi_context)
+        transfer_checked(cpi_context, amount, mint_a.decimals)
     }
 }
\ No newline at end of file
@@ -0,0 +1,255 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked}};
+
+use crate::state::{AssetEscrow, AssetKind, AssetSide, Config, MPL_CORE_ID};
+use crate::utils::{
+    check_mint_extensions, core_asset, drain_vault, required, spl_nft_collection, transfer_checked,
+    transfer_core_asset,
+};
+use crate::EscrowError;
+
+/// Settles an [`AssetEscrow`] whole: the asset goes one way and the fungible
+/// amount the other, with the protocol fee taken from the fungible leg.
+#[derive(Accounts)]
+pub struct TakeAsset<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+    /// Passed writable only for a Token-2022 mint, whose withheld transfer fees
+    /// are harvested before the vault closes
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint,
+        associated_token::authority = taker,
+    )]
+    pub taker_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint,
+        associated_token::authority = maker,
+    )]
+    pub maker_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        has_one = mint @ EscrowError::InvalidMint,
+        seeds = [b"asset_escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+    )]
+    pub escrow: Box<Account<'info, AssetEscrow>>,
+    /// Holds the fungible deposit of an escrow requesting an asset
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+    )]
+    pub vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// CHECK: checked against the escrow by core_asset and validated by Core
+    #[account(mut)]
+    pub asset: Option<UncheckedAccount<'info>>,
+    /// Core collection of the asset, which Core requires to move it
+    /// CHECK: validated by Core against the asset
+    pub collection: Option<UncheckedAccount<'info>>,
+    pub nft_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = nft_mint,
+        associated_token::authority = taker,
+    )]
+    pub taker_nft_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = nft_mint,
+        associated_token::authority = maker,
+    )]
+    pub maker_nft_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        associated_token::mint = nft_mint,
+        associated_token::authority = escrow,
+    )]
+    pub nft_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// Token Metadata of a requested SPL NFT, needed when the escrow asks for a collection
+    /// CHECK: address, owner and layout checked by spl_nft_collection
+    pub nft_metadata: Option<UncheckedAccount<'info>>,
+    /// CHECK: read by Config::load, which allows it to be uninitialized
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: Only receives fees; checked against the config by Config::check_fee_recipient
+    #[account(mut)]
+    pub fee_recipient: UncheckedAccount<'info>,
+    /// Treasury for the fee; only needed when it is non-zero
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint,
+        associated_token::authority = fee_recipient,
+    )]
+    pub treasury_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// CHECK: Metaplex Core; only for Core assets
+    #[account(address = MPL_CORE_ID)]
+    pub core_program: Option<UncheckedAccount<'info>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TakeAsset<'info> {
+    /// `remaining_accounts` carries any transfer-hook extra accounts for the fungible mint.
+    pub fn settle(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let config = Config::load_for_fill(&self.config, self.fee_recipient.key)?;
+        match self.escrow.side {
+            AssetSide::Offered => {
+                self.pay_maker(&config, remaining_accounts)?;
+                self.release_asset()
+            }
+            AssetSide::Requested => {
+                self.deliver_asset()?;
+                self.release_vault(&config, remaining_accounts)
+            }
+        }
+    }
+
+    /// Pays the asking price from the taker to the maker, minus the protocol fee.
+    fn pay_maker(&self, config: &Config, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let amount = self.escrow.amount;
+        let fee = config.fee_for(amount);
+
+        self.send_tokens(required(&self.maker_ata)?.to_account_info(), amount - fee, remaining_accounts)?;
+        if fee > 0 {
+            self.send_tokens(required(&self.treasury_ata)?.to_account_info(), fee, remaining_accounts)?;
+        }
+
+        Ok(())
+    }
+
+    fn send_tokens(&self, to: AccountInfo<'info>, amount: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: required(&self.taker_ata)?.to_account_info(),
+            to,
+            mint: self.mint.to_account_info(),
+            authority: self.taker.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
+        transfer_checked(cpi_ctx, amount, self.mint.decimals)
+    }
+
+    /// Moves the escrowed asset to the taker.
+    fn release_asset(&self) -> Result<()> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"asset_escrow",
+            self.maker.key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+
+        match self.escrow.kind {
+            AssetKind::Core => {
+                let asset = self.asset.as_ref().ok_or(EscrowError::InvalidAsset)?;
+                require!(self.escrow.asset == Some(asset.key()), EscrowError::InvalidAsset);
+                transfer_core_asset(
+                    required(&self.core_program)?,
+                    asset,
+                    self.collection.as_deref(),
+                    &self.taker.to_account_info(),
+                    &self.escrow.to_account_info(),
+                    &self.taker.to_account_info(),
+                    &signer_seeds,
+                )
+            }
+            AssetKind::SplNft => {
+                let nft_mint = self.nft_mint.as_ref().ok_or(EscrowError::InvalidAsset)?;
+                require!(self.escrow.asset == Some(nft_mint.key()), EscrowError::InvalidAsset);
+                drain_vault(
+                    &self.token_program.to_account_info(),
+                    nft_mint,
+                    required(&self.nft_vault)?,
+                    required(&self.taker_nft_ata)?.to_account_info(),
+                    self.escrow.to_account_info(),
+                    self.maker.to_account_info(),
+                    &signer_seeds,
+                    &[],
+                )
+            }
+        }
+    }
+
+    /// Moves the taker's asset to the maker once it is checked to be one the escrow asks for.
+    fn deliver_asset(&self) -> Result<()> {
+        match self.escrow.kind {
+            AssetKind::Core => {
+                let asset = self.asset.as_ref().ok_or(EscrowError::InvalidAsset)?;
+                let (_, collection) = core_asset(asset, self.collection.as_deref())?;
+                require!(self.escrow.accepts(&asset.key(), collection), EscrowError::InvalidAsset);
+                transfer_core_asset(
+                    required(&self.core_program)?,
+                    asset,
+                    self.collection.as_deref(),
+                    &self.taker.to_account_info(),
+                    &self.taker.to_account_info(),
+                    &self.maker.to_account_info(),
+                    &[],
+                )
+            }
+            AssetKind::SplNft => {
+                let nft_mint = self.nft_mint.as_ref().ok_or(EscrowError::InvalidAsset)?;
+                check_mint_extensions(nft_mint)?;
+                let collection = spl_nft_collection(nft_mint, self.nft_metadata.as_deref())?;
+                require!(self.escrow.accepts(&nft_mint.key(), collection), EscrowError::InvalidAsset);
+
+                let cpi_accounts = TransferChecked {
+                    from: required(&self.taker_nft_ata)?.to_account_info(),
+                    to: required(&self.maker_nft_ata)?.to_account_info(),
+                    mint: nft_mint.to_account_info(),
+                    authority: self.taker.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+                transfer_checked(cpi_ctx, 1, 0)
+            }
+        }
+    }
+
+    /// Moves the maker's deposit to the taker, minus the protocol fee, and closes the vault.
+    fn release_vault(&mut self, config: &Config, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"asset_escrow",
+            self.maker.key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+
+        let vault = self.vault.as_mut().ok_or(EscrowError::MissingTokenAccount)?;
+        let fee = config.fee_for(vault.amount);
+        if fee > 0 {
+            let cpi_accounts = TransferChecked {
+                from: vault.to_account_info(),
+                to: required(&self.treasury_ata)?.to_account_info(),
+                mint: self.mint.to_account_info(),
+                authority: self.escrow.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds)
+                .with_remaining_accounts(remaining_accounts.to_vec());
+            transfer_checked(cpi_ctx, fee, self.mint.decimals)?;
+            vault.reload()?;
+        }
+
+        drain_vault(
+            &self.token_program.to_account_info(),
+            &self.mint,
+            vault,
+            required(&self.taker_ata)?.to_account_info(),
+            self.escrow.to_account_info(),
+            self.maker.to_account_info(),
+            &signer_seeds,
+            remaining_accounts,
+        )
+    }
+}
@@ -0,0 +1,203 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{create_idempotent, AssociatedToken, Create},
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::state::{BundleEscrow, Config};
+use crate::utils::{ata_address, drain_vault, load_leg, transfer_checked};
+use crate::EscrowError;
+
+/// Remaining accounts per requested leg: token program, mint, taker token account,
+/// maker ATA, treasury ATA.
+const REQUESTED_LEG_ACCOUNTS: usize = 5;
+/// Remaining accounts per offered leg: token program, mint, vault, taker ATA, treasury ATA.
+const OFFERED_LEG_ACCOUNTS: usize = 5;
+
+#[derive(Accounts)]
+pub struct TakeBundle<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        seeds = [b"bundle", maker.key().as_ref(), bundle.seed.to_le_bytes().as_ref()],
+        bump = bundle.bump,
+    )]
+    pub bundle: Account<'info, BundleEscrow>,
+    /// CHECK: read by Config::load, which allows it to be uninitialized
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: Only owns the treasury ATAs; checked against the config by Config::check_fee_recipient
+    pub fee_recipient: UncheckedAccount<'info>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TakeBundle<'info> {
+    /// Pays every requested leg to the maker and empties every vault to the taker,
+    /// charging the protocol fee on each leg as `take` does on both of its legs.
+    ///
+    /// `remaining_accounts` holds one [token program, mint, taker token account, maker ATA,
+    /// treasury ATA] group per requested leg, then one [token program, mint, vault, taker ATA,
+    /// treasury ATA] group per offered leg, then any transfer-hook extra accounts. Each leg's
+    /// token program must own its mint. Missing maker, taker and treasury ATAs are created;
+    /// a treasury ATA is only touched when its fee is non-zero.
+    pub fn settle(&mut self, remaining_accounts: &'info [AccountInfo<'info>]) -> Result<()> {
+        if let Some(expires_at) = self.bundle.expires_at {
+            require!(
+                Clock::get()?.unix_timestamp < expires_at,
+                EscrowError::EscrowExpired
+            );
+        }
+        let config = Config::load_for_fill(&self.config, self.fee_recipient.key)?;
+
+        let requested_len = self.bundle.requested.len() * REQUESTED_LEG_ACCOUNTS;
+        let offered_len = self.bundle.offered.len() * OFFERED_LEG_ACCOUNTS;
+        require!(
+            remaining_accounts.len() >= requested_len + offered_len,
+            EscrowError::InvalidRemainingAccounts
+        );
+        let (requested_accounts, rest) = remaining_accounts.split_at(requested_len);
+        let (offered_accounts, hook_accounts) = rest.split_at(offered_len);
+
+        for (leg, accounts) in self
+            .bundle
+            .requested
+            .iter()
+            .zip(requested_accounts.chunks(REQUESTED_LEG_ACCOUNTS))
+        {
+            let [token_program, mint_info, taker_ata, maker_ata, treasury_ata] = accounts else {
+                return err!(EscrowError::InvalidRemainingAccounts);
+            };
+            let (token_program, mint) = load_leg(token_program, mint_info, &leg.mint)?;
+            require_keys_eq!(
+                maker_ata.key(),
+                ata_address(self.maker.key, &leg.mint, token_program.key),
+                EscrowError::InvalidTokenAccount
+            );
+            self.create_ata(&token_program, maker_ata, self.maker.to_account_info(), mint_info)?;
+
+            let fee = config.fee_for(leg.amount);
+            self.pay(&token_program, &mint, taker_ata, maker_ata, leg.amount - fee, hook_accounts)?;
+            if fee > 0 {
+                self.check_treasury(&token_program, treasury_ata, mint_info)?;
+                self.pay(&token_program, &mint, taker_ata, treasury_ata, fee, hook_accounts)?;
+            }
+        }
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"bundle",
+            self.maker.key.as_ref(),
+            &self.bundle.seed.to_le_bytes()[..],
+            &[self.bundle.bump],
+        ]];
+
+        for (leg, accounts) in self
+            .bundle
+            .offered
+            .iter()
+            .zip(offered_accounts.chunks(OFFERED_LEG_ACCOUNTS))
+        {
+            let [token_program, mint_info, vault, taker_ata, treasury_ata] = accounts else {
+                return err!(EscrowError::InvalidRemainingAccounts);
+            };
+            let (token_program, mint) = load_leg(token_program, mint_info, &leg.mint)?;
+            require_keys_eq!(
+                vault.key(),
+                ata_address(&self.bundle.key(), &leg.mint, token_program.key),
+                EscrowError::InvalidTokenAccount
+            );
+            require_keys_eq!(
+                taker_ata.key(),
+                ata_address(self.taker.key, &leg.mint, token_program.key),
+                EscrowError::InvalidTokenAccount
+            );
+            self.create_ata(&token_program, taker_ata, self.taker.to_account_info(), mint_info)?;
+
+            let fee = config.fee_for(InterfaceAccount::<TokenAccount>::try_from(vault)?.amount);
+            if fee > 0 {
+                self.check_treasury(&token_program, treasury_ata, mint_info)?;
+                let cpi_accounts = TransferChecked {
+                    from: vault.clone(),
+                    to: treasury_ata.clone(),
+                    authority: self.bundle.to_account_info(),
+                    mint: mint_info.clone(),
+                };
+                let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, &signer_seeds)
+                    .with_remaining_accounts(hook_accounts.to_vec());
+                transfer_checked(cpi_ctx, fee, mint.decimals)?;
+            }
+
+            // Reloaded so the drain sees the balance left after the fee
+            drain_vault(
+                &token_program.to_account_info(),
+                &mint,
+                &InterfaceAccount::<TokenAccount>::try_from(vault)?,
+                taker_ata.clone(),
+                self.bundle.to_account_info(),
+                self.maker.to_account_info(),
+                &signer_seeds,
+                hook_accounts,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn pay(
+        &self,
+        token_program: &Interface<'info, TokenInterface>,
+        mint: &InterfaceAccount<'info, Mint>,
+        from: &AccountInfo<'info>,
+        to: &AccountInfo<'info>,
+        amount: u64,
+        hook_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let cpi_accounts = TransferChecked {
+            from: from.clone(),
+            to: to.clone(),
+            authority: self.taker.to_account_info(),
+            mint: mint.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts)
+            .with_remaining_accounts(hook_accounts.to_vec());
+        transfer_checked(cpi_ctx, amount, mint.decimals)
+    }
+
+    /// Checks `treasury_ata` is the fee recipient's ATA for the mint and creates it if needed.
+    fn check_treasury(
+        &self,
+        token_program: &Interface<'info, TokenInterface>,
+        treasury_ata: &AccountInfo<'info>,
+        mint: &AccountInfo<'info>,
+    ) -> Result<()> {
+        require_keys_eq!(
+            treasury_ata.key(),
+            ata_address(self.fee_recipient.key, mint.key, token_program.key),
+            EscrowError::InvalidTokenAccount
+        );
+        self.create_ata(token_program, treasury_ata, self.fee_recipient.to_account_info(), mint)
+    }
+
+    fn create_ata(
+        &self,
+        token_program: &Interface<'info, TokenInterface>,
+        ata: &AccountInfo<'info>,
+        authority: AccountInfo<'info>,
+        mint: &AccountInfo<'info>,
+    ) -> Result<()> {
+        let cpi_accounts = Create {
+            payer: self.taker.to_account_info(),
+            associated_token: ata.clone(),
+            authority,
+            mint: mint.clone(),
+            system_program: self.system_program.to_account_info(),
+            token_program: token_program.to_account_info(),
+        };
+        create_idempotent(CpiContext::new(self.associated_token_program.to_account_info(), cpi_accounts))
+    }
+}
@@ -0,0 +1,68 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{Config, MAX_FEE_BPS, MAX_TASK_QUEUES};
+use crate::EscrowError;
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        has_one = admin @ EscrowError::Unauthorized,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> UpdateConfig<'info> {
+    /// Applies every setting that is provided and leaves the rest unchanged.
+    pub fn update_config(
+        &mut self,
+        fee_bps: Option<u16>,
+        fee_recipient: Option<Pubkey>,
+        paused: Option<bool>,
+    ) -> Result<()> {
+        if let Some(fee_bps) = fee_bps {
+            require!(fee_bps <= MAX_FEE_BPS, EscrowError::InvalidFee);
+            self.config.fee_bps = fee_bps;
+        }
+        if let Some(fee_recipient) = fee_recipient {
+            self.config.fee_recipient = fee_recipient;
+        }
+        if let Some(paused) = paused {
+            self.config.paused = paused;
+        }
+
+        Ok(())
+    }
+
+    /// Proposes `new_admin`, who only takes over after signing `accept_admin`, so
+    /// a mistyped key cannot lock the config. `None` withdraws the proposal.
+    pub fn transfer_admin(&mut self, new_admin: Option<Pubkey>) -> Result<()> {
+        self.config.pending_admin = new_admin;
+        Ok(())
+    }
+
+    /// Updates the crank reward bounds and task queue allowlist used by
+    /// `schedule_refund`, leaving anything not provided unchanged.
+    pub fn update_scheduling(
+        &mut self,
+        min_crank_reward: Option<u64>,
+        max_crank_reward: Option<u64>,
+        task_queues: Option<Vec<Pubkey>>,
+    ) -> Result<()> {
+        let min_crank_reward = min_crank_reward.unwrap_or(self.config.min_crank_reward);
+        let max_crank_reward = max_crank_reward.unwrap_or(self.config.max_crank_reward);
+        require!(min_crank_reward <= max_crank_reward, EscrowError::InvalidCrankReward);
+        self.config.min_crank_reward = min_crank_reward;
+        self.config.max_crank_reward = max_crank_reward;
+
+        if let Some(task_queues) = task_queues {
+            require!(task_queues.len() <= MAX_TASK_QUEUES, EscrowError::TooManyTaskQueues);
+            self.config.task_queues = task_queues;
+        }
+
+        Ok(())
+    }
+}
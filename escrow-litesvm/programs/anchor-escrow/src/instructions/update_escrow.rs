@@ -0,0 +1,160 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::events::EscrowUpdated;
+use crate::state::{DepositChange, Escrow, NATIVE_SOL};
+use crate::utils::{mint_key, required, transfer_checked};
+use crate::EscrowError;
+
+#[derive(Accounts)]
+pub struct UpdateEscrow<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    /// None when the offered leg is native SOL
+    pub mint_a: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+    )]
+    pub maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        has_one = maker,
+        constraint = escrow.mint_a == mint_key(&mint_a) @ EscrowError::InvalidMint,
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump,
+    )]
+    pub escrow: Account<'info, Escrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> UpdateEscrow<'info> {
+    /// Changes the terms of an open escrow in place, keeping its `created_at` and
+    /// any scheduled auto-refund. The expiry cannot move while an auto-refund is
+    /// scheduled, since the TukTuk task would still fire at the old one.
+    /// `remaining_accounts` carries any transfer-hook extra accounts for mint_a.
+    pub fn update_escrow(
+        &mut self,
+        deposit_change: Option<DepositChange>,
+        receive: Option<u64>,
+        expires_at: Option<i64>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let old_deposited = self.escrow.deposited;
+        let old_receive = self.escrow.receive;
+        let old_expires_at = self.escrow.expires_at;
+
+        match deposit_change {
+            Some(DepositChange::TopUp(amount)) => self.top_up(amount, remaining_accounts)?,
+            Some(DepositChange::Withdraw(amount)) => self.withdraw(amount, remaining_accounts)?,
+            None => {}
+        }
+
+        if let Some(receive) = receive {
+            // An auction's price comes from its curve, not a fixed receive
+            require!(self.escrow.auction.is_none(), EscrowError::InvalidAuction);
+            require!(receive > 0, EscrowError::InvalidFillAmount);
+            self.escrow.receive = receive;
+        }
+
+        if let Some(expires_at) = expires_at {
+            require!(self.escrow.refund_task.is_none(), EscrowError::RefundTaskPending);
+            require!(expires_at > self.escrow.unlock_at, EscrowError::InvalidTimeWindow);
+            self.escrow.expires_at = Some(expires_at);
+        }
+
+        emit!(EscrowUpdated {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            old_deposited,
+            new_deposited: self.escrow.deposited,
+            old_receive,
+            new_receive: self.escrow.receive,
+            old_expires_at,
+            new_expires_at: self.escrow.expires_at,
+        });
+
+        Ok(())
+    }
+
+    fn top_up(&mut self, amount: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        require!(amount > 0, EscrowError::InvalidDepositChange);
+
+        let Some(mint_a) = &self.mint_a else {
+            let cpi_accounts = Transfer {
+                from: self.maker.to_account_info(),
+                to: self.escrow.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+            transfer(cpi_ctx, amount)?;
+            self.escrow.deposited = self.escrow.deposited
+                .checked_add(amount)
+                .ok_or(EscrowError::MathOverflow)?;
+            return Ok(());
+        };
+
+        let cpi_accounts = TransferChecked {
+            from: required(&self.maker_ata_a)?.to_account_info(),
+            to: required(&self.vault)?.to_account_info(),
+            authority: self.maker.to_account_info(),
+            mint: mint_a.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
+        transfer_checked(cpi_ctx, amount, mint_a.decimals)?;
+
+        // A transfer fee means the vault holds less than was sent
+        let vault = self.vault.as_mut().ok_or(EscrowError::MissingTokenAccount)?;
+        vault.reload()?;
+        self.escrow.deposited = vault.amount;
+
+        Ok(())
+    }
+
+    fn withdraw(&mut self, amount: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let balance = if self.escrow.mint_a == NATIVE_SOL {
+            self.escrow.deposited
+        } else {
+            required(&self.vault)?.amount
+        };
+        require!(amount > 0 && amount < balance, EscrowError::InvalidDepositChange);
+
+        let Some(mint_a) = &self.mint_a else {
+            // The escrow PDA is program-owned, so lamports can be moved directly
+            self.escrow.sub_lamports(amount)?;
+            self.maker.add_lamports(amount)?;
+            self.escrow.deposited = self.escrow.deposited.saturating_sub(amount);
+            return Ok(());
+        };
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            self.maker.key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump]
+        ]];
+
+        let cpi_accounts = TransferChecked {
+            from: required(&self.vault)?.to_account_info(),
+            to: required(&self.maker_ata_a)?.to_account_info(),
+            authority: self.escrow.to_account_info(),
+            mint: mint_a.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds)
+            .with_remaining_accounts(remaining_accounts.to_vec());
+        transfer_checked(cpi_ctx, amount, mint_a.decimals)?;
+
+        self.escrow.deposited = self.escrow.deposited.saturating_sub(amount);
+
+        Ok(())
+    }
+}
@@ -3,11 +3,15 @@
 
 use anchor_lang::prelude::*;
 
-mod state;
+pub mod state;
 mod instructions;
+mod utils;
+mod events;
 mod tests;
 
 use instructions::*;
+use state::{AssetKind, AssetSide, BundleLeg, DepositChange, DutchAuction, IndexPages};
+pub use events::*;
 
 declare_id!("FircrADQ2wgGuvpm8qneNCfKM7o5zoHTWnDQxngpTQ3J");
 
@@ -15,41 +19,280 @@ declare_id!("FircrADQ2wgGuvpm8qneNCfKM7o5zoHTWnDQxngpTQ3J");
 pub mod anchor_escrow {
     use super::*;
 
-    pub fn make(ctx: Context<Make>, seed: u64, deposit: u64, receive: u64) -> Result<()> {
-        ctx.accounts.init_escrow(seed, receive, &ctx.bumps)?;
-        ctx.accounts.deposit(deposit)
+    /// `index_pages` picks the pages of the mint-pair and maker indexes the escrow is
+    /// listed on; each must have room for it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn make<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Make<'info>>,
+        seed: u64,
+        index_pages: IndexPages,
+        deposit: u64,
+        receive: u64,
+        unlock_at: i64,
+        expires_at: Option<i64>,
+        allowed_takers: Vec<Pubkey>,
+    ) -> Result<()> {
+        ctx.accounts.init_escrow(seed, receive, unlock_at, expires_at, allowed_takers, &ctx.bumps)?;
+        ctx.accounts.index_escrow(index_pages, &ctx.bumps)?;
+        ctx.accounts.deposit(deposit, ctx.remaining_accounts)?;
+        ctx.accounts.emit_created()
+    }
+
+    /// `make` with the requested amount set by a Dutch auction rather than a fixed `receive`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn make_auction<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Make<'info>>,
+        seed: u64,
+        index_pages: IndexPages,
+        deposit: u64,
+        auction: DutchAuction,
+        unlock_at: i64,
+        expires_at: Option<i64>,
+        allowed_takers: Vec<Pubkey>,
+    ) -> Result<()> {
+        ctx.accounts.init_escrow(seed, auction.start_price, unlock_at, expires_at, allowed_takers, &ctx.bumps)?;
+        ctx.accounts.index_escrow(index_pages, &ctx.bumps)?;
+        ctx.accounts.set_auction(auction)?;
+        ctx.accounts.deposit(deposit, ctx.remaining_accounts)?;
+        ctx.accounts.emit_created()
+    }
+
+    /// `make` and `schedule_refund` in one instruction, so the escrow can never exist
+    /// without its auto-refund queued for `expires_at`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn make_with_expiry<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MakeWithExpiry<'info>>,
+        seed: u64,
+        index_pages: IndexPages,
+        deposit: u64,
+        receive: u64,
+        unlock_at: i64,
+        expires_at: i64,
+        allowed_takers: Vec<Pubkey>,
+        task_id: u16,
+        crank_reward: u64,
+    ) -> Result<()> {
+        let make = &mut ctx.accounts.make;
+        make.init_escrow(seed, receive, unlock_at, Some(expires_at), allowed_takers, &ctx.bumps.make)?;
+        make.index_escrow(index_pages, &ctx.bumps.make)?;
+        make.deposit(deposit, ctx.remaining_accounts)?;
+        make.emit_created()?;
+        ctx.accounts.schedule_refund(seed, expires_at, task_id, crank_reward, &ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn init_config(ctx: Context<InitConfig>, fee_bps: u16, fee_recipient: Pubkey) -> Result<()> {
+        ctx.accounts.init_config(fee_bps, fee_recipient, &ctx.bumps)
+    }
+
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        fee_bps: Option<u16>,
+        fee_recipient: Option<Pubkey>,
+        paused: Option<bool>,
+    ) -> Result<()> {
+        ctx.accounts.update_config(fee_bps, fee_recipient, paused)
+    }
+
+    pub fn update_scheduling(
+        ctx: Context<UpdateConfig>,
+        min_crank_reward: Option<u64>,
+        max_crank_reward: Option<u64>,
+        task_queues: Option<Vec<Pubkey>>,
+    ) -> Result<()> {
+        ctx.accounts.update_scheduling(min_crank_reward, max_crank_reward, task_queues)
+    }
+
+    /// First step of an admin transfer; `new_admin` must then call `accept_admin`.
+    pub fn transfer_admin(ctx: Context<UpdateConfig>, new_admin: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.transfer_admin(new_admin)
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        ctx.accounts.accept_admin()
+    }
+
+    pub fn update_escrow<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UpdateEscrow<'info>>,
+        deposit_change: Option<DepositChange>,
+        receive: Option<u64>,
+        expires_at: Option<i64>,
+    ) -> Result<()> {
+        ctx.accounts.update_escrow(deposit_change, receive, expires_at, ctx.remaining_accounts)
+    }
+
+    pub fn refund<'info>(ctx: Context<'_, '_, 'info, 'info, Refund<'info>>) -> Result<()> {
+        ctx.accounts.refund_and_close_vault(ctx.remaining_accounts)
+    }
+
+    pub fn take<'info>(ctx: Context<'_, '_, 'info, 'info, Take<'info>>, amount: u64) -> Result<()> {
+        let config = ctx.accounts.load_config()?;
+        ctx.accounts.check_time_window()?;
+        ctx.accounts.check_taker()?;
+        let amount = ctx.accounts.auction_price(amount)?;
+        let payout = ctx.accounts.payout_for(amount)?;
+        ctx.accounts.deposit(&config, amount, ctx.remaining_accounts)?;
+        ctx.accounts.withdraw(&config, amount, payout, ctx.remaining_accounts)
+    }
+
+    /// Locks a taker's `amount` of mint_b as a proposal to fill the whole escrow.
+    pub fn make_counter_offer<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MakeCounterOffer<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        ctx.accounts.check_escrow()?;
+        ctx.accounts.init_counter_offer(&ctx.bumps)?;
+        ctx.accounts.lock(amount, ctx.remaining_accounts)
+    }
+
+    pub fn accept_counter_offer<'info>(ctx: Context<'_, '_, 'info, 'info, AcceptCounterOffer<'info>>) -> Result<()> {
+        ctx.accounts.settle(ctx.remaining_accounts)
+    }
+
+    /// Signed by the maker to reject a counter-offer, or by the taker to withdraw it.
+    pub fn reject_counter_offer<'info>(ctx: Context<'_, '_, 'info, 'info, RejectCounterOffer<'info>>) -> Result<()> {
+        ctx.accounts.refund_taker(ctx.remaining_accounts)
+    }
+
+    pub fn make_bundle<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MakeBundle<'info>>,
+        seed: u64,
+        offered: Vec<BundleLeg>,
+        requested: Vec<BundleLeg>,
+        expires_at: Option<i64>,
+    ) -> Result<()> {
+        ctx.accounts.init_bundle(seed, &offered, requested, expires_at, &ctx.bumps)?;
+        ctx.accounts.deposit_legs(&offered, ctx.remaining_accounts)
+    }
+
+    pub fn take_bundle<'info>(ctx: Context<'_, '_, 'info, 'info, TakeBundle<'info>>) -> Result<()> {
+        ctx.accounts.settle(ctx.remaining_accounts)
+    }
+
+    pub fn refund_bundle<'info>(ctx: Context<'_, '_, 'info, 'info, RefundBundle<'info>>) -> Result<()> {
+        ctx.accounts.refund_bundle(ctx.remaining_accounts)
+    }
+
+    pub fn auto_refund_bundle<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AutoRefundBundle<'info>>,
+        _seed: u64,
+    ) -> Result<()> {
+        ctx.accounts.auto_refund_bundle(ctx.remaining_accounts)
+    }
+
+    /// Escrow trading one Metaplex Core asset or SPL NFT against `amount` of `mint`.
+    /// `asset` and `collection` describe the asset asked for when `side` is `Requested`.
+    pub fn make_asset<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MakeAsset<'info>>,
+        seed: u64,
+        kind: AssetKind,
+        side: AssetSide,
+        asset: Option<Pubkey>,
+        collection: Option<Pubkey>,
+        amount: u64,
+    ) -> Result<()> {
+        ctx.accounts.init_escrow(seed, kind, side, asset, collection, amount, &ctx.bumps)?;
+        ctx.accounts.deposit(ctx.remaining_accounts)
     }
 
-    pub fn refund(ctx: Context<Refund>) -> Result<()> {
-        ctx.accounts.refund_and_close_vault()
+    pub fn take_asset<'info>(ctx: Context<'_, '_, 'info, 'info, TakeAsset<'info>>) -> Result<()> {
+        ctx.accounts.settle(ctx.remaining_accounts)
     }
 
-    pub fn take(ctx: Context<Take>) -> Result<()> {
-        ctx.accounts.check_time_lock()?;
-        ctx.accounts.deposit()?;
-        ctx.accounts.withdraw_and_close_vault()
+    pub fn refund_asset<'info>(ctx: Context<'_, '_, 'info, 'info, RefundAsset<'info>>) -> Result<()> {
+        ctx.accounts.refund(ctx.remaining_accounts)
     }
 
-    pub fn auto_refund(ctx: Context<AutoRefund>, seed: u64) -> Result<()> {
-        ctx.accounts.auto_refund_and_close_vault(seed, ctx.bumps.escrow)
+    pub fn auto_refund<'info>(ctx: Context<'_, '_, 'info, 'info, AutoRefund<'info>>, seed: u64) -> Result<()> {
+        ctx.accounts.auto_refund_and_close_vault(seed, ctx.bumps.escrow, ctx.remaining_accounts)
     }
 
-    pub fn schedule_refund(
-        ctx: Context<ScheduleRefund>,
+    pub fn schedule_refund<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ScheduleRefund<'info>>,
         seed: u64,
         task_id: u16,
         expiry_timestamp: i64,
+        crank_reward: u64,
     ) -> Result<()> {
-        ctx.accounts.schedule_refund(seed, task_id, expiry_timestamp, &ctx.bumps)
+        ctx.accounts.schedule_refund(seed, task_id, expiry_timestamp, crank_reward, &ctx.bumps, ctx.remaining_accounts)
     }
 }
 
 #[error_code]
 pub enum EscrowError {
-    #[msg("The escrow time lock has not expired. Take can only happen 5 days after make.")]
+    #[msg("The escrow time lock has not expired. Take can only happen after unlock_at.")]
     TimeLockNotExpired,
     #[msg("Escrow maker does not match the provided maker account.")]
     InvalidMaker,
     #[msg("Failed to compile auto_refund transaction for TukTuk.")]
     CompileTransactionFailed,
+    #[msg("The escrow has expired and can no longer be taken.")]
+    EscrowExpired,
+    #[msg("Escrow expires_at must be later than unlock_at.")]
+    InvalidTimeWindow,
+    #[msg("Fill amount must be between 1 and the remaining receive amount.")]
+    InvalidFillAmount,
+    #[msg("Fill amount is too small to receive any tokens from the vault.")]
+    FillTooSmall,
+    #[msg("Arithmetic overflow.")]
+    MathOverflow,
+    #[msg("This escrow can only be taken by one of its designated takers.")]
+    TakerNotAllowed,
+    #[msg("Too many designated takers for a single escrow.")]
+    TooManyAllowedTakers,
+    #[msg("Mint account does not match the escrow.")]
+    InvalidMint,
+    #[msg("A token account required for this escrow leg was not provided.")]
+    MissingTokenAccount,
+    #[msg("An escrow cannot have native SOL on both legs.")]
+    BothLegsNative,
+    #[msg("Fee exceeds the maximum allowed basis points.")]
+    InvalidFee,
+    #[msg("Only the config admin can perform this action.")]
+    Unauthorized,
+    #[msg("The protocol is paused.")]
+    ProtocolPaused,
+    #[msg("The mint uses a Token-2022 extension the escrow does not support.")]
+    UnsupportedMintExtension,
+    #[msg("Bundle legs must be 1 to 4 distinct mints with non-zero amounts.")]
+    InvalidBundle,
+    #[msg("Remaining accounts do not match the bundle legs.")]
+    InvalidRemainingAccounts,
+    #[msg("Token account does not match the expected associated token account.")]
+    InvalidTokenAccount,
+    #[msg("Deposit change must be non-zero and leave part of the deposit in the escrow.")]
+    InvalidDepositChange,
+    #[msg("An auto-refund is already scheduled for this escrow.")]
+    RefundAlreadyScheduled,
+    #[msg("Task accounts do not match the auto-refund scheduled for this escrow.")]
+    InvalidRefundTask,
+    #[msg("The task queue is not on the config's allowlist.")]
+    TaskQueueNotAllowed,
+    #[msg("Crank reward is outside the bounds set in the config.")]
+    InvalidCrankReward,
+    #[msg("Too many task queues for the config allowlist.")]
+    TooManyTaskQueues,
+    #[msg("Auction prices must fall from the start price to a non-zero floor over a non-empty window.")]
+    InvalidAuction,
+    #[msg("The take amount is below the current auction price.")]
+    AuctionPriceNotMet,
+    #[msg("The escrow deposit changed since the counter-offer was made.")]
+    StaleCounterOffer,
+    #[msg("Index account does not match the escrow's mint pair or maker.")]
+    InvalidIndex,
+    #[msg("Only the proposed admin can accept the admin role.")]
+    NotPendingAdmin,
+    #[msg("The asset does not match the escrow or is not a supported NFT.")]
+    InvalidAsset,
+    #[msg("Fee recipient does not match the config.")]
+    InvalidFeeRecipient,
+    #[msg("A native fee would leave the fee recipient below its rent-exempt minimum.")]
+    FeeRecipientNotRentExempt,
+    #[msg("The mint must be writable to harvest withheld transfer fees.")]
+    MintNotWritable,
+    #[msg("The escrow has not expired; only its maker can refund it before then.")]
+    EscrowNotExpired,
+    #[msg("The expiry cannot change while an auto-refund is scheduled for it.")]
+    RefundTaskPending,
+    #[msg("The index page is full; list the escrow on a later page.")]
+    IndexPageFull,
 }
\ No newline at end of file
@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+
+/// Metaplex Core, owner of Core assets and collections.
+pub const MPL_CORE_ID: Pubkey = pubkey!("CoREENxT6tW1HoK8ypY1SxRMZTcVPm7R94rH4PZNhX7d");
+/// Metaplex Token Metadata, owner of SPL NFT metadata accounts.
+pub const TOKEN_METADATA_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+/// Standard of the non-fungible side of an [`AssetEscrow`].
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace, Debug, PartialEq)]
+pub enum AssetKind {
+    /// A Metaplex Core asset, moved with Core's `TransferV1`.
+    Core,
+    /// A 0-decimal SPL mint with a supply of one, held in the escrow's ATA.
+    SplNft,
+}
+
+/// Which side of an [`AssetEscrow`] the asset is on.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace, Debug, PartialEq)]
+pub enum AssetSide {
+    /// The maker offers the asset for `amount` of `mint`.
+    Offered,
+    /// The maker offers `amount` of `mint` for an asset.
+    Requested,
+}
+
+/// Escrow trading one non-fungible asset against an amount of an SPL mint, in
+/// either direction. Settled whole by `take_asset`.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct AssetEscrow {
+    pub seed: u64,
+    pub maker: Pubkey,
+    pub kind: AssetKind,
+    pub side: AssetSide,
+    /// The Core asset or SPL NFT mint. `None` on a requested side that accepts
+    /// any asset of `collection`.
+    pub asset: Option<Pubkey>,
+    /// Core collection, or verified Metaplex collection of an SPL NFT, that the
+    /// asset belongs to.
+    pub collection: Option<Pubkey>,
+    /// Fungible leg; for a requested asset, the net amount held in the vault.
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub bump: u8,
+    pub created_at: i64,
+}
+
+impl AssetEscrow {
+    /// Whether `asset` of `collection` is one this escrow asks for.
+    pub fn accepts(&self, asset: &Pubkey, collection: Option<Pubkey>) -> bool {
+        self.asset.iter().all(|wanted| wanted == asset)
+            && self.collection.iter().all(|wanted| collection == Some(*wanted))
+    }
+}
@@ -0,0 +1,49 @@
+use anchor_lang::prelude::*;
+
+use crate::EscrowError;
+
+pub const MAX_BUNDLE_LEGS: usize = 4;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace, Debug, PartialEq)]
+pub struct BundleLeg {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/// Escrow offering several SPL mints, each in its own vault, for several
+/// requested mints. Settled all at once; there are no partial fills.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct BundleEscrow {
+    pub seed: u64,
+    pub maker: Pubkey,
+    /// Offered mints with the net amount held in each vault.
+    #[max_len(MAX_BUNDLE_LEGS)]
+    pub offered: Vec<BundleLeg>,
+    /// Requested mints with the amount of each the taker must pay.
+    #[max_len(MAX_BUNDLE_LEGS)]
+    pub requested: Vec<BundleLeg>,
+    pub bump: u8,
+    pub created_at: i64,
+    /// After this, `take_bundle` is rejected and anyone may `auto_refund_bundle`.
+    /// None means only the maker can refund.
+    pub expires_at: Option<i64>,
+}
+
+impl BundleEscrow {
+    /// One side of a bundle needs 1 to [`MAX_BUNDLE_LEGS`] distinct mints, each with a non-zero amount.
+    pub fn validate_legs(legs: &[BundleLeg]) -> Result<()> {
+        require!(
+            !legs.is_empty() && legs.len() <= MAX_BUNDLE_LEGS,
+            EscrowError::InvalidBundle
+        );
+        for (i, leg) in legs.iter().enumerate() {
+            require!(leg.amount > 0, EscrowError::InvalidBundle);
+            require!(
+                legs[..i].iter().all(|other| other.mint != leg.mint),
+                EscrowError::InvalidBundle
+            );
+        }
+        Ok(())
+    }
+}
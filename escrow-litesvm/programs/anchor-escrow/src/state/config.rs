@@ -0,0 +1,83 @@
+use anchor_lang::prelude::*;
+
+pub const BPS_DENOMINATOR: u64 = 10_000;
+pub const MAX_FEE_BPS: u16 = 1_000;
+pub const MAX_TASK_QUEUES: usize = 4;
+/// Upper bound on the crank reward until the admin configures one: 0.01 SOL.
+pub const DEFAULT_MAX_CRANK_REWARD: u64 = 10_000_000;
+
+/// Protocol-wide settings, stored once at the `config` PDA.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct Config {
+    pub admin: Pubkey,
+    /// Fee charged on each leg of a take, in basis points.
+    pub fee_bps: u16,
+    /// Owner of the treasury token accounts; receives native SOL fees directly.
+    pub fee_recipient: Pubkey,
+    pub paused: bool,
+    pub bump: u8,
+    /// Bounds on the lamports a maker may offer the crank that runs a scheduled refund.
+    pub min_crank_reward: u64,
+    pub max_crank_reward: u64,
+    /// TukTuk task queues `schedule_refund` may queue on; empty disables scheduling.
+    #[max_len(MAX_TASK_QUEUES)]
+    pub task_queues: Vec<Pubkey>,
+    /// Admin proposed by `transfer_admin`; takes over once it calls `accept_admin`.
+    pub pending_admin: Option<Pubkey>,
+}
+
+impl Config {
+    /// Settings in effect until `init_config` has run: no fee, not paused and
+    /// no task queues.
+    pub fn unset() -> Config {
+        Config {
+            admin: Pubkey::default(),
+            fee_bps: 0,
+            fee_recipient: Pubkey::default(),
+            paused: false,
+            bump: 0,
+            min_crank_reward: 0,
+            max_crank_reward: DEFAULT_MAX_CRANK_REWARD,
+            task_queues: vec![],
+            pending_admin: None,
+        }
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.admin != Pubkey::default()
+    }
+
+    /// Reads the config from its PDA, or [`Config::unset`] while the account is empty.
+    pub fn load(info: &AccountInfo) -> Result<Config> {
+        if info.data_is_empty() {
+            return Ok(Config::unset());
+        }
+
+        require_keys_eq!(*info.owner, crate::ID, ErrorCode::AccountOwnedByWrongProgram);
+        Config::try_deserialize(&mut &info.try_borrow_data()?[..])
+    }
+
+    /// Loads the config for a fill paying its fees to `fee_recipient`, failing
+    /// while the protocol is paused or the fee recipient is not the configured one.
+    pub fn load_for_fill(info: &AccountInfo, fee_recipient: &Pubkey) -> Result<Config> {
+        let config = Config::load(info)?;
+        require!(!config.paused, crate::EscrowError::ProtocolPaused);
+        config.check_fee_recipient(fee_recipient)?;
+        Ok(config)
+    }
+
+    /// Checks `fee_recipient` is the configured one; any account will do before
+    /// `init_config`, as no fee is charged then.
+    pub fn check_fee_recipient(&self, fee_recipient: &Pubkey) -> Result<()> {
+        if self.is_initialized() {
+            require_keys_eq!(*fee_recipient, self.fee_recipient, crate::EscrowError::InvalidFeeRecipient);
+        }
+        Ok(())
+    }
+
+    /// Fee owed on `amount`, rounded down.
+    pub fn fee_for(&self, amount: u64) -> u64 {
+        (amount as u128 * self.fee_bps as u128 / BPS_DENOMINATOR as u128) as u64
+    }
+}
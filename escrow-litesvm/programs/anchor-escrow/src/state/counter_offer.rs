@@ -0,0 +1,20 @@
+use anchor_lang::prelude::*;
+
+/// A taker's proposal to fill an escrow for a different amount of mint_b,
+/// locked in the counter-offer PDA (or its ATA) until the maker accepts or
+/// either side rejects it.
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct CounterOffer {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub mint_b: Pubkey,
+    /// Net amount of mint_b locked by the taker, after any Token-2022 transfer fee.
+    pub amount: u64,
+    /// `escrow.deposited` when the offer was made; accepting requires it unchanged
+    /// so the taker never gets less than they bid for.
+    pub deposited: u64,
+    pub bump: u8,
+    pub created_at: i64,
+}
@@ -1,5 +1,13 @@
 use anchor_lang::prelude::*;
 
+use crate::state::IndexPages;
+
+pub const MAX_ALLOWED_TAKERS: usize = 5;
+
+/// Recorded as `mint_a`/`mint_b` when that leg is native lamports instead of an SPL mint.
+/// An address nobody holds a key for, unlike the all-zero System Program id.
+pub const NATIVE_SOL: Pubkey = pubkey!("NativeSo11111111111111111111111111111111111");
+
 #[account]
 #[derive(InitSpace, Debug)]
 pub struct Escrow {
@@ -7,7 +15,84 @@ pub struct Escrow {
     pub maker: Pubkey,
     pub mint_a: Pubkey,
     pub mint_b: Pubkey,
+    /// Amount of mint_b still owed; reduced by every partial fill. For an
+    /// auction this is the start price, and `take` charges the current one.
     pub receive: u64,
+    /// Net amount of mint_a held for the taker, after any Token-2022 transfer fee
+    /// on the deposit; reduced by every fill. For a native leg this is what is
+    /// paid out, so lamports sent straight to the PDA go back to the maker on close.
+    /// For a token leg it mirrors the vault balance and is informational: payouts
+    /// read the vault, so tokens sent straight to it go to the taker of the last fill.
+    pub deposited: u64,
     pub bump: u8,
     pub created_at: i64,
-}
\ No newline at end of file
+    pub unlock_at: i64,
+    pub expires_at: Option<i64>,
+    /// Takers allowed to fill this escrow; empty means anyone can take it.
+    #[max_len(MAX_ALLOWED_TAKERS)]
+    pub allowed_takers: Vec<Pubkey>,
+    /// Auto-refund queued by `schedule_refund`; dequeued when the escrow is
+    /// taken or refunded so the task does not later run as a no-op.
+    pub refund_task: Option<RefundTask>,
+    /// Dutch-auction pricing set by `make_auction`; such escrows are filled whole.
+    pub auction: Option<DutchAuction>,
+    /// Index pages the escrow is listed on, passed back when it closes.
+    pub index_pages: IndexPages,
+}
+
+/// Change to the deposit of an open escrow, passed to `update_escrow`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum DepositChange {
+    /// Add this much mint_a (or lamports) to the escrow.
+    TopUp(u64),
+    /// Take this much back; something must remain for the taker.
+    Withdraw(u64),
+}
+
+/// TukTuk task that will run `auto_refund` for an escrow.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace, Debug, PartialEq)]
+pub struct RefundTask {
+    pub task_queue: Pubkey,
+    pub task: Pubkey,
+    pub task_id: u16,
+}
+
+/// Price of mint_b that decays from `start_price` at `start_at` to
+/// `floor_price` at `end_at`, and stays at the floor afterwards.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace, Debug, PartialEq)]
+pub struct DutchAuction {
+    pub start_price: u64,
+    pub floor_price: u64,
+    pub start_at: i64,
+    pub end_at: i64,
+    /// 0 for a linear decay; otherwise the price drops in this many equal steps.
+    pub steps: u16,
+}
+
+impl DutchAuction {
+    pub fn is_valid(&self) -> bool {
+        self.floor_price > 0 && self.floor_price <= self.start_price && self.start_at < self.end_at
+    }
+
+    /// Amount of mint_b asked at `now`, rounded up so the maker never gets less than the curve.
+    pub fn price_at(&self, now: i64) -> u64 {
+        if now <= self.start_at {
+            return self.start_price;
+        }
+        if now >= self.end_at {
+            return self.floor_price;
+        }
+
+        let elapsed = (now - self.start_at) as u128;
+        let duration = (self.end_at - self.start_at) as u128;
+        let drop = (self.start_price - self.floor_price) as u128;
+
+        // Fraction of the window that has passed, as numerator / denominator
+        let (numerator, denominator) = match self.steps {
+            0 => (elapsed, duration),
+            steps => (elapsed * steps as u128 / duration, steps as u128),
+        };
+
+        self.start_price - (drop * numerator / denominator) as u64
+    }
+}
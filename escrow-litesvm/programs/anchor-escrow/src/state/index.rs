@@ -0,0 +1,92 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::EscrowError;
+
+/// Escrows one index page holds; `make` moves on to the next page once it is full.
+pub const ESCROWS_PER_PAGE: usize = 32;
+
+/// One page of the open escrows for a mint pair (`[b"pair_index", mint_a, mint_b, page]`)
+/// or a maker (`[b"maker_index", maker, page]`), with `page` a little-endian u32, so
+/// clients can list them page by page without a `getProgramAccounts` scan. Grows and
+/// shrinks by one key per escrow, up to `ESCROWS_PER_PAGE`.
+#[account]
+#[derive(Debug)]
+pub struct EscrowIndex {
+    pub bump: u8,
+    pub page: u32,
+    pub escrows: Vec<Pubkey>,
+}
+
+impl EscrowIndex {
+    /// Account size, discriminator included, holding `len` escrows.
+    pub const fn space(len: usize) -> usize {
+        8 + 1 + 4 + 4 + 32 * len
+    }
+
+    /// Appends `escrow`, growing the account and topping up its rent from `payer`.
+    /// Fails once the page is full.
+    pub fn push<'info>(
+        index: &mut Account<'info, EscrowIndex>,
+        escrow: Pubkey,
+        payer: AccountInfo<'info>,
+        system_program: AccountInfo<'info>,
+    ) -> Result<()> {
+        require!(index.escrows.len() < ESCROWS_PER_PAGE, EscrowError::IndexPageFull);
+
+        let info = index.to_account_info();
+        let new_len = Self::space(index.escrows.len() + 1);
+
+        let rent = Rent::get()?.minimum_balance(new_len).saturating_sub(info.lamports());
+        if rent > 0 {
+            let cpi_accounts = Transfer {
+                from: payer,
+                to: info.clone(),
+            };
+            transfer(CpiContext::new(system_program, cpi_accounts), rent)?;
+        }
+
+        info.realloc(new_len, false)?;
+        index.escrows.push(escrow);
+        Ok(())
+    }
+
+    /// Removes `escrow` from the page, shrinking the account and returning the
+    /// freed rent to `rent_to`. Fails when the page does not list the escrow.
+    pub fn remove(index: &mut Account<EscrowIndex>, escrow: &Pubkey, rent_to: &AccountInfo) -> Result<()> {
+        let position = index
+            .escrows
+            .iter()
+            .position(|key| key == escrow)
+            .ok_or(error!(EscrowError::InvalidIndex))?;
+        index.escrows.swap_remove(position);
+
+        let info = index.to_account_info();
+        let new_len = Self::space(index.escrows.len());
+        info.realloc(new_len, false)?;
+
+        let excess = info.lamports().saturating_sub(Rent::get()?.minimum_balance(new_len));
+        info.sub_lamports(excess)?;
+        rent_to.add_lamports(excess)?;
+        Ok(())
+    }
+
+    /// Checks that this is page `page` of the index at `seeds`, living at `address`,
+    /// for accounts whose seeds cannot be checked by constraints.
+    pub fn require_page(&self, address: &Pubkey, seeds: &[&[u8]], page: u32) -> Result<()> {
+        require_eq!(self.page, page, EscrowError::InvalidIndex);
+        let page = page.to_le_bytes();
+        let bump = [self.bump];
+        let expected = Pubkey::create_program_address(&[seeds, &[&page, &bump]].concat(), &crate::ID)
+            .map_err(|_| error!(EscrowError::InvalidIndex))?;
+        require_keys_eq!(expected, *address, EscrowError::InvalidIndex);
+        Ok(())
+    }
+}
+
+/// Pages of the mint-pair and maker indexes `make` listed an escrow on.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace, Debug, PartialEq)]
+pub struct IndexPages {
+    pub pair: u32,
+    pub maker: u32,
+}
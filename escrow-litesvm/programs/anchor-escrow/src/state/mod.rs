@@ -1,3 +1,13 @@
 pub mod escrow;
+pub mod config;
+pub mod bundle;
+pub mod counter_offer;
+pub mod index;
+pub mod asset_escrow;
 
-pub use escrow::*;
\ No newline at end of file
+pub use escrow::*;
+pub use config::*;
+pub use bundle::*;
+pub use counter_offer::*;
+pub use index::*;
+pub use asset_escrow::*;
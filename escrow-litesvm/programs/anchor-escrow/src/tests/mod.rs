@@ -2,6 +2,26 @@
 mod tests {
 
     use {
+        anchor_escrow_client::{
+            self as client,
+            pda,
+            AcceptCounterOfferBuilder,
+            AutoRefundBuilder,
+            AutoRefundBundleBuilder,
+            MakeAssetBuilder,
+            MakeBuilder,
+            MakeBundleBuilder,
+            MakeCounterOfferBuilder,
+            RefundAssetBuilder,
+            RefundBuilder,
+            RefundBundleBuilder,
+            RejectCounterOfferBuilder,
+            ScheduleRefundBuilder,
+            TakeAssetBuilder,
+            TakeBuilder,
+            TakeBundleBuilder,
+            UpdateEscrowBuilder
+        },
         anchor_lang::{
             prelude::msg,
             solana_program::program_pack::Pack,
@@ -9,22 +29,23 @@ mod tests {
             InstructionData,
             ToAccountMetas
         }, anchor_spl::{
-            associated_token::{
-                self,
-                spl_associated_token_account
-            },
+            associated_token,
             token::spl_token
         },
-        litesvm::LiteSVM,
+        litesvm::{types::TransactionResult, LiteSVM},
         litesvm_token::{
             spl_token::ID as TOKEN_PROGRAM_ID,
             CreateAssociatedTokenAccount,
             CreateMint, MintTo
         },
-        solana_rpc_client::rpc_client::RpcClient,
+        spl_token_2022::{
+            extension::{transfer_fee::instruction::initialize_transfer_fee_config, ExtensionType},
+            ID as TOKEN_2022_PROGRAM_ID,
+        },
+        solana_system_interface::instruction::create_account,
         solana_account::Account,
         solana_clock::Clock,
-        solana_instruction::Instruction,
+        solana_instruction::{AccountMeta, Instruction},
         solana_keypair::Keypair,
         solana_message::Message,
         solana_native_token::LAMPORTS_PER_SOL,
@@ -32,18 +53,34 @@ mod tests {
         solana_sdk_ids::system_program::ID as SYSTEM_PROGRAM_ID,
         solana_signer::Signer,
         solana_transaction::Transaction,
-        solana_address::Address,
         std::{
+            collections::{BTreeMap, BTreeSet},
             path::PathBuf,
             str::FromStr
         }
     };
 
+    use client::state::{AssetKind, MPL_CORE_ID, TOKEN_METADATA_ID};
+
     static PROGRAM_ID: Pubkey = crate::ID;
 
-    // Setup function to initialize LiteSVM and create a payer keypair
-    // Also loads an account from devnet into the LiteSVM environment (for testing purposes)
+    // Setup function to initialize LiteSVM and create a payer keypair, with the
+    // protocol config created by the payer as admin
     fn setup() -> (LiteSVM, Keypair) {
+        let (mut program, payer) = setup_without_config();
+
+        // Create the protocol config with no fee, the payer acting as admin and fee recipient
+        let init_config_ix = client::init_config(payer.pubkey(), 0, payer.pubkey());
+        let message = Message::new(&[init_config_ix], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).expect("Failed to create config");
+
+        (program, payer)
+    }
+
+    // Like setup, but stops before init_config; the payer is the program's upgrade authority
+    // Also loads the example account file in fixtures/ onto the payer
+    fn setup_without_config() -> (LiteSVM, Keypair) {
         // Initialize LiteSVM and payer
         let mut program = LiteSVM::new();
         let payer = Keypair::new();
@@ -59,33 +96,193 @@ mod tests {
 
         let program_data = std::fs::read(so_path).expect("Failed to read program SO file");
 
-        program.add_program(PROGRAM_ID, &program_data);
+        add_upgradeable_program(&mut program, &program_data, &payer.pubkey());
+
+        // Load the TukTuk stand-in at TukTuk's program id so schedule_refund's CPI succeeds
+        let tuktuk_mock_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../../target/deploy/tuktuk_mock.so");
+
+        let tuktuk_mock_data = std::fs::read(tuktuk_mock_path).expect("Failed to read TukTuk mock SO file");
+
+        program.add_program(tuktuk_mock::ID, &tuktuk_mock_data);
 
-        // Example on how to Load an account from devnet
-        // LiteSVM does not have access to real Solana network data since it does not have network access,
-        // so we use an RPC client to fetch account data from devnet
-        let rpc_client = RpcClient::new("https://api.devnet.solana.com");
-        let account_address = Address::from_str("DRYvf71cbF2s5wgaJQvAGkghMkRcp5arvsK2w97vXhi2").unwrap();
-        let fetched_account = rpc_client
-            .get_account(&account_address)
-            .expect("Failed to fetch account from devnet");
+        // Likewise the Metaplex Core stand-in, for asset escrows
+        let core_mock_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../../target/deploy/mpl_core_mock.so");
 
-        // Set the fetched account in the LiteSVM environment
+        let core_mock_data = std::fs::read(core_mock_path).expect("Failed to read Core mock SO file");
+
+        program.add_program(MPL_CORE_ID, &core_mock_data);
+
+        // Example of loading an account from disk into the LiteSVM environment, which has no
+        // network access. This one is written by hand in the format scripts/record-fixtures.sh
+        // records accounts from a live cluster in: a wallet funded like the payer's airdrop
+        let fixture_account = fixture("DRYvf71cbF2s5wgaJQvAGkghMkRcp5arvsK2w97vXhi2");
+        let fixture_lamports = fixture_account.lamports;
+
+        // Set the account in the LiteSVM environment
         // This allows us to simulate interactions with this account during testing
-        program.set_account(payer.pubkey(), Account {
-            lamports: fetched_account.lamports,
-            data: fetched_account.data,
-            owner: Pubkey::from(fetched_account.owner.to_bytes()),
-            executable: fetched_account.executable,
-            rent_epoch: fetched_account.rent_epoch
-        }).unwrap();
+        program.set_account(payer.pubkey(), fixture_account).unwrap();
 
-        msg!("Lamports of fetched account: {}", fetched_account.lamports);
+        msg!("Lamports of fixture account: {}", fixture_lamports);
 
         // Return the LiteSVM instance and payer keypair
         (program, payer)
     }
 
+    // Deploys the escrow program through the upgradeable loader with `authority` as its
+    // upgrade authority, which init_config checks; add_program uses the non-upgradeable loader
+    fn add_upgradeable_program(program: &mut LiteSVM, elf: &[u8], authority: &Pubkey) {
+        let loader = solana_sdk_ids::bpf_loader_upgradeable::ID;
+        let program_data = pda::program_data();
+
+        // UpgradeableLoaderState::ProgramData { slot: 0, upgrade_authority_address: Some(authority) }
+        let mut data = vec![3, 0, 0, 0];
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(elf);
+        let lamports = program.minimum_balance_for_rent_exemption(data.len());
+        program.set_account(program_data, Account { lamports, data, owner: loader, executable: false, rent_epoch: 0 }).unwrap();
+
+        // UpgradeableLoaderState::Program { programdata_address }, set last so the program loads
+        let mut data = vec![2, 0, 0, 0];
+        data.extend_from_slice(program_data.as_ref());
+        let lamports = program.minimum_balance_for_rent_exemption(data.len());
+        program.set_account(PROGRAM_ID, Account { lamports, data, owner: loader, executable: true, rent_epoch: 0 }).unwrap();
+    }
+
+    // Loads the account file fixtures/<address>.json, in the format written by `solana account --output json`
+    fn fixture(address: &str) -> Account {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(format!("{}.json", address));
+        let contents = std::fs::read_to_string(&path)
+            .unwrap_or_else(|_| panic!("Missing fixture {}, record it with scripts/record-fixtures.sh", path.display()));
+        let json: serde_json::Value = serde_json::from_str(&contents)
+            .unwrap_or_else(|err| panic!("Invalid JSON in fixture {}: {}", path.display(), err));
+        assert_eq!(json["pubkey"], address, "fixture {} holds another account", path.display());
+
+        let account = &json["account"];
+        let bad = |field: &str| format!("fixture {} has a missing or invalid account.{}", path.display(), field);
+        let data = account["data"][0].as_str().unwrap_or_else(|| panic!("{}", bad("data")));
+
+        Account {
+            lamports: account["lamports"].as_u64().unwrap_or_else(|| panic!("{}", bad("lamports"))),
+            data: STANDARD.decode(data).unwrap_or_else(|_| panic!("{}", bad("data"))),
+            owner: account["owner"].as_str()
+                .and_then(|owner| Pubkey::from_str(owner).ok())
+                .unwrap_or_else(|| panic!("{}", bad("owner"))),
+            executable: account["executable"].as_bool().unwrap_or_else(|| panic!("{}", bad("executable"))),
+            rent_epoch: account["rentEpoch"].as_u64().unwrap_or_else(|| panic!("{}", bad("rentEpoch"))),
+        }
+    }
+
+    // Raw account data source for the client's fetch helpers
+    fn fetcher(program: &LiteSVM) -> impl Fn(&Pubkey) -> Option<Vec<u8>> + '_ {
+        |address: &Pubkey| program.get_account(address).map(|account| account.data)
+    }
+
+    // Sends an update_config signed by `admin`
+    fn update_config(
+        program: &mut LiteSVM,
+        admin: &Keypair,
+        fee_bps: Option<u16>,
+        fee_recipient: Option<Pubkey>,
+        paused: Option<bool>,
+    ) -> TransactionResult {
+        let ix = client::update_config(admin.pubkey(), fee_bps, fee_recipient, paused);
+        let message = Message::new(&[ix], Some(&admin.pubkey()));
+        let transaction = Transaction::new(&[admin], message, program.latest_blockhash());
+        program.send_transaction(transaction)
+    }
+
+    // Sends an update_scheduling signed by `admin`
+    fn update_scheduling(
+        program: &mut LiteSVM,
+        admin: &Keypair,
+        min_crank_reward: Option<u64>,
+        max_crank_reward: Option<u64>,
+        task_queues: Option<Vec<Pubkey>>,
+    ) -> TransactionResult {
+        let ix = client::update_scheduling(admin.pubkey(), min_crank_reward, max_crank_reward, task_queues);
+        let message = Message::new(&[ix], Some(&admin.pubkey()));
+        let transaction = Transaction::new(&[admin], message, program.latest_blockhash());
+        program.send_transaction(transaction)
+    }
+
+    // Reads the amount of a Token or Token-2022 account (offset 64 in both layouts)
+    fn token_amount(program: &LiteSVM, account: &Pubkey) -> u64 {
+        let data = program.get_account(account).unwrap().data;
+        u64::from_le_bytes(data[64..72].try_into().unwrap())
+    }
+
+    // Lamports held by `accounts`, counting missing ones as zero
+    fn lamports(program: &LiteSVM, accounts: &[Pubkey]) -> u64 {
+        accounts.iter().filter_map(|account| program.get_account(account)).map(|account| account.lamports).sum()
+    }
+
+    // Decodes the first Anchor event of type `E` from a transaction's logs
+    fn find_event<E: anchor_lang::Event + anchor_lang::AnchorDeserialize>(logs: &[String]) -> Option<E> {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        logs.iter()
+            .filter_map(|log| log.strip_prefix("Program data: "))
+            .filter_map(|data| STANDARD.decode(data).ok())
+            .find(|bytes| bytes.starts_with(E::DISCRIMINATOR))
+            .map(|bytes| E::deserialize(&mut &bytes[E::DISCRIMINATOR.len()..]).unwrap())
+    }
+
+    // Runs a task queued on the TukTuk mock the way a crank would: run_task_v0 checks the
+    // trigger and pays the crank reward, then the stored instructions execute in the same transaction
+    fn crank_task(program: &mut LiteSVM, cranker: &Keypair, task: &Pubkey) -> TransactionResult {
+        let task_data = tuktuk_mock::TaskV0::try_deserialize(
+            &mut program.get_account(task).unwrap().data.as_ref()
+        ).unwrap();
+        let tuktuk_mock::TransactionSourceV0::CompiledV0(compiled) = task_data.transaction else {
+            panic!("the TukTuk mock only replays compiled transactions");
+        };
+        assert_eq!(compiled.num_rw_signers + compiled.num_ro_signers, 0, "replayed instructions cannot require signers");
+
+        let mut instructions = vec![Instruction {
+            program_id: tuktuk_mock::ID,
+            accounts: tuktuk_mock::accounts::RunTaskV0 {
+                crank_turner: cranker.pubkey(),
+                rent_refund: task_data.rent_refund,
+                task: *task,
+            }.to_account_metas(None),
+            data: tuktuk_mock::instruction::RunTaskV0 {}.data(),
+        }];
+
+        // Accounts are ordered writable first, so the first num_rw are the writable ones
+        instructions.extend(compiled.instructions.iter().map(|ix| Instruction {
+            program_id: compiled.accounts[ix.program_id_index as usize],
+            accounts: ix.accounts.iter().map(|&index| AccountMeta {
+                pubkey: compiled.accounts[index as usize],
+                is_signer: false,
+                is_writable: index < compiled.num_rw,
+            }).collect(),
+            data: ix.data.clone(),
+        }));
+
+        let message = Message::new(&instructions, Some(&cranker.pubkey()));
+        let transaction = Transaction::new(&[cranker], message, program.latest_blockhash());
+        program.send_transaction(transaction)
+    }
+
+    // Asserts that a transaction failed with the given Anchor error code name
+    fn assert_anchor_error(result: TransactionResult, code: &str) {
+        let failed = result.expect_err("transaction should have failed");
+        assert!(
+            failed.meta.logs.iter().any(|log| log.contains(&format!("Error Code: {}", code))),
+            "expected {} in logs: {:#?}",
+            code,
+            failed.meta.logs
+        );
+    }
+
     #[test]
     fn test_make() {
 
@@ -117,43 +314,28 @@ mod tests {
             .owner(&maker).send().unwrap();
         msg!("Maker ATA A: {}\n", maker_ata_a);
 
-        // Derive the PDA for the escrow account using the maker's public key and a seed value
-        let escrow = Pubkey::find_program_address(
-            &[b"escrow", maker.as_ref(), &123u64.to_le_bytes()],
-            &PROGRAM_ID
-        ).0;
-        msg!("Escrow PDA: {}\n", escrow);
-
-        // Derive the PDA for the vault associated token account using the escrow PDA and Mint A
-        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
-        msg!("Vault PDA: {}\n", vault);
-
-        // Define program IDs for associated token program, token program, and system program
-        let asspciated_token_program = spl_associated_token_account::ID;
-        let token_program = TOKEN_PROGRAM_ID;
-        let system_program = SYSTEM_PROGRAM_ID;
-
         // Mint 1,000 tokens (with 6 decimal places) of Mint A to the maker's associated token account
         MintTo::new(&mut program, &payer, &mint_a, &maker_ata_a, 1000000000)
             .send()
             .unwrap();
 
         // Create the "Make" instruction to deposit tokens into the escrow
-        let make_ix = Instruction {
-            program_id: PROGRAM_ID,
-            accounts: crate::accounts::Make {
-                maker: maker,
-                mint_a: mint_a,
-                mint_b: mint_b,
-                maker_ata_a: maker_ata_a,
-                escrow: escrow,
-                vault: vault,
-                associated_token_program: asspciated_token_program,
-                token_program: token_program,
-                system_program: system_program,
-            }.to_account_metas(None),
-            data: crate::instruction::Make {deposit: 10, seed: 123u64, receive: 10 }.data(),
-        };
+        // The client derives the escrow PDA, the vault and the maker's ATA from the maker and seed
+        // Lock the escrow for 5 days from the current clock time
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp + 5 * 24 * 60 * 60;
+
+        let make = MakeBuilder::new(maker, 123)
+            .offer(mint_a, 10)
+            .request(mint_b, 10)
+            .unlock_at(unlock_at);
+        let make_ix = make.instruction();
+
+        let escrow = make.escrow();
+        msg!("Escrow PDA: {}\n", escrow);
+
+        let vault = make.vault().unwrap();
+        msg!("Vault PDA: {}\n", vault);
 
         // Create and send the transaction containing the "Make" instruction
         let message = Message::new(&[make_ix], Some(&payer.pubkey()));
@@ -169,6 +351,16 @@ mod tests {
         msg!("CUs Consumed: {}", tx.compute_units_consumed);
         msg!("Tx Signature: {}", tx.signature);
 
+        // Verify the EscrowCreated event emitted by "Make"
+        let created: crate::EscrowCreated = find_event(&tx.logs).expect("EscrowCreated event");
+        assert_eq!(created.escrow, escrow);
+        assert_eq!(created.maker, maker);
+        assert_eq!(created.mint_a, mint_a);
+        assert_eq!(created.mint_b, mint_b);
+        assert_eq!(created.deposited, 10);
+        assert_eq!(created.receive, 10);
+        assert_eq!(created.unlock_at, unlock_at);
+
         // Verify the vault account and escrow account data after the "Make" instruction
         let vault_account = program.get_account(&vault).unwrap();
         let vault_data = spl_token::state::Account::unpack(&vault_account.data).unwrap();
@@ -176,13 +368,15 @@ mod tests {
         assert_eq!(vault_data.owner, escrow);
         assert_eq!(vault_data.mint, mint_a);
 
-        let escrow_account = program.get_account(&escrow).unwrap();
-        let escrow_data = crate::state::Escrow::try_deserialize(&mut escrow_account.data.as_ref()).unwrap();
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 123).unwrap();
         assert_eq!(escrow_data.seed, 123u64);
         assert_eq!(escrow_data.maker, maker);
         assert_eq!(escrow_data.mint_a, mint_a);
         assert_eq!(escrow_data.mint_b, mint_b);
         assert_eq!(escrow_data.receive, 10);
+        assert_eq!(escrow_data.unlock_at, unlock_at);
+        assert_eq!(escrow_data.expires_at, None);
+        assert!(escrow_data.allowed_takers.is_empty());
 
     }
 
@@ -229,37 +423,20 @@ mod tests {
             .send()
             .unwrap();
 
-        // Derive escrow PDA and vault
+        // Execute the Make instruction (maker deposits 10 tokens of mint_a, expects 10 of mint_b)
+        // Lock the escrow for 5 days from the current clock time
         let seed: u64 = 123;
-        let escrow = Pubkey::find_program_address(
-            &[b"escrow", maker.as_ref(), &seed.to_le_bytes()],
-            &PROGRAM_ID
-        ).0;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp + 5 * 24 * 60 * 60;
 
-        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
-
-        let associated_token_program = spl_associated_token_account::ID;
-        let token_program = TOKEN_PROGRAM_ID;
-        let system_program = SYSTEM_PROGRAM_ID;
-
-        // Execute the Make instruction (maker deposits 10 tokens of mint_a, expects 10 of mint_b)
-        let make_ix = Instruction {
-            program_id: PROGRAM_ID,
-            accounts: crate::accounts::Make {
-                maker,
-                mint_a,
-                mint_b,
-                maker_ata_a,
-                escrow,
-                vault,
-                associated_token_program,
-                token_program,
-                system_program,
-            }.to_account_metas(None),
-            data: crate::instruction::Make { deposit: 10, seed, receive: 10 }.data(),
-        };
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 10)
+            .request(mint_b, 10)
+            .unlock_at(unlock_at);
+        let escrow = make.escrow();
+        let vault = make.vault().unwrap();
 
-        let message = Message::new(&[make_ix], Some(&payer.pubkey()));
+        let message = Message::new(&[make.instruction()], Some(&payer.pubkey()));
         let recent_blockhash = program.latest_blockhash();
         let transaction = Transaction::new(&[&payer], message, recent_blockhash);
         program.send_transaction(transaction).unwrap();
@@ -272,31 +449,17 @@ mod tests {
         clock.unix_timestamp += five_days + 1;
         program.set_sysvar(&clock);
 
-        // Derive the taker's ATA for Mint A (will be init_if_needed by the instruction)
-        let taker_ata_a = associated_token::get_associated_token_address(&taker, &mint_a);
+        // The taker's ATA for Mint A and the maker's ATA for Mint B are init_if_needed by the instruction
+        let taker_ata_a = pda::ata(&taker, &mint_a, &TOKEN_PROGRAM_ID);
+        let maker_ata_b = pda::ata(&maker, &mint_b, &TOKEN_PROGRAM_ID);
 
-        // Derive the maker's ATA for Mint B (will be init_if_needed by the instruction)
-        let maker_ata_b = associated_token::get_associated_token_address(&maker, &mint_b);
+        // Execute the Take instruction, built from the escrow and config as fetched on-chain
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take_ix = TakeBuilder::new(taker, &escrow_data, &config).instruction();
 
-        // Execute the Take instruction
-        let take_ix = Instruction {
-            program_id: PROGRAM_ID,
-            accounts: crate::accounts::Take {
-                taker,
-                maker,
-                mint_a,
-                mint_b,
-                taker_ata_a,
-                taker_ata_b,
-                maker_ata_b,
-                escrow,
-                vault,
-                associated_token_program,
-                token_program,
-                system_program,
-            }.to_account_metas(None),
-            data: crate::instruction::Take {}.data(),
-        };
+        // A legacy SPL mint has no withheld fees to harvest, so it is not write-locked
+        assert!(take_ix.accounts.iter().any(|meta| meta.pubkey == mint_a && !meta.is_writable));
 
         let message = Message::new(&[take_ix], Some(&taker_kp.pubkey()));
         let recent_blockhash = program.latest_blockhash();
@@ -306,6 +469,18 @@ mod tests {
         msg!("\n\nTake transaction successful");
         msg!("CUs Consumed: {}", tx.compute_units_consumed);
 
+        // Verify: the EscrowTaken event describes the full fill
+        let taken: crate::EscrowTaken = find_event(&tx.logs).expect("EscrowTaken event");
+        assert_eq!(taken.escrow, escrow);
+        assert_eq!(taken.maker, maker);
+        assert_eq!(taken.taker, taker);
+        assert_eq!(taken.mint_a, mint_a);
+        assert_eq!(taken.mint_b, mint_b);
+        assert_eq!((taken.amount, taken.payout), (10, 10));
+        assert_eq!((taken.fee_a, taken.fee_b), (0, 0));
+        assert_eq!(taken.remaining_receive, 0);
+        assert_eq!(taken.timestamp, program.get_sysvar::<Clock>().unix_timestamp);
+
         // Verify: taker received mint_a tokens from the vault
         let taker_ata_a_account = program.get_account(&taker_ata_a).unwrap();
         let taker_ata_a_data = spl_token::state::Account::unpack(&taker_ata_a_account.data).unwrap();
@@ -319,6 +494,7 @@ mod tests {
         assert_eq!(maker_ata_b_data.amount, 10);
         assert_eq!(maker_ata_b_data.mint, mint_b);
         assert_eq!(maker_ata_b_data.owner, maker);
+        assert_eq!(token_amount(&program, &taker_ata_b), 1000000000 - 10);
 
         // Verify: vault account is closed (lamports drained to 0)
         let vault_after = program.get_account(&vault);
@@ -366,37 +542,20 @@ mod tests {
         let initial_balance = maker_ata_a_data.amount;
         msg!("Maker initial balance: {}", initial_balance);
 
-        // Derive escrow PDA and vault
+        // Execute the Make instruction (deposit 50 tokens)
+        // Lock the escrow for 5 days from the current clock time
         let seed: u64 = 456;
-        let escrow = Pubkey::find_program_address(
-            &[b"escrow", maker.as_ref(), &seed.to_le_bytes()],
-            &PROGRAM_ID
-        ).0;
-
-        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp + 5 * 24 * 60 * 60;
 
-        let associated_token_program = spl_associated_token_account::ID;
-        let token_program = TOKEN_PROGRAM_ID;
-        let system_program = SYSTEM_PROGRAM_ID;
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 50)
+            .request(mint_b, 25)
+            .unlock_at(unlock_at);
+        let escrow = make.escrow();
+        let vault = make.vault().unwrap();
 
-        // Execute the Make instruction (deposit 50 tokens)
-        let make_ix = Instruction {
-            program_id: PROGRAM_ID,
-            accounts: crate::accounts::Make {
-                maker,
-                mint_a,
-                mint_b,
-                maker_ata_a,
-                escrow,
-                vault,
-                associated_token_program,
-                token_program,
-                system_program,
-            }.to_account_metas(None),
-            data: crate::instruction::Make { deposit: 50, seed, receive: 25 }.data(),
-        };
-
-        let message = Message::new(&[make_ix], Some(&payer.pubkey()));
+        let message = Message::new(&[make.instruction()], Some(&payer.pubkey()));
         let recent_blockhash = program.latest_blockhash();
         let transaction = Transaction::new(&[&payer], message, recent_blockhash);
         program.send_transaction(transaction).unwrap();
@@ -414,19 +573,8 @@ mod tests {
         assert_eq!(maker_ata_a_data.amount, initial_balance - 50);
 
         // Execute the Refund instruction
-        let refund_ix = Instruction {
-            program_id: PROGRAM_ID,
-            accounts: crate::accounts::Refund {
-                maker,
-                mint_a,
-                maker_ata_a,
-                escrow,
-                vault,
-                token_program,
-                system_program,
-            }.to_account_metas(None),
-            data: crate::instruction::Refund {}.data(),
-        };
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let refund_ix = RefundBuilder::new(&escrow_data).instruction();
 
         let message = Message::new(&[refund_ix], Some(&payer.pubkey()));
         let recent_blockhash = program.latest_blockhash();
@@ -436,6 +584,13 @@ mod tests {
         msg!("\n\nRefund transaction successful");
         msg!("CUs Consumed: {}", tx.compute_units_consumed);
 
+        // Verify: the EscrowRefunded event carries the returned amount
+        let refunded: crate::EscrowRefunded = find_event(&tx.logs).expect("EscrowRefunded event");
+        assert_eq!(refunded.escrow, escrow);
+        assert_eq!(refunded.maker, maker);
+        assert_eq!(refunded.mint_a, mint_a);
+        assert_eq!(refunded.amount, 50);
+
         // Verify: maker received tokens back (full original balance restored)
         let maker_ata_a_account = program.get_account(&maker_ata_a).unwrap();
         let maker_ata_a_data = spl_token::state::Account::unpack(&maker_ata_a_account.data).unwrap();
@@ -454,7 +609,7 @@ mod tests {
 
     #[test]
     fn test_take_before_timelock() {
-        // This test verifies that the take instruction fails if called before 5 days have passed
+        // This test verifies that the take instruction fails if called before unlock_at
 
         let (mut program, payer) = setup();
 
@@ -496,37 +651,20 @@ mod tests {
             .send()
             .unwrap();
 
-        // Derive escrow PDA and vault
+        // Execute the Make instruction
+        // Lock the escrow for 5 days from the current clock time
         let seed: u64 = 789;
-        let escrow = Pubkey::find_program_address(
-            &[b"escrow", maker.as_ref(), &seed.to_le_bytes()],
-            &PROGRAM_ID
-        ).0;
-
-        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp + 5 * 24 * 60 * 60;
 
-        let associated_token_program = spl_associated_token_account::ID;
-        let token_program = TOKEN_PROGRAM_ID;
-        let system_program = SYSTEM_PROGRAM_ID;
-
-        // Execute the Make instruction
-        let make_ix = Instruction {
-            program_id: PROGRAM_ID,
-            accounts: crate::accounts::Make {
-                maker,
-                mint_a,
-                mint_b,
-                maker_ata_a,
-                escrow,
-                vault,
-                associated_token_program,
-                token_program,
-                system_program,
-            }.to_account_metas(None),
-            data: crate::instruction::Make { deposit: 10, seed, receive: 10 }.data(),
-        };
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 10)
+            .request(mint_b, 10)
+            .unlock_at(unlock_at);
+        let escrow = make.escrow();
+        let vault = make.vault().unwrap();
 
-        let message = Message::new(&[make_ix], Some(&payer.pubkey()));
+        let message = Message::new(&[make.instruction()], Some(&payer.pubkey()));
         let recent_blockhash = program.latest_blockhash();
         let transaction = Transaction::new(&[&payer], message, recent_blockhash);
         program.send_transaction(transaction).unwrap();
@@ -534,27 +672,9 @@ mod tests {
         msg!("Make transaction successful, attempting take WITHOUT advancing clock...");
 
         // DO NOT advance the clock - try to take immediately (should fail)
-        let taker_ata_a = associated_token::get_associated_token_address(&taker, &mint_a);
-        let maker_ata_b = associated_token::get_associated_token_address(&maker, &mint_b);
-
-        let take_ix = Instruction {
-            program_id: PROGRAM_ID,
-            accounts: crate::accounts::Take {
-                taker,
-                maker,
-                mint_a,
-                mint_b,
-                taker_ata_a,
-                taker_ata_b,
-                maker_ata_b,
-                escrow,
-                vault,
-                associated_token_program,
-                token_program,
-                system_program,
-            }.to_account_metas(None),
-            data: crate::instruction::Take {}.data(),
-        };
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take_ix = TakeBuilder::new(taker, &escrow_data, &config).instruction();
 
         let message = Message::new(&[take_ix], Some(&taker_kp.pubkey()));
         let recent_blockhash = program.latest_blockhash();
@@ -602,36 +722,22 @@ mod tests {
 
         let initial_balance = 1_000_000_000u64;
 
-        // Derive escrow PDA and vault
-        let seed: u64 = 999;
-        let escrow = Pubkey::find_program_address(
-            &[b"escrow", maker.as_ref(), &seed.to_le_bytes()],
-            &PROGRAM_ID
-        ).0;
-
-        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
-        let token_program = TOKEN_PROGRAM_ID;
-        let system_program = SYSTEM_PROGRAM_ID;
-        let associated_token_program = spl_associated_token_account::ID;
-
         // Execute Make instruction (deposit 50 tokens)
-        let make_ix = Instruction {
-            program_id: PROGRAM_ID,
-            accounts: crate::accounts::Make {
-                maker,
-                mint_a,
-                mint_b,
-                maker_ata_a,
-                escrow,
-                vault,
-                associated_token_program,
-                token_program,
-                system_program,
-            }.to_account_metas(None),
-            data: crate::instruction::Make { deposit: 50, seed, receive: 25 }.data(),
-        };
-
-        let message = Message::new(&[make_ix], Some(&payer.pubkey()));
+        // Lock the escrow for 5 days from the current clock time
+        let seed: u64 = 999;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp + 5 * 24 * 60 * 60;
+        let expires_at = unlock_at + 24 * 60 * 60;
+
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 50)
+            .request(mint_b, 25)
+            .unlock_at(unlock_at)
+            .expires_at(expires_at);
+        let escrow = make.escrow();
+        let vault = make.vault().unwrap();
+
+        let message = Message::new(&[make.instruction()], Some(&payer.pubkey()));
         let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
         program.send_transaction(transaction).unwrap();
 
@@ -647,19 +753,22 @@ mod tests {
         program.airdrop(&cranker.pubkey(), 1 * LAMPORTS_PER_SOL).unwrap();
 
         // Execute AutoRefund — cranker signs, NOT the maker
-        let auto_refund_ix = Instruction {
-            program_id: PROGRAM_ID,
-            accounts: crate::accounts::AutoRefund {
-                maker,
-                mint_a,
-                maker_ata_a,
-                escrow,
-                vault,
-                token_program,
-                system_program,
-            }.to_account_metas(None),
-            data: crate::instruction::AutoRefund { seed }.data(),
-        };
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let auto_refund_ix = AutoRefundBuilder::new(&escrow_data).instruction();
+
+        // Before the expiry only the maker can refund, even once unlocked
+        let mut clock: Clock = program.get_sysvar();
+        clock.unix_timestamp = expires_at - 1;
+        program.set_sysvar(&clock);
+
+        let message = Message::new(&[auto_refund_ix.clone()], Some(&cranker.pubkey()));
+        let transaction = Transaction::new(&[&cranker], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "EscrowNotExpired");
+        assert_eq!(token_amount(&program, &vault), 50);
+
+        clock.unix_timestamp = expires_at;
+        program.set_sysvar(&clock);
+        program.expire_blockhash();
 
         let message = Message::new(&[auto_refund_ix], Some(&cranker.pubkey()));
         let transaction = Transaction::new(&[&cranker], message, program.latest_blockhash());
@@ -668,6 +777,13 @@ mod tests {
         msg!("\n\nAutoRefund transaction successful");
         msg!("CUs Consumed: {}", tx.compute_units_consumed);
 
+        // Verify: the AutoRefundExecuted event carries the returned amount
+        let executed: crate::AutoRefundExecuted = find_event(&tx.logs).expect("AutoRefundExecuted event");
+        assert_eq!(executed.escrow, escrow);
+        assert_eq!(executed.maker, maker);
+        assert_eq!(executed.mint_a, mint_a);
+        assert_eq!(executed.amount, 50);
+
         // Verify: maker received tokens back
         let maker_ata_a_account = program.get_account(&maker_ata_a).unwrap();
         let maker_ata_a_data = spl_token::state::Account::unpack(&maker_ata_a_account.data).unwrap();
@@ -720,35 +836,19 @@ mod tests {
             .send()
             .unwrap();
 
+        // Make
+        // Lock the escrow for 5 days from the current clock time
         let seed: u64 = 888;
-        let escrow = Pubkey::find_program_address(
-            &[b"escrow", maker.as_ref(), &seed.to_le_bytes()],
-            &PROGRAM_ID
-        ).0;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp + 5 * 24 * 60 * 60;
 
-        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
-        let token_program = TOKEN_PROGRAM_ID;
-        let system_program = SYSTEM_PROGRAM_ID;
-        let associated_token_program = spl_associated_token_account::ID;
-
-        // Make
-        let make_ix = Instruction {
-            program_id: PROGRAM_ID,
-            accounts: crate::accounts::Make {
-                maker,
-                mint_a,
-                mint_b,
-                maker_ata_a,
-                escrow,
-                vault,
-                associated_token_program,
-                token_program,
-                system_program,
-            }.to_account_metas(None),
-            data: crate::instruction::Make { deposit: 10, seed, receive: 10 }.data(),
-        };
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 10)
+            .request(mint_b, 10)
+            .unlock_at(unlock_at);
+        let escrow = make.escrow();
 
-        let message = Message::new(&[make_ix], Some(&payer.pubkey()));
+        let message = Message::new(&[make.instruction()], Some(&payer.pubkey()));
         let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
         program.send_transaction(transaction).unwrap();
 
@@ -759,27 +859,9 @@ mod tests {
         program.set_sysvar(&clock);
 
         // Take (taker fulfills the escrow)
-        let taker_ata_a = associated_token::get_associated_token_address(&taker, &mint_a);
-        let maker_ata_b = associated_token::get_associated_token_address(&maker, &mint_b);
-
-        let take_ix = Instruction {
-            program_id: PROGRAM_ID,
-            accounts: crate::accounts::Take {
-                taker,
-                maker,
-                mint_a,
-                mint_b,
-                taker_ata_a,
-                taker_ata_b,
-                maker_ata_b,
-                escrow,
-                vault,
-                associated_token_program,
-                token_program,
-                system_program,
-            }.to_account_metas(None),
-            data: crate::instruction::Take {}.data(),
-        };
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take_ix = TakeBuilder::new(taker, &escrow_data, &config).instruction();
 
         let message = Message::new(&[take_ix], Some(&taker_kp.pubkey()));
         let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
@@ -795,19 +877,8 @@ mod tests {
         let cranker = Keypair::new();
         program.airdrop(&cranker.pubkey(), 1 * LAMPORTS_PER_SOL).unwrap();
 
-        let auto_refund_ix = Instruction {
-            program_id: PROGRAM_ID,
-            accounts: crate::accounts::AutoRefund {
-                maker,
-                mint_a,
-                maker_ata_a,
-                escrow,
-                vault,
-                token_program,
-                system_program,
-            }.to_account_metas(None),
-            data: crate::instruction::AutoRefund { seed }.data(),
-        };
+        // Built from the escrow as it was before the take closed it
+        let auto_refund_ix = AutoRefundBuilder::new(&escrow_data).instruction();
 
         let message = Message::new(&[auto_refund_ix], Some(&cranker.pubkey()));
         let transaction = Transaction::new(&[&cranker], message, program.latest_blockhash());
@@ -817,4 +888,3162 @@ mod tests {
         msg!("AutoRefund no-op test passed!");
     }
 
+    #[test]
+    fn test_take_time_window_boundaries() {
+        // Take is only allowed in [unlock_at, expires_at): check both edges of the window
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let mint_a = CreateMint::new(&mut program, &payer)
+            .decimals(6)
+            .authority(&maker)
+            .send()
+            .unwrap();
+
+        let mint_b = CreateMint::new(&mut program, &taker_kp)
+            .decimals(6)
+            .authority(&taker)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut program, &payer, &mint_a)
+            .owner(&maker).send().unwrap();
+
+        MintTo::new(&mut program, &payer, &mint_a, &maker_ata_a, 1_000_000_000)
+            .send()
+            .unwrap();
+
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut program, &taker_kp, &mint_b)
+            .owner(&taker).send().unwrap();
+
+        MintTo::new(&mut program, &taker_kp, &mint_b, &taker_ata_b, 1_000_000_000)
+            .send()
+            .unwrap();
+
+        // Open for one hour, starting one day from now
+        let seed: u64 = 321;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp + 24 * 60 * 60;
+        let expires_at = unlock_at + 60 * 60;
+
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 10)
+            .request(mint_b, 10)
+            .unlock_at(unlock_at)
+            .expires_at(expires_at);
+        let escrow = make.escrow();
+
+        let message = Message::new(&[make.instruction()], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let taker_ata_a = pda::ata(&taker, &mint_a, &TOKEN_PROGRAM_ID);
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take_ix = TakeBuilder::new(taker, &escrow_data, &config).instruction();
+
+        // One second before unlock_at: still locked
+        let mut clock: Clock = program.get_sysvar();
+        clock.unix_timestamp = unlock_at - 1;
+        program.set_sysvar(&clock);
+
+        let message = Message::new(&[take_ix.clone()], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "TimeLockNotExpired");
+
+        // Exactly at expires_at: expired
+        clock.unix_timestamp = expires_at;
+        program.set_sysvar(&clock);
+        program.expire_blockhash();
+
+        let message = Message::new(&[take_ix.clone()], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "EscrowExpired");
+
+        // Last second of the window: take succeeds
+        clock.unix_timestamp = expires_at - 1;
+        program.set_sysvar(&clock);
+        program.expire_blockhash();
+
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let taker_ata_a_account = program.get_account(&taker_ata_a).unwrap();
+        let taker_ata_a_data = spl_token::state::Account::unpack(&taker_ata_a_account.data).unwrap();
+        assert_eq!(taker_ata_a_data.amount, 10);
+
+        let escrow_after = program.get_account(&escrow);
+        assert!(escrow_after.is_none() || escrow_after.unwrap().lamports == 0);
+
+        msg!("Time window boundary assertions passed!");
+    }
+
+    #[test]
+    fn test_make_rejects_expiry_before_unlock() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let mint_a = CreateMint::new(&mut program, &payer)
+            .decimals(6)
+            .authority(&maker)
+            .send()
+            .unwrap();
+
+        let mint_b = CreateMint::new(&mut program, &payer)
+            .decimals(6)
+            .authority(&maker)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut program, &payer, &mint_a)
+            .owner(&maker).send().unwrap();
+
+        MintTo::new(&mut program, &payer, &mint_a, &maker_ata_a, 1_000_000_000)
+            .send()
+            .unwrap();
+
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp + 60;
+
+        let make = MakeBuilder::new(maker, 654)
+            .offer(mint_a, 10)
+            .request(mint_b, 10)
+            .unlock_at(unlock_at)
+            .expires_at(unlock_at);
+        let escrow = make.escrow();
+
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "InvalidTimeWindow");
+
+        assert!(program.get_account(&escrow).is_none());
+    }
+
+    #[test]
+    fn test_partial_fills() {
+        // Maker offers 100 of mint_a for 30 of mint_b; the taker fills it in two steps
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let mint_a = CreateMint::new(&mut program, &payer)
+            .decimals(6)
+            .authority(&maker)
+            .send()
+            .unwrap();
+
+        let mint_b = CreateMint::new(&mut program, &taker_kp)
+            .decimals(6)
+            .authority(&taker)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut program, &payer, &mint_a)
+            .owner(&maker).send().unwrap();
+
+        MintTo::new(&mut program, &payer, &mint_a, &maker_ata_a, 1_000_000_000)
+            .send()
+            .unwrap();
+
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut program, &taker_kp, &mint_b)
+            .owner(&taker).send().unwrap();
+
+        MintTo::new(&mut program, &taker_kp, &mint_b, &taker_ata_b, 1_000_000_000)
+            .send()
+            .unwrap();
+
+        // No time lock: takeable immediately
+        let seed: u64 = 555;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp;
+
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 100)
+            .request(mint_b, 30)
+            .unlock_at(unlock_at);
+        let escrow = make.escrow();
+        let vault = make.vault().unwrap();
+
+        let message = Message::new(&[make.instruction()], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let taker_ata_a = pda::ata(&taker, &mint_a, &TOKEN_PROGRAM_ID);
+        let maker_ata_b = pda::ata(&maker, &mint_b, &TOKEN_PROGRAM_ID);
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take = TakeBuilder::new(taker, &escrow_data, &config);
+
+        // Asking for more than the remaining receive amount is rejected
+        let take_ix = take.clone().amount(31).instruction();
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "InvalidFillAmount");
+
+        // First fill: 10 of 30 -> floor(100 * 10 / 30) = 33 of mint_a
+        let take_ix = take.clone().amount(10).instruction();
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let taker_ata_a_data = spl_token::state::Account::unpack(&program.get_account(&taker_ata_a).unwrap().data).unwrap();
+        assert_eq!(taker_ata_a_data.amount, 33);
+
+        let vault_data = spl_token::state::Account::unpack(&program.get_account(&vault).unwrap().data).unwrap();
+        assert_eq!(vault_data.amount, 67);
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        assert_eq!(escrow_data.receive, 20);
+
+        // Final fill: remaining 20 of mint_b takes everything left in the vault
+        let take_ix = take.amount(20).instruction();
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let taker_ata_a_data = spl_token::state::Account::unpack(&program.get_account(&taker_ata_a).unwrap().data).unwrap();
+        assert_eq!(taker_ata_a_data.amount, 100);
+
+        let maker_ata_b_data = spl_token::state::Account::unpack(&program.get_account(&maker_ata_b).unwrap().data).unwrap();
+        assert_eq!(maker_ata_b_data.amount, 30);
+
+        let vault_after = program.get_account(&vault);
+        assert!(vault_after.is_none() || vault_after.unwrap().lamports == 0);
+
+        let escrow_after = program.get_account(&escrow);
+        assert!(escrow_after.is_none() || escrow_after.unwrap().lamports == 0);
+
+        msg!("Partial fill assertions passed!");
+    }
+
+    #[test]
+    fn test_partial_fill_too_small() {
+        // 10 of mint_a for 100 of mint_b: paying 1 of mint_b would round down to 0
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let mint_a = CreateMint::new(&mut program, &payer)
+            .decimals(6)
+            .authority(&maker)
+            .send()
+            .unwrap();
+
+        let mint_b = CreateMint::new(&mut program, &taker_kp)
+            .decimals(6)
+            .authority(&taker)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut program, &payer, &mint_a)
+            .owner(&maker).send().unwrap();
+
+        MintTo::new(&mut program, &payer, &mint_a, &maker_ata_a, 1_000_000_000)
+            .send()
+            .unwrap();
+
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut program, &taker_kp, &mint_b)
+            .owner(&taker).send().unwrap();
+
+        MintTo::new(&mut program, &taker_kp, &mint_b, &taker_ata_b, 1_000_000_000)
+            .send()
+            .unwrap();
+
+        let seed: u64 = 556;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp;
+
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 10)
+            .request(mint_b, 100)
+            .unlock_at(unlock_at);
+        let vault = make.vault().unwrap();
+
+        let message = Message::new(&[make.instruction()], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take_ix = TakeBuilder::new(taker, &escrow_data, &config).amount(1).instruction();
+
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "FillTooSmall");
+
+        // Nothing moved
+        let vault_data = spl_token::state::Account::unpack(&program.get_account(&vault).unwrap().data).unwrap();
+        assert_eq!(vault_data.amount, 10);
+    }
+
+    #[test]
+    fn test_designated_taker() {
+        // A private escrow can only be taken by the taker named at make time
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let outsider_kp = Keypair::new();
+        program.airdrop(&outsider_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let outsider = outsider_kp.pubkey();
+
+        let mint_a = CreateMint::new(&mut program, &payer)
+            .decimals(6)
+            .authority(&maker)
+            .send()
+            .unwrap();
+
+        let mint_b = CreateMint::new(&mut program, &taker_kp)
+            .decimals(6)
+            .authority(&taker)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut program, &payer, &mint_a)
+            .owner(&maker).send().unwrap();
+
+        MintTo::new(&mut program, &payer, &mint_a, &maker_ata_a, 1_000_000_000)
+            .send()
+            .unwrap();
+
+        // Both the designated taker and the outsider hold enough mint_b
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut program, &taker_kp, &mint_b)
+            .owner(&taker).send().unwrap();
+
+        MintTo::new(&mut program, &taker_kp, &mint_b, &taker_ata_b, 1_000_000_000)
+            .send()
+            .unwrap();
+
+        let outsider_ata_b = CreateAssociatedTokenAccount::new(&mut program, &outsider_kp, &mint_b)
+            .owner(&outsider).send().unwrap();
+
+        MintTo::new(&mut program, &taker_kp, &mint_b, &outsider_ata_b, 1_000_000_000)
+            .send()
+            .unwrap();
+
+        let seed: u64 = 777;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp;
+
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 10)
+            .request(mint_b, 10)
+            .unlock_at(unlock_at)
+            .allowed_takers(vec![taker]);
+        let escrow = make.escrow();
+
+        let message = Message::new(&[make.instruction()], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        assert_eq!(escrow_data.allowed_takers, vec![taker]);
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+
+        // The outsider is rejected
+        let take_ix = TakeBuilder::new(outsider, &escrow_data, &config).instruction();
+
+        let message = Message::new(&[take_ix], Some(&outsider));
+        let transaction = Transaction::new(&[&outsider_kp], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "TakerNotAllowed");
+
+        // The designated taker succeeds
+        let taker_ata_a = pda::ata(&taker, &mint_a, &TOKEN_PROGRAM_ID);
+        let take_ix = TakeBuilder::new(taker, &escrow_data, &config).instruction();
+
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let taker_ata_a_data = spl_token::state::Account::unpack(&program.get_account(&taker_ata_a).unwrap().data).unwrap();
+        assert_eq!(taker_ata_a_data.amount, 10);
+
+        let escrow_after = program.get_account(&escrow);
+        assert!(escrow_after.is_none() || escrow_after.unwrap().lamports == 0);
+
+        msg!("Designated taker assertions passed!");
+    }
+
+    #[test]
+    fn test_take_token_for_native_sol() {
+        // Maker offers 10 of mint_a and asks for 2 SOL in return
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let mint_a = CreateMint::new(&mut program, &payer)
+            .decimals(6)
+            .authority(&maker)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut program, &payer, &mint_a)
+            .owner(&maker).send().unwrap();
+
+        MintTo::new(&mut program, &payer, &mint_a, &maker_ata_a, 1_000_000_000)
+            .send()
+            .unwrap();
+
+        let seed: u64 = 31;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp;
+        let receive = 2 * LAMPORTS_PER_SOL;
+
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 10)
+            .request(client::state::NATIVE_SOL, receive)
+            .unlock_at(unlock_at);
+        let escrow = make.escrow();
+        let vault = make.vault().unwrap();
+
+        let message = Message::new(&[make.instruction()], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        assert_eq!(escrow_data.mint_b, client::state::NATIVE_SOL);
+
+        // The maker gets the SOL payment plus the rent of the closed escrow and vault,
+        // and the rent its index entries no longer need
+        let indexes = [pda::pair_index(&mint_a, &client::state::NATIVE_SOL, 0), pda::maker_index(&maker, 0)];
+        let maker_before = program.get_account(&maker).unwrap().lamports;
+        let indexes_before = lamports(&program, &indexes);
+        let rent_back = program.get_account(&escrow).unwrap().lamports
+            + program.get_account(&vault).unwrap().lamports;
+
+        let taker_ata_a = pda::ata(&taker, &mint_a, &TOKEN_PROGRAM_ID);
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take_ix = TakeBuilder::new(taker, &escrow_data, &config).instruction();
+
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let taker_ata_a_data = spl_token::state::Account::unpack(&program.get_account(&taker_ata_a).unwrap().data).unwrap();
+        assert_eq!(taker_ata_a_data.amount, 10);
+
+        let maker_after = program.get_account(&maker).unwrap().lamports;
+        let index_rent = indexes_before - lamports(&program, &indexes);
+        assert_eq!(maker_after, maker_before + receive + rent_back + index_rent);
+
+        let escrow_after = program.get_account(&escrow);
+        assert!(escrow_after.is_none() || escrow_after.unwrap().lamports == 0);
+
+        msg!("Token for SOL assertions passed!");
+    }
+
+    #[test]
+    fn test_take_native_sol_for_token() {
+        // Maker offers 1 SOL held in the escrow PDA and asks for 10 of mint_b
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let mint_b = CreateMint::new(&mut program, &taker_kp)
+            .decimals(6)
+            .authority(&taker)
+            .send()
+            .unwrap();
+
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut program, &taker_kp, &mint_b)
+            .owner(&taker).send().unwrap();
+
+        MintTo::new(&mut program, &taker_kp, &mint_b, &taker_ata_b, 1_000_000_000)
+            .send()
+            .unwrap();
+
+        // Created up front so the take does not charge the taker any ATA rent
+        let maker_ata_b = CreateAssociatedTokenAccount::new(&mut program, &payer, &mint_b)
+            .owner(&maker).send().unwrap();
+
+        let seed: u64 = 32;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp;
+        let deposit = LAMPORTS_PER_SOL;
+
+        let make = MakeBuilder::new(maker, seed)
+            .offer(client::state::NATIVE_SOL, deposit)
+            .request(mint_b, 10)
+            .unlock_at(unlock_at);
+        let escrow = make.escrow();
+
+        let message = Message::new(&[make.instruction()], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        // The deposit sits in the escrow PDA on top of its rent-exempt minimum
+        let escrow_account = program.get_account(&escrow).unwrap();
+        let rent = program.minimum_balance_for_rent_exemption(escrow_account.data.len());
+        assert_eq!(escrow_account.lamports, rent + deposit);
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        assert_eq!(escrow_data.mint_a, client::state::NATIVE_SOL);
+        assert_ne!(client::state::NATIVE_SOL, SYSTEM_PROGRAM_ID);
+        assert_eq!(escrow_data.deposited, deposit);
+
+        // Lamports sent straight to the PDA are not part of the deposit
+        let mut donated = escrow_account.clone();
+        donated.lamports += 12_345;
+        program.set_account(escrow, donated).unwrap();
+
+        let taker_before = program.get_account(&taker).unwrap().lamports;
+
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take_ix = TakeBuilder::new(taker, &escrow_data, &config).instruction();
+
+        // The maker pays the fee so the taker's lamport delta is exactly the deposit
+        let message = Message::new(&[take_ix], Some(&maker));
+        let transaction = Transaction::new(&[&payer, &taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let taker_after = program.get_account(&taker).unwrap().lamports;
+        assert_eq!(taker_after, taker_before + deposit);
+
+        let maker_ata_b_data = spl_token::state::Account::unpack(&program.get_account(&maker_ata_b).unwrap().data).unwrap();
+        assert_eq!(maker_ata_b_data.amount, 10);
+
+        let escrow_after = program.get_account(&escrow);
+        assert!(escrow_after.is_none() || escrow_after.unwrap().lamports == 0);
+
+        msg!("SOL for token assertions passed!");
+    }
+
+    #[test]
+    fn test_refund_native_sol() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let mint_b = CreateMint::new(&mut program, &payer)
+            .decimals(6)
+            .authority(&maker)
+            .send()
+            .unwrap();
+
+        let seed: u64 = 33;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp + 5 * 24 * 60 * 60;
+
+        let make = MakeBuilder::new(maker, seed)
+            .offer(client::state::NATIVE_SOL, LAMPORTS_PER_SOL)
+            .request(mint_b, 10)
+            .unlock_at(unlock_at);
+        let escrow = make.escrow();
+
+        let message = Message::new(&[make.instruction()], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let indexes = [pda::pair_index(&client::state::NATIVE_SOL, &mint_b, 0), pda::maker_index(&maker, 0)];
+        let maker_before = program.get_account(&maker).unwrap().lamports;
+        let indexes_before = lamports(&program, &indexes);
+        let escrow_lamports = program.get_account(&escrow).unwrap().lamports;
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let refund_ix = RefundBuilder::new(&escrow_data).instruction();
+
+        // A separate fee payer keeps the maker's lamport delta exact
+        let fee_payer = Keypair::new();
+        program.airdrop(&fee_payer.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        let message = Message::new(&[refund_ix], Some(&fee_payer.pubkey()));
+        let transaction = Transaction::new(&[&fee_payer, &payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        // The deposit and the escrow rent both come back with the closed escrow,
+        // along with the rent its index entries no longer need
+        let maker_after = program.get_account(&maker).unwrap().lamports;
+        let index_rent = indexes_before - lamports(&program, &indexes);
+        assert_eq!(maker_after, maker_before + escrow_lamports + index_rent);
+
+        let escrow_after = program.get_account(&escrow);
+        assert!(escrow_after.is_none() || escrow_after.unwrap().lamports == 0);
+
+        msg!("Native refund assertions passed!");
+    }
+
+    // Takes a 1_000 mint_a for 401 mint_b escrow under a 2.5% fee, using mints owned by `token_program`
+    fn take_with_fee(token_program: Pubkey) {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        // The treasury owner never signs; the taker pays for the treasury ATAs
+        let treasury = Pubkey::new_unique();
+        update_config(&mut program, &payer, Some(250), Some(treasury), None).unwrap();
+
+        let mint_a = CreateMint::new(&mut program, &payer)
+            .decimals(6)
+            .authority(&maker)
+            .token_program_id(&token_program)
+            .send()
+            .unwrap();
+
+        let mint_b = CreateMint::new(&mut program, &taker_kp)
+            .decimals(6)
+            .authority(&taker)
+            .token_program_id(&token_program)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut program, &payer, &mint_a)
+            .owner(&maker)
+            .token_program_id(&token_program)
+            .send()
+            .unwrap();
+
+        MintTo::new(&mut program, &payer, &mint_a, &maker_ata_a, 1_000_000)
+            .token_program_id(&token_program)
+            .send()
+            .unwrap();
+
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut program, &taker_kp, &mint_b)
+            .owner(&taker)
+            .token_program_id(&token_program)
+            .send()
+            .unwrap();
+
+        MintTo::new(&mut program, &taker_kp, &mint_b, &taker_ata_b, 1_000_000)
+            .token_program_id(&token_program)
+            .send()
+            .unwrap();
+
+        let seed: u64 = 32;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp;
+
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 1_000)
+            .request(mint_b, 401)
+            .unlock_at(unlock_at)
+            .token_program(token_program);
+        let escrow = make.escrow();
+
+        let message = Message::new(&[make.instruction()], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let taker_ata_a = pda::ata(&taker, &mint_a, &token_program);
+        let maker_ata_b = pda::ata(&maker, &mint_b, &token_program);
+        let treasury_ata_a = pda::ata(&treasury, &mint_a, &token_program);
+        let treasury_ata_b = pda::ata(&treasury, &mint_b, &token_program);
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take_ix = TakeBuilder::new(taker, &escrow_data, &config)
+            .token_program(token_program)
+            .instruction();
+
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        // mint_a leg: 2.5% of 1_000 is exactly 25
+        assert_eq!(token_amount(&program, &taker_ata_a), 975);
+        assert_eq!(token_amount(&program, &treasury_ata_a), 25);
+
+        // mint_b leg: 2.5% of 401 is 10.025, rounded down to 10
+        assert_eq!(token_amount(&program, &maker_ata_b), 391);
+        assert_eq!(token_amount(&program, &treasury_ata_b), 10);
+        assert_eq!(token_amount(&program, &taker_ata_b), 1_000_000 - 401);
+
+        let escrow_after = program.get_account(&escrow);
+        assert!(escrow_after.is_none() || escrow_after.unwrap().lamports == 0);
+    }
+
+    #[test]
+    fn test_take_with_fee_token() {
+        take_with_fee(TOKEN_PROGRAM_ID);
+    }
+
+    #[test]
+    fn test_take_with_fee_token_2022() {
+        take_with_fee(TOKEN_2022_PROGRAM_ID);
+    }
+
+    #[test]
+    fn test_update_config_admin_only() {
+        let (mut program, payer) = setup();
+
+        let intruder = Keypair::new();
+        program.airdrop(&intruder.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        assert_anchor_error(
+            update_config(&mut program, &intruder, Some(500), Some(intruder.pubkey()), None),
+            "Unauthorized",
+        );
+
+        // Fees above the cap are rejected even for the admin
+        assert_anchor_error(
+            update_config(&mut program, &payer, Some(client::state::MAX_FEE_BPS + 1), None, None),
+            "InvalidFee",
+        );
+
+        update_config(&mut program, &payer, Some(100), None, Some(true)).unwrap();
+
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        assert_eq!(config.admin, payer.pubkey());
+        assert_eq!(config.fee_bps, 100);
+        assert_eq!(config.fee_recipient, payer.pubkey());
+        assert!(config.paused);
+    }
+
+    #[test]
+    fn test_init_config_requires_upgrade_authority() {
+        let (mut program, payer) = setup_without_config();
+
+        let intruder = Keypair::new();
+        program.airdrop(&intruder.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        let ix = client::init_config(intruder.pubkey(), 0, intruder.pubkey());
+        let message = Message::new(&[ix], Some(&intruder.pubkey()));
+        let transaction = Transaction::new(&[&intruder], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "Unauthorized");
+        assert!(program.get_account(&pda::config()).is_none());
+
+        let ix = client::init_config(payer.pubkey(), 0, payer.pubkey());
+        let message = Message::new(&[ix], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        assert_eq!(config.admin, payer.pubkey());
+    }
+
+    #[test]
+    fn test_make_and_take_before_init_config() {
+        let (mut program, payer) = setup_without_config();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let (mint_a, _) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, _) = funded_mint(&mut program, &taker_kp, &taker);
+
+        let make = MakeBuilder::new(maker, 67).offer(mint_a, 100).request(mint_b, 50);
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        // With no config the take is free and the taker stands in as fee recipient
+        assert!(client::fetch_config(&fetcher(&program)).is_none());
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 67).unwrap();
+        let take_ix = TakeBuilder::new(taker, &escrow_data, &client::state::Config::unset()).instruction();
+
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        assert_eq!(token_amount(&program, &pda::ata(&taker, &mint_a, &TOKEN_PROGRAM_ID)), 100);
+        assert_eq!(token_amount(&program, &pda::ata(&maker, &mint_b, &TOKEN_PROGRAM_ID)), 50);
+
+        // Bundles read the missing config the same way
+        let bundle = make_bundle(&mut program, &payer, 2, &[(mint_a, 100)], &[(mint_b, 50)], None);
+        let bundle_data = client::fetch_bundle(&fetcher(&program), &maker, 2).unwrap();
+        let take_ix = TakeBundleBuilder::new(taker, &bundle_data, &client::state::Config::unset()).instruction();
+
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        assert_eq!(token_amount(&program, &pda::ata(&taker, &mint_a, &TOKEN_PROGRAM_ID)), 200);
+        assert_eq!(token_amount(&program, &pda::ata(&maker, &mint_b, &TOKEN_PROGRAM_ID)), 100);
+        assert!(program.get_account(&bundle).is_none_or(|account| account.lamports == 0));
+    }
+
+    #[test]
+    fn test_native_fee_needs_rent_exempt_recipient() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let (mint_b, _) = funded_mint(&mut program, &taker_kp, &taker);
+
+        // 1% of 1_000 lamports is far below the rent-exempt minimum of an empty account
+        let recipient = Pubkey::new_unique();
+        update_config(&mut program, &payer, Some(100), Some(recipient), None).unwrap();
+
+        let make = MakeBuilder::new(maker, 68).offer(client::state::NATIVE_SOL, 1_000).request(mint_b, 10);
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 68).unwrap();
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take_ix = TakeBuilder::new(taker, &escrow_data, &config).instruction();
+
+        let message = Message::new(&[take_ix.clone()], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "FeeRecipientNotRentExempt");
+
+        // Once funded, the recipient takes the fee
+        program.airdrop(&recipient, LAMPORTS_PER_SOL).unwrap();
+        program.expire_blockhash();
+
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+        assert_eq!(program.get_account(&recipient).unwrap().lamports, LAMPORTS_PER_SOL + 10);
+    }
+
+    #[test]
+    fn test_take_rejected_while_paused() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let mint_a = CreateMint::new(&mut program, &payer)
+            .decimals(6)
+            .authority(&maker)
+            .send()
+            .unwrap();
+
+        let mint_b = CreateMint::new(&mut program, &taker_kp)
+            .decimals(6)
+            .authority(&taker)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut program, &payer, &mint_a)
+            .owner(&maker).send().unwrap();
+
+        MintTo::new(&mut program, &payer, &mint_a, &maker_ata_a, 1_000_000)
+            .send()
+            .unwrap();
+
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut program, &taker_kp, &mint_b)
+            .owner(&taker).send().unwrap();
+
+        MintTo::new(&mut program, &taker_kp, &mint_b, &taker_ata_b, 1_000_000)
+            .send()
+            .unwrap();
+
+        let seed: u64 = 34;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp;
+
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 10)
+            .request(mint_b, 10)
+            .unlock_at(unlock_at);
+        let escrow = make.escrow();
+
+        let message = Message::new(&[make.instruction()], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        update_config(&mut program, &payer, None, None, Some(true)).unwrap();
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take_ix = TakeBuilder::new(taker, &escrow_data, &config).instruction();
+
+        let message = Message::new(&[take_ix.clone()], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "ProtocolPaused");
+
+        // Unpausing lets the same take go through
+        update_config(&mut program, &payer, None, None, Some(false)).unwrap();
+        program.expire_blockhash();
+
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let escrow_after = program.get_account(&escrow);
+        assert!(escrow_after.is_none() || escrow_after.unwrap().lamports == 0);
+    }
+
+    // Creates a 6-decimal Token-2022 mint with `extensions`, initialized by `extension_ixs`
+    fn create_mint_2022(
+        program: &mut LiteSVM,
+        payer: &Keypair,
+        mint_kp: &Keypair,
+        extensions: &[ExtensionType],
+        extension_ixs: Vec<Instruction>,
+    ) -> Pubkey {
+        let mint = mint_kp.pubkey();
+
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions).unwrap();
+        let lamports = program.minimum_balance_for_rent_exemption(space);
+
+        let mut ixs = vec![create_account(&payer.pubkey(), &mint, lamports, space as u64, &TOKEN_2022_PROGRAM_ID)];
+        ixs.extend(extension_ixs);
+        ixs.push(
+            spl_token_2022::instruction::initialize_mint2(&TOKEN_2022_PROGRAM_ID, &mint, &payer.pubkey(), None, 6).unwrap()
+        );
+
+        let message = Message::new(&ixs, Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[payer, mint_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        mint
+    }
+
+    #[test]
+    fn test_transfer_fee_mint_deposit_and_refund() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        // 1% transfer fee, effectively uncapped
+        let mint_a_kp = Keypair::new();
+        let mint_a = create_mint_2022(
+            &mut program,
+            &payer,
+            &mint_a_kp,
+            &[ExtensionType::TransferFeeConfig],
+            vec![initialize_transfer_fee_config(&TOKEN_2022_PROGRAM_ID, &mint_a_kp.pubkey(), Some(&maker), Some(&maker), 100, u64::MAX).unwrap()],
+        );
+
+        let mint_b = CreateMint::new(&mut program, &payer)
+            .decimals(6)
+            .authority(&maker)
+            .token_program_id(&TOKEN_2022_PROGRAM_ID)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut program, &payer, &mint_a)
+            .owner(&maker)
+            .token_program_id(&TOKEN_2022_PROGRAM_ID)
+            .send()
+            .unwrap();
+
+        MintTo::new(&mut program, &payer, &mint_a, &maker_ata_a, 1_000_000)
+            .token_program_id(&TOKEN_2022_PROGRAM_ID)
+            .send()
+            .unwrap();
+
+        let seed: u64 = 35;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp + 5 * 24 * 60 * 60;
+
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 1_000)
+            .request(mint_b, 10)
+            .unlock_at(unlock_at)
+            .token_program(TOKEN_2022_PROGRAM_ID);
+        let escrow = make.escrow();
+        let vault = make.vault().unwrap();
+        let make_ix = make.instruction();
+
+        let message = Message::new(&[make_ix], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        // 1% of 1_000 is withheld, and the escrow records the net amount
+        assert_eq!(token_amount(&program, &vault), 990);
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        assert_eq!(escrow_data.deposited, 990);
+
+        // Refunding harvests the withheld fee so the vault can be closed, which
+        // needs the Token-2022 mint writable
+        let refund_ix = RefundBuilder::new(&escrow_data)
+            .token_program(TOKEN_2022_PROGRAM_ID)
+            .instruction();
+        assert!(refund_ix.accounts.iter().any(|meta| meta.pubkey == mint_a && meta.is_writable));
+
+        let mut read_only_ix = refund_ix.clone();
+        read_only_ix.accounts.iter_mut()
+            .filter(|meta| meta.pubkey == mint_a)
+            .for_each(|meta| meta.is_writable = false);
+        let message = Message::new(&[read_only_ix], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        let result = program.send_transaction(transaction);
+        assert!(result.is_err(), "Refund should fail when the transfer-fee mint is read-only");
+        program.expire_blockhash();
+
+        let message = Message::new(&[refund_ix], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        // The refund of 990 pays ceil(9.9) = 10 in fees again
+        assert_eq!(token_amount(&program, &maker_ata_a), 1_000_000 - 1_000 + 980);
+
+        let vault_after = program.get_account(&vault);
+        assert!(vault_after.is_none() || vault_after.unwrap().lamports == 0);
+
+        let escrow_after = program.get_account(&escrow);
+        assert!(escrow_after.is_none() || escrow_after.unwrap().lamports == 0);
+    }
+
+    #[test]
+    fn test_make_rejects_non_transferable_mint() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let mint_a = CreateMint::new(&mut program, &payer)
+            .decimals(6)
+            .authority(&maker)
+            .token_program_id(&TOKEN_2022_PROGRAM_ID)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut program, &payer, &mint_a)
+            .owner(&maker)
+            .token_program_id(&TOKEN_2022_PROGRAM_ID)
+            .send()
+            .unwrap();
+
+        MintTo::new(&mut program, &payer, &mint_a, &maker_ata_a, 1_000_000)
+            .token_program_id(&TOKEN_2022_PROGRAM_ID)
+            .send()
+            .unwrap();
+
+        // Asking for a non-transferable mint would leave the taker unable to pay
+        let mint_b_kp = Keypair::new();
+        let mint_b = create_mint_2022(
+            &mut program,
+            &payer,
+            &mint_b_kp,
+            &[ExtensionType::NonTransferable],
+            vec![spl_token_2022::instruction::initialize_non_transferable_mint(&TOKEN_2022_PROGRAM_ID, &mint_b_kp.pubkey()).unwrap()],
+        );
+
+        let seed: u64 = 36;
+        let clock: Clock = program.get_sysvar();
+
+        let make_ix = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 10)
+            .request(mint_b, 10)
+            .unlock_at(clock.unix_timestamp)
+            .token_program(TOKEN_2022_PROGRAM_ID)
+            .instruction();
+
+        let message = Message::new(&[make_ix], Some(&payer.pubkey()));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "UnsupportedMintExtension");
+    }
+
+    // Creates a legacy Token mint owned by `authority` and an ATA for `owner` holding 1_000_000
+    fn funded_mint(program: &mut LiteSVM, authority: &Keypair, owner: &Pubkey) -> (Pubkey, Pubkey) {
+        let mint = CreateMint::new(program, authority)
+            .decimals(6)
+            .authority(&authority.pubkey())
+            .send()
+            .unwrap();
+
+        let ata = CreateAssociatedTokenAccount::new(program, authority, &mint)
+            .owner(owner).send().unwrap();
+
+        MintTo::new(program, authority, &mint, &ata, 1_000_000)
+            .send()
+            .unwrap();
+
+        (mint, ata)
+    }
+
+    // Token program owning `mint`
+    fn mint_program(program: &LiteSVM, mint: &Pubkey) -> Pubkey {
+        program.get_account(mint).unwrap().owner
+    }
+
+    fn bundle_ata(program: &LiteSVM, owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        associated_token::get_associated_token_address_with_program_id(owner, mint, &mint_program(program, mint))
+    }
+
+    // Sends make_bundle for `offered` (mint, amount) legs funded from the maker's ATAs
+    fn make_bundle(
+        program: &mut LiteSVM,
+        payer: &Keypair,
+        seed: u64,
+        offered: &[(Pubkey, u64)],
+        requested: &[(Pubkey, u64)],
+        expires_at: Option<i64>,
+    ) -> Pubkey {
+        let maker = payer.pubkey();
+        let mut make = MakeBundleBuilder::new(maker, seed);
+        for (mint, amount) in offered {
+            make = make.offer(*mint, *amount).token_program(*mint, mint_program(program, mint));
+        }
+        for (mint, amount) in requested {
+            make = make.request(*mint, *amount);
+        }
+        if let Some(expires_at) = expires_at {
+            make = make.expires_at(expires_at);
+        }
+        let bundle = make.bundle();
+        let make_ix = make.instruction();
+
+        let message = Message::new(&[make_ix], Some(&maker));
+        let transaction = Transaction::new(&[payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        bundle
+    }
+
+    #[test]
+    fn test_take_bundle() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        // A 1% protocol fee is charged on every leg
+        let treasury = Pubkey::new_unique();
+        update_config(&mut program, &payer, Some(100), Some(treasury), None).unwrap();
+
+        let (mint_a1, _) = funded_mint(&mut program, &payer, &maker);
+        let (mint_a2, _) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b1, taker_ata_b1) = funded_mint(&mut program, &taker_kp, &taker);
+
+        // The second requested leg is a Token-2022 mint, settled by its own token program
+        let mint_b2 = CreateMint::new(&mut program, &taker_kp)
+            .decimals(6)
+            .authority(&taker)
+            .token_program_id(&TOKEN_2022_PROGRAM_ID)
+            .send()
+            .unwrap();
+        let taker_ata_b2 = CreateAssociatedTokenAccount::new(&mut program, &taker_kp, &mint_b2)
+            .owner(&taker)
+            .token_program_id(&TOKEN_2022_PROGRAM_ID)
+            .send()
+            .unwrap();
+        MintTo::new(&mut program, &taker_kp, &mint_b2, &taker_ata_b2, 1_000_000)
+            .token_program_id(&TOKEN_2022_PROGRAM_ID)
+            .send()
+            .unwrap();
+
+        let bundle = make_bundle(
+            &mut program,
+            &payer,
+            1,
+            &[(mint_a1, 100), (mint_a2, 200)],
+            &[(mint_b1, 300), (mint_b2, 400)],
+            None,
+        );
+
+        let vault_1 = bundle_ata(&program, &bundle, &mint_a1);
+        let vault_2 = bundle_ata(&program, &bundle, &mint_a2);
+        assert_eq!(token_amount(&program, &vault_1), 100);
+        assert_eq!(token_amount(&program, &vault_2), 200);
+
+        let bundle_data = client::fetch_bundle(&fetcher(&program), &maker, 1).unwrap();
+        assert_eq!(bundle_data.maker, maker);
+        assert_eq!(bundle_data.offered.len(), 2);
+        assert_eq!(bundle_data.requested[1].amount, 400);
+        assert_eq!(bundle_data.expires_at, None);
+
+        // The fee recipient is checked against the config, as for `take`
+        let mut config = client::fetch_config(&fetcher(&program)).unwrap();
+        config.fee_recipient = taker;
+        let take_ix = TakeBundleBuilder::new(taker, &bundle_data, &config)
+            .token_program(mint_b2, TOKEN_2022_PROGRAM_ID)
+            .instruction();
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "InvalidFeeRecipient");
+
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take_ix = TakeBundleBuilder::new(taker, &bundle_data, &config)
+            .token_program(mint_b2, TOKEN_2022_PROGRAM_ID)
+            .instruction();
+
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        let tx = program.send_transaction(transaction).unwrap();
+        msg!("TakeBundle CUs Consumed: {}", tx.compute_units_consumed);
+
+        // Every requested leg reached the maker, less the 1% fee
+        assert_eq!(token_amount(&program, &bundle_ata(&program, &maker, &mint_b1)), 297);
+        assert_eq!(token_amount(&program, &bundle_ata(&program, &maker, &mint_b2)), 396);
+        assert_eq!(token_amount(&program, &taker_ata_b1), 1_000_000 - 300);
+        assert_eq!(token_amount(&program, &taker_ata_b2), 1_000_000 - 400);
+
+        // Every vault was emptied to the taker, less the 1% fee, and closed
+        assert_eq!(token_amount(&program, &bundle_ata(&program, &taker, &mint_a1)), 99);
+        assert_eq!(token_amount(&program, &bundle_ata(&program, &taker, &mint_a2)), 198);
+        for (mint, fee) in [(mint_a1, 1), (mint_a2, 2), (mint_b1, 3), (mint_b2, 4)] {
+            assert_eq!(token_amount(&program, &bundle_ata(&program, &treasury, &mint)), fee);
+        }
+        for account in [vault_1, vault_2, bundle] {
+            let after = program.get_account(&account);
+            assert!(after.is_none() || after.unwrap().lamports == 0);
+        }
+    }
+
+    #[test]
+    fn test_refund_and_auto_refund_bundle() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let (mint_a1, maker_ata_a1) = funded_mint(&mut program, &payer, &maker);
+        let (mint_a2, maker_ata_a2) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, _) = funded_mint(&mut program, &payer, &maker);
+
+        let offered = [(mint_a1, 100), (mint_a2, 200)];
+        let requested = [(mint_b, 10)];
+        let expires_at = program.get_sysvar::<Clock>().unix_timestamp + 60 * 60;
+        let refunded = make_bundle(&mut program, &payer, 2, &offered, &requested, None);
+        let auto_refunded = make_bundle(&mut program, &payer, 3, &offered, &requested, Some(expires_at));
+        assert_eq!(token_amount(&program, &maker_ata_a1), 1_000_000 - 200);
+        assert_eq!(token_amount(&program, &maker_ata_a2), 1_000_000 - 400);
+
+        // The maker refunds the first bundle
+        let refund_ix = RefundBundleBuilder::new(&client::fetch_bundle(&fetcher(&program), &maker, 2).unwrap())
+            .instruction();
+
+        let message = Message::new(&[refund_ix], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        // A cranker can auto-refund the second one without the maker's signature,
+        // but only once it has expired
+        let cranker = Keypair::new();
+        program.airdrop(&cranker.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        let auto_refund_ix = AutoRefundBundleBuilder::new(
+            cranker.pubkey(),
+            &client::fetch_bundle(&fetcher(&program), &maker, 3).unwrap(),
+        ).instruction();
+
+        let message = Message::new(&[auto_refund_ix.clone()], Some(&cranker.pubkey()));
+        let transaction = Transaction::new(&[&cranker], message, program.latest_blockhash());
+        let result = program.send_transaction(transaction);
+        assert!(result.is_err(), "AutoRefundBundle should fail before the bundle expires");
+
+        let mut clock: Clock = program.get_sysvar();
+        clock.unix_timestamp = expires_at;
+        program.set_sysvar(&clock);
+        program.expire_blockhash();
+
+        let message = Message::new(&[auto_refund_ix], Some(&cranker.pubkey()));
+        let transaction = Transaction::new(&[&cranker], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        // Both bundles returned every vault in full
+        assert_eq!(token_amount(&program, &maker_ata_a1), 1_000_000);
+        assert_eq!(token_amount(&program, &maker_ata_a2), 1_000_000);
+        for bundle in [refunded, auto_refunded] {
+            for mint in [mint_a1, mint_a2] {
+                let vault = program.get_account(&associated_token::get_associated_token_address(&bundle, &mint));
+                assert!(vault.is_none() || vault.unwrap().lamports == 0);
+            }
+            let after = program.get_account(&bundle);
+            assert!(after.is_none() || after.unwrap().lamports == 0);
+        }
+    }
+
+    #[test]
+    fn test_auto_refund_bundle_requires_expiry() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let (mint_a, maker_ata_a) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, _) = funded_mint(&mut program, &payer, &maker);
+        make_bundle(&mut program, &payer, 4, &[(mint_a, 100)], &[(mint_b, 10)], None);
+
+        // Without an expiry only the maker can refund, however late it gets
+        let mut clock: Clock = program.get_sysvar();
+        clock.unix_timestamp += 365 * 24 * 60 * 60;
+        program.set_sysvar(&clock);
+
+        let cranker = Keypair::new();
+        program.airdrop(&cranker.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        let auto_refund_ix = AutoRefundBundleBuilder::new(
+            cranker.pubkey(),
+            &client::fetch_bundle(&fetcher(&program), &maker, 4).unwrap(),
+        ).instruction();
+
+        let message = Message::new(&[auto_refund_ix], Some(&cranker.pubkey()));
+        let transaction = Transaction::new(&[&cranker], message, program.latest_blockhash());
+        let result = program.send_transaction(transaction);
+        assert!(result.is_err(), "AutoRefundBundle should fail for a bundle without an expiry");
+        assert_eq!(token_amount(&program, &maker_ata_a), 1_000_000 - 100);
+    }
+
+    #[test]
+    fn test_update_escrow() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let (mint_a, maker_ata_a) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, _) = funded_mint(&mut program, &payer, &maker);
+
+        let seed: u64 = 37;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp + 24 * 60 * 60;
+
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 100)
+            .request(mint_b, 50)
+            .unlock_at(unlock_at);
+        let escrow = make.escrow();
+        let vault = make.vault().unwrap();
+
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let created_at = escrow_data.created_at;
+
+        // Top up by 50, ask for 60 instead of 50 and add an expiry
+        let expires_at = unlock_at + 60 * 60;
+        let update_ix = UpdateEscrowBuilder::new(&escrow_data)
+            .top_up(50)
+            .receive(60)
+            .expires_at(expires_at)
+            .instruction();
+
+        let message = Message::new(&[update_ix], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        let tx = program.send_transaction(transaction).unwrap();
+
+        let event: crate::EscrowUpdated = find_event(&tx.logs).expect("EscrowUpdated event");
+        assert_eq!(event.escrow, escrow);
+        assert_eq!((event.old_deposited, event.new_deposited), (100, 150));
+        assert_eq!((event.old_receive, event.new_receive), (50, 60));
+        assert_eq!((event.old_expires_at, event.new_expires_at), (None, Some(expires_at)));
+
+        assert_eq!(token_amount(&program, &vault), 150);
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        assert_eq!(escrow_data.deposited, 150);
+        assert_eq!(escrow_data.receive, 60);
+        assert_eq!(escrow_data.expires_at, Some(expires_at));
+        assert_eq!(escrow_data.created_at, created_at);
+
+        // Withdrawing the whole deposit is rejected; withdrawing part of it is not
+        let withdraw = |amount: u64| UpdateEscrowBuilder::new(&escrow_data).withdraw(amount).instruction();
+
+        let message = Message::new(&[withdraw(150)], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "InvalidDepositChange");
+
+        let message = Message::new(&[withdraw(30)], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        assert_eq!(token_amount(&program, &vault), 120);
+        assert_eq!(token_amount(&program, &maker_ata_a), 1_000_000 - 120);
+    }
+
+    #[test]
+    fn test_schedule_refund_end_to_end() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let (mint_a, maker_ata_a) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, _) = funded_mint(&mut program, &payer, &maker);
+
+        let seed: u64 = 39;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp + 24 * 60 * 60;
+
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 100)
+            .request(mint_b, 50)
+            .unlock_at(unlock_at);
+        let escrow = make.escrow();
+        let vault = make.vault().unwrap();
+
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        // Queue the auto-refund an hour after the escrow unlocks, on a queue the config allows;
+        // the trigger becomes the escrow's expiry
+        let task_queue = Pubkey::new_unique();
+        update_scheduling(&mut program, &payer, None, None, Some(vec![task_queue])).unwrap();
+
+        let expires_at = unlock_at + 60 * 60;
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let schedule = ScheduleRefundBuilder::new(&escrow_data, task_queue, 0, expires_at)
+            .crank_reward(1_000_000);
+        let task = schedule.task();
+
+        let message = Message::new(&[schedule.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        let tx = program.send_transaction(transaction).unwrap();
+
+        let scheduled: crate::RefundScheduled = find_event(&tx.logs).expect("RefundScheduled event");
+        assert_eq!(scheduled.escrow, escrow);
+        assert_eq!(scheduled.task, task);
+        assert_eq!(scheduled.expiry_timestamp, expires_at);
+        assert_eq!(scheduled.crank_reward, 1_000_000);
+        assert_eq!(client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap().expires_at, Some(expires_at));
+
+        let task_data = tuktuk_mock::TaskV0::try_deserialize(
+            &mut program.get_account(&task).unwrap().data.as_ref()
+        ).unwrap();
+        assert_eq!(task_data.trigger, tuktuk_mock::TriggerV0::Timestamp(expires_at));
+        assert_eq!(task_data.rent_refund, maker);
+        assert_eq!(task_data.crank_reward, 1_000_000);
+
+        // Cranking before the trigger is rejected and leaves the escrow untouched
+        let cranker = Keypair::new();
+        program.airdrop(&cranker.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        assert_anchor_error(crank_task(&mut program, &cranker, &task), "TaskNotReady");
+        assert_eq!(token_amount(&program, &vault), 100);
+
+        // Warp to the trigger: the replayed auto_refund returns the deposit and the cranker earns the reward
+        let mut clock: Clock = program.get_sysvar();
+        clock.unix_timestamp = expires_at;
+        program.set_sysvar(&clock);
+        program.expire_blockhash();
+
+        let cranker_before = program.get_account(&cranker.pubkey()).unwrap().lamports;
+        let tx = crank_task(&mut program, &cranker, &task).unwrap();
+
+        let executed: crate::AutoRefundExecuted = find_event(&tx.logs).expect("AutoRefundExecuted event");
+        assert_eq!(executed.escrow, escrow);
+        assert_eq!(executed.amount, 100);
+        assert_eq!(token_amount(&program, &maker_ata_a), 1_000_000);
+
+        let cranker_after = program.get_account(&cranker.pubkey()).unwrap().lamports;
+        assert_eq!(cranker_after + 5_000, cranker_before + task_data.crank_reward);
+
+        for account in [vault, escrow, task] {
+            let after = program.get_account(&account);
+            assert!(after.is_none() || after.unwrap().lamports == 0);
+        }
+    }
+
+    #[test]
+    fn test_take_and_refund_dequeue_scheduled_task() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let (mint_a, maker_ata_a) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, _) = funded_mint(&mut program, &taker_kp, &taker);
+
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp;
+        let task_queue = Pubkey::new_unique();
+        update_scheduling(&mut program, &payer, None, None, Some(vec![task_queue])).unwrap();
+
+        // Two escrows, each with an auto-refund queued a day out
+        let mut tasks = vec![];
+        for seed in [40u64, 41] {
+            let make = MakeBuilder::new(maker, seed)
+                .offer(mint_a, 100)
+                .request(mint_b, 50)
+                .unlock_at(unlock_at);
+            let message = Message::new(&[make.instruction()], Some(&maker));
+            let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+            program.send_transaction(transaction).unwrap();
+
+            let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+            let schedule = ScheduleRefundBuilder::new(&escrow_data, task_queue, seed as u16, unlock_at + 24 * 60 * 60)
+                .crank_reward(1_000_000);
+            let message = Message::new(&[schedule.instruction()], Some(&maker));
+            let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+            program.send_transaction(transaction).unwrap();
+
+            let refund_task = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap().refund_task.unwrap();
+            assert_eq!(refund_task.task_queue, task_queue);
+            assert_eq!(refund_task.task, schedule.task());
+            assert_eq!(refund_task.task_id, seed as u16);
+            tasks.push(refund_task.task);
+        }
+
+        // An escrow holds a single scheduled refund
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 40).unwrap();
+        let reschedule = ScheduleRefundBuilder::new(&escrow_data, task_queue, 99, unlock_at + 60);
+        let message = Message::new(&[reschedule.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "RefundAlreadyScheduled");
+
+        // Refunding the first escrow returns the task's rent and crank reward along with the deposit
+        let fee_payer = Keypair::new();
+        program.airdrop(&fee_payer.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        let escrow = pda::escrow(&maker, 40);
+        let vault = pda::ata(&escrow, &mint_a, &TOKEN_PROGRAM_ID);
+        let lamports = |program: &LiteSVM, account: &Pubkey| program.get_account(account).map_or(0, |account| account.lamports);
+        let maker_before = lamports(&program, &maker);
+        let released = lamports(&program, &escrow) + lamports(&program, &vault) + lamports(&program, &tasks[0]);
+
+        let message = Message::new(&[RefundBuilder::new(&escrow_data).instruction()], Some(&fee_payer.pubkey()));
+        let transaction = Transaction::new(&[&fee_payer, &payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        assert_eq!(lamports(&program, &maker), maker_before + released);
+        assert_eq!(token_amount(&program, &maker_ata_a), 1_000_000 - 100);
+
+        // Fully taking the second escrow dequeues its task too
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 41).unwrap();
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let message = Message::new(&[TakeBuilder::new(taker, &escrow_data, &config).instruction()], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        for task in tasks {
+            assert_eq!(lamports(&program, &task), 0);
+        }
+    }
+
+    #[test]
+    fn test_schedule_refund_validates_queue_and_reward() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let (mint_a, _) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, _) = funded_mint(&mut program, &payer, &maker);
+
+        let seed: u64 = 41;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp + 24 * 60 * 60;
+
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 100)
+            .request(mint_b, 50)
+            .unlock_at(unlock_at);
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let task_queue = Pubkey::new_unique();
+        let schedule = |program: &mut LiteSVM, task_id: u16, crank_reward: u64| {
+            let ix = ScheduleRefundBuilder::new(&escrow_data, task_queue, task_id, unlock_at + 24 * 60 * 60)
+                .crank_reward(crank_reward)
+                .instruction();
+            let message = Message::new(&[ix], Some(&maker));
+            let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+            program.send_transaction(transaction)
+        };
+
+        // Nothing can be scheduled until the admin allows the queue
+        assert_anchor_error(schedule(&mut program, 0, 1_000), "TaskQueueNotAllowed");
+
+        // Only the admin manages scheduling, with consistent bounds and a short allowlist
+        let intruder = Keypair::new();
+        program.airdrop(&intruder.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        assert_anchor_error(
+            update_scheduling(&mut program, &intruder, None, None, Some(vec![task_queue])),
+            "Unauthorized",
+        );
+        assert_anchor_error(
+            update_scheduling(&mut program, &payer, Some(5_001), Some(5_000), None),
+            "InvalidCrankReward",
+        );
+        let too_many = (0..=client::state::MAX_TASK_QUEUES).map(|_| Pubkey::new_unique()).collect();
+        assert_anchor_error(
+            update_scheduling(&mut program, &payer, None, None, Some(too_many)),
+            "TooManyTaskQueues",
+        );
+
+        update_scheduling(&mut program, &payer, Some(1_000), Some(5_000), Some(vec![task_queue])).unwrap();
+
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        assert_eq!((config.min_crank_reward, config.max_crank_reward), (1_000, 5_000));
+        assert_eq!(config.task_queues, vec![task_queue]);
+
+        // Rewards outside the bounds are rejected; the upper bound itself is accepted
+        assert_anchor_error(schedule(&mut program, 1, 999), "InvalidCrankReward");
+        assert_anchor_error(schedule(&mut program, 2, 5_001), "InvalidCrankReward");
+
+        // The trigger becomes the escrow's expiry, so it must fall after the unlock
+        let ix = ScheduleRefundBuilder::new(&escrow_data, task_queue, 3, unlock_at).crank_reward(5_000).instruction();
+        let message = Message::new(&[ix], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "InvalidTimeWindow");
+
+        let tx = schedule(&mut program, 3, 5_000).unwrap();
+        let scheduled: crate::RefundScheduled = find_event(&tx.logs).expect("RefundScheduled event");
+        assert_eq!(scheduled.crank_reward, 5_000);
+    }
+
+    #[test]
+    fn test_make_with_expiry_queues_auto_refund() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let (mint_a, maker_ata_a) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, _) = funded_mint(&mut program, &payer, &maker);
+
+        let task_queue = Pubkey::new_unique();
+        update_scheduling(&mut program, &payer, None, None, Some(vec![task_queue])).unwrap();
+
+        let seed: u64 = 42;
+        let clock: Clock = program.get_sysvar();
+        let unlock_at = clock.unix_timestamp;
+        let expires_at = unlock_at + 24 * 60 * 60;
+
+        let make = |task_queue: Pubkey| MakeBuilder::new(maker, seed)
+            .offer(mint_a, 100)
+            .request(mint_b, 50)
+            .unlock_at(unlock_at)
+            .expires_at(expires_at)
+            .auto_refund(task_queue, 7, 1_000_000);
+
+        // A queue outside the allowlist fails the whole instruction, so no escrow is left behind
+        let message = Message::new(&[make(Pubkey::new_unique()).instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "TaskQueueNotAllowed");
+        assert!(program.get_account(&make(task_queue).escrow()).is_none());
+
+        let make = make(task_queue);
+        let escrow = make.escrow();
+        let vault = make.vault().unwrap();
+        let task = make.task().unwrap();
+
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        let tx = program.send_transaction(transaction).unwrap();
+
+        let created: crate::EscrowCreated = find_event(&tx.logs).expect("EscrowCreated event");
+        assert_eq!(created.expires_at, Some(expires_at));
+        let scheduled: crate::RefundScheduled = find_event(&tx.logs).expect("RefundScheduled event");
+        assert_eq!(scheduled.escrow, escrow);
+        assert_eq!(scheduled.task, task);
+        assert_eq!(scheduled.expiry_timestamp, expires_at);
+
+        assert_eq!(token_amount(&program, &vault), 100);
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        assert_eq!(escrow_data.expires_at, Some(expires_at));
+        assert_eq!(
+            escrow_data.refund_task,
+            Some(client::state::RefundTask { task_queue, task, task_id: 7 })
+        );
+
+        let task_data = tuktuk_mock::TaskV0::try_deserialize(
+            &mut program.get_account(&task).unwrap().data.as_ref()
+        ).unwrap();
+        assert_eq!(task_data.trigger, tuktuk_mock::TriggerV0::Timestamp(expires_at));
+
+        // The queued task would still fire at the old expiry, so extending it is rejected
+        let extend_ix = UpdateEscrowBuilder::new(&escrow_data).expires_at(expires_at + 24 * 60 * 60).instruction();
+        let message = Message::new(&[extend_ix], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "RefundTaskPending");
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        assert_eq!(escrow_data.expires_at, Some(expires_at));
+
+        // Once the escrow expires the queued auto_refund returns the deposit
+        let cranker = Keypair::new();
+        program.airdrop(&cranker.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        let mut clock: Clock = program.get_sysvar();
+        clock.unix_timestamp = expires_at;
+        program.set_sysvar(&clock);
+        program.expire_blockhash();
+
+        crank_task(&mut program, &cranker, &task).unwrap();
+        assert_eq!(token_amount(&program, &maker_ata_a), 1_000_000);
+
+        for account in [vault, escrow, task] {
+            let after = program.get_account(&account);
+            assert!(after.is_none() || after.unwrap().lamports == 0);
+        }
+    }
+
+    #[test]
+    fn test_auto_refund_rejects_substituted_accounts() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let (mint_a, maker_ata_a) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, _) = funded_mint(&mut program, &payer, &maker);
+        let (other_mint, other_maker_ata) = funded_mint(&mut program, &payer, &maker);
+
+        let clock: Clock = program.get_sysvar();
+        let expires_at = clock.unix_timestamp + 60;
+        let mut vaults = vec![];
+        for (seed, deposit) in [(43u64, 100), (44, 200)] {
+            let make = MakeBuilder::new(maker, seed)
+                .offer(mint_a, deposit)
+                .request(mint_b, 50)
+                .unlock_at(clock.unix_timestamp)
+                .expires_at(expires_at);
+            vaults.push(make.vault().unwrap());
+
+            let message = Message::new(&[make.instruction()], Some(&maker));
+            let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+            program.send_transaction(transaction).unwrap();
+        }
+
+        // Both escrows have expired, so only the account checks stand in the cranker's way
+        let mut clock: Clock = program.get_sysvar();
+        clock.unix_timestamp = expires_at;
+        program.set_sysvar(&clock);
+
+        let seed: u64 = 43;
+        let escrow = pda::escrow(&maker, seed);
+        let cranker = Keypair::new();
+        program.airdrop(&cranker.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        // The builder's instruction with mint_a, maker_ata_a and the vault swapped out
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let auto_refund = |mint: Pubkey, maker_ata: Pubkey, vault: Pubkey| {
+            let mut ix = AutoRefundBuilder::new(&escrow_data).instruction();
+            for meta in &mut ix.accounts {
+                if meta.pubkey == mint_a {
+                    meta.pubkey = mint;
+                } else if meta.pubkey == maker_ata_a {
+                    meta.pubkey = maker_ata;
+                } else if meta.pubkey == vaults[0] {
+                    meta.pubkey = vault;
+                }
+            }
+            ix
+        };
+
+        // Another escrow's vault holds the same mint but is not this escrow's ATA
+        let message = Message::new(&[auto_refund(mint_a, maker_ata_a, vaults[1])], Some(&cranker.pubkey()));
+        let transaction = Transaction::new(&[&cranker], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "InvalidTokenAccount");
+
+        // A different mint, even with a matching ATA for this escrow, is not the offered one
+        let other_vault = associated_token::get_associated_token_address(&escrow, &other_mint);
+        let message = Message::new(&[auto_refund(other_mint, other_maker_ata, other_vault)], Some(&cranker.pubkey()));
+        let transaction = Transaction::new(&[&cranker], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "InvalidMint");
+
+        assert_eq!(token_amount(&program, &vaults[0]), 100);
+        assert_eq!(token_amount(&program, &vaults[1]), 200);
+
+        // Refunding the escrow back into existence within the same transaction
+        // leaves a closed discriminator that no longer deserializes
+        let revive = solana_system_interface::instruction::transfer(&cranker.pubkey(), &escrow, LAMPORTS_PER_SOL / 10);
+        let message = Message::new(
+            &[auto_refund(mint_a, maker_ata_a, vaults[0]), revive, auto_refund(mint_a, maker_ata_a, vaults[0])],
+            Some(&cranker.pubkey()),
+        );
+        let transaction = Transaction::new(&[&cranker], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "AccountDiscriminatorMismatch");
+
+        // The honest auto-refund still goes through
+        let message = Message::new(&[auto_refund(mint_a, maker_ata_a, vaults[0])], Some(&cranker.pubkey()));
+        let transaction = Transaction::new(&[&cranker], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        assert_eq!(token_amount(&program, &maker_ata_a), 1_000_000 - 200);
+        for account in [vaults[0], escrow] {
+            let after = program.get_account(&account);
+            assert!(after.is_none() || after.unwrap().lamports == 0);
+        }
+    }
+
+    // Makes one auction escrow per `(seed, time)` sample, warps to each time in turn and
+    // takes the escrow at its default (start price) limit, returning what the maker was paid
+    fn take_auction_samples(auction: client::state::DutchAuction, samples: &[(u64, i64)]) -> Vec<u64> {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let (mint_a, _) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, _) = funded_mint(&mut program, &taker_kp, &taker);
+        let maker_ata_b = associated_token::get_associated_token_address(&maker, &mint_b);
+
+        for (seed, _) in samples {
+            let make = MakeBuilder::new(maker, *seed)
+                .offer(mint_a, 100)
+                .request(mint_b, 0)
+                .unlock_at(auction.start_at - 60)
+                .auction(auction);
+            let message = Message::new(&[make.instruction()], Some(&maker));
+            let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+            program.send_transaction(transaction).unwrap();
+
+            let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, *seed).unwrap();
+            assert_eq!(escrow_data.receive, auction.start_price);
+            assert_eq!(escrow_data.auction, Some(auction));
+        }
+
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        samples.iter().map(|(seed, now)| {
+            let mut clock: Clock = program.get_sysvar();
+            clock.unix_timestamp = *now;
+            program.set_sysvar(&clock);
+            program.expire_blockhash();
+
+            let before = program.get_account(&maker_ata_b).map_or(0, |_| token_amount(&program, &maker_ata_b));
+            let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, *seed).unwrap();
+            let take_ix = TakeBuilder::new(taker, &escrow_data, &config).instruction();
+
+            let message = Message::new(&[take_ix], Some(&taker));
+            let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+            program.send_transaction(transaction).unwrap();
+
+            // The whole deposit goes to the taker, whatever the price
+            assert_eq!(token_amount(&program, &associated_token::get_associated_token_address(&taker, &mint_a)), 100 * (seed - samples[0].0 + 1));
+            let escrow_after = program.get_account(&pda::escrow(&maker, *seed));
+            assert!(escrow_after.is_none() || escrow_after.unwrap().lamports == 0);
+
+            token_amount(&program, &maker_ata_b) - before
+        }).collect()
+    }
+
+    #[test]
+    fn test_linear_auction_price_decay() {
+        let start_at = 1_000_000;
+        let auction = client::state::DutchAuction {
+            start_price: 1_000,
+            floor_price: 400,
+            start_at,
+            end_at: start_at + 600,
+            steps: 0,
+        };
+
+        // Before the start, a quarter and half way through, one second in (rounded up), and past the end
+        let paid = take_auction_samples(auction, &[
+            (45, start_at - 10),
+            (46, start_at + 1),
+            (47, start_at + 150),
+            (48, start_at + 300),
+            (49, start_at + 10_000),
+        ]);
+        assert_eq!(paid, vec![1_000, 999, 850, 700, 400]);
+    }
+
+    #[test]
+    fn test_stepwise_auction_price_decay() {
+        let start_at = 1_000_000;
+        let auction = client::state::DutchAuction {
+            start_price: 1_000,
+            floor_price: 400,
+            start_at,
+            end_at: start_at + 600,
+            steps: 3,
+        };
+
+        // The price holds within a step and drops by 200 at each boundary
+        let paid = take_auction_samples(auction, &[
+            (50, start_at + 199),
+            (51, start_at + 200),
+            (52, start_at + 399),
+            (53, start_at + 400),
+            (54, start_at + 600),
+        ]);
+        assert_eq!(paid, vec![1_000, 800, 800, 600, 400]);
+    }
+
+    #[test]
+    fn test_auction_rejects_limit_below_price() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let (mint_a, _) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, taker_ata_b) = funded_mint(&mut program, &taker_kp, &taker);
+
+        let clock: Clock = program.get_sysvar();
+        let auction = client::state::DutchAuction {
+            start_price: 1_000,
+            floor_price: 400,
+            start_at: clock.unix_timestamp,
+            end_at: clock.unix_timestamp + 600,
+            steps: 0,
+        };
+        let make = |seed: u64, auction| MakeBuilder::new(maker, seed)
+            .offer(mint_a, 100)
+            .request(mint_b, 0)
+            .unlock_at(clock.unix_timestamp)
+            .auction(auction);
+
+        // A floor above the start price is not a Dutch auction
+        let inverted = client::state::DutchAuction { floor_price: 2_000, ..auction };
+        let message = Message::new(&[make(55, inverted).instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "InvalidAuction");
+
+        let message = Message::new(&[make(55, auction).instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        // Half way through the price is 700, so a 699 limit is refused and 700 fills
+        let mut clock: Clock = program.get_sysvar();
+        clock.unix_timestamp = auction.start_at + 300;
+        program.set_sysvar(&clock);
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 55).unwrap();
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take = |amount| TakeBuilder::new(taker, &escrow_data, &config).amount(amount).instruction();
+
+        let message = Message::new(&[take(699)], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "AuctionPriceNotMet");
+
+        let message = Message::new(&[take(700)], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+        assert_eq!(token_amount(&program, &taker_ata_b), 1_000_000 - 700);
+    }
+
+    #[test]
+    fn test_accept_counter_offer() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let treasury = Pubkey::new_unique();
+        update_config(&mut program, &payer, Some(250), Some(treasury), None).unwrap();
+
+        let (mint_a, _) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, taker_ata_b) = funded_mint(&mut program, &taker_kp, &taker);
+
+        let seed: u64 = 56;
+        let clock: Clock = program.get_sysvar();
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 100)
+            .request(mint_b, 50)
+            .unlock_at(clock.unix_timestamp);
+        let escrow = make.escrow();
+        let vault = make.vault().unwrap();
+
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        // The taker bids 40 instead of the asked 50
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let counter = MakeCounterOfferBuilder::new(taker, &escrow_data, 40);
+        let counter_offer = counter.counter_offer();
+        let counter_vault = counter.counter_vault().unwrap();
+
+        let message = Message::new(&[counter.instruction()], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        let tx = program.send_transaction(transaction).unwrap();
+
+        let made: crate::CounterOfferMade = find_event(&tx.logs).expect("CounterOfferMade event");
+        assert_eq!((made.escrow, made.counter_offer, made.amount), (escrow, counter_offer, 40));
+        assert_eq!(token_amount(&program, &counter_vault), 40);
+        assert_eq!(token_amount(&program, &taker_ata_b), 1_000_000 - 40);
+
+        let counter_data = client::fetch_counter_offer(&fetcher(&program), &escrow, &taker).unwrap();
+        assert_eq!(counter_data.maker, maker);
+        assert_eq!(counter_data.deposited, 100);
+
+        // Accepting settles both legs at the bid, with the 2.5% fee on each
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let accept_ix = AcceptCounterOfferBuilder::new(&escrow_data, &counter_data, &config).instruction();
+
+        let message = Message::new(&[accept_ix], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        let tx = program.send_transaction(transaction).unwrap();
+
+        let accepted: crate::CounterOfferAccepted = find_event(&tx.logs).expect("CounterOfferAccepted event");
+        assert_eq!((accepted.amount, accepted.payout), (40, 100));
+
+        let ata = |owner: &Pubkey, mint: &Pubkey| associated_token::get_associated_token_address(owner, mint);
+        assert_eq!(token_amount(&program, &ata(&maker, &mint_b)), 39);
+        assert_eq!(token_amount(&program, &ata(&treasury, &mint_b)), 1);
+        assert_eq!(token_amount(&program, &ata(&taker, &mint_a)), 98);
+        assert_eq!(token_amount(&program, &ata(&treasury, &mint_a)), 2);
+
+        for account in [vault, escrow, counter_vault, counter_offer] {
+            let after = program.get_account(&account);
+            assert!(after.is_none() || after.unwrap().lamports == 0);
+        }
+    }
+
+    #[test]
+    fn test_reject_and_stale_counter_offers() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let (mint_a, _) = funded_mint(&mut program, &payer, &maker);
+        let mint_b = CreateMint::new(&mut program, &payer)
+            .decimals(6)
+            .authority(&maker)
+            .send()
+            .unwrap();
+
+        // Two takers, each holding some mint_b
+        let takers: Vec<Keypair> = (0..2).map(|_| Keypair::new()).collect();
+        for taker in &takers {
+            program.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+            let taker_ata_b = CreateAssociatedTokenAccount::new(&mut program, &payer, &mint_b)
+                .owner(&taker.pubkey()).send().unwrap();
+            MintTo::new(&mut program, &payer, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+        }
+
+        let seed: u64 = 57;
+        let clock: Clock = program.get_sysvar();
+        let make = MakeBuilder::new(maker, seed)
+            .offer(mint_a, 100)
+            .request(mint_b, 50)
+            .unlock_at(clock.unix_timestamp);
+        let escrow = make.escrow();
+        let vault = make.vault().unwrap();
+
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        for (taker, amount) in takers.iter().zip([30, 45]) {
+            let counter_ix = MakeCounterOfferBuilder::new(taker.pubkey(), &escrow_data, amount).instruction();
+            let message = Message::new(&[counter_ix], Some(&taker.pubkey()));
+            let transaction = Transaction::new(&[taker], message, program.latest_blockhash());
+            program.send_transaction(transaction).unwrap();
+        }
+
+        let counter_offer = |program: &LiteSVM, taker: &Keypair| {
+            client::fetch_counter_offer(&fetcher(program), &escrow, &taker.pubkey()).unwrap()
+        };
+        let taker_ata_b = |taker: &Keypair| associated_token::get_associated_token_address(&taker.pubkey(), &mint_b);
+
+        // A stranger cannot unwind someone else's counter-offer
+        let stranger = Keypair::new();
+        program.airdrop(&stranger.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let reject_ix = RejectCounterOfferBuilder::new(stranger.pubkey(), &counter_offer(&program, &takers[0])).instruction();
+        let message = Message::new(&[reject_ix], Some(&stranger.pubkey()));
+        let transaction = Transaction::new(&[&stranger], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "Unauthorized");
+
+        // The maker rejects the first bid, refunding the taker
+        let reject_ix = RejectCounterOfferBuilder::new(maker, &counter_offer(&program, &takers[0])).instruction();
+        let message = Message::new(&[reject_ix], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        let tx = program.send_transaction(transaction).unwrap();
+
+        let rejected: crate::CounterOfferRejected = find_event(&tx.logs).expect("CounterOfferRejected event");
+        assert_eq!((rejected.taker, rejected.amount), (takers[0].pubkey(), 30));
+        assert_eq!(token_amount(&program, &taker_ata_b(&takers[0])), 1_000);
+        assert!(client::fetch_counter_offer(&fetcher(&program), &escrow, &takers[0].pubkey()).is_none());
+
+        // Withdrawing part of the deposit makes the second bid stale
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let withdraw_ix = UpdateEscrowBuilder::new(&escrow_data).withdraw(40).instruction();
+        let message = Message::new(&[withdraw_ix], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let accept_ix = AcceptCounterOfferBuilder::new(&escrow_data, &counter_offer(&program, &takers[1]), &config).instruction();
+        let message = Message::new(&[accept_ix], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "StaleCounterOffer");
+
+        // The second taker withdraws their bid instead
+        let withdraw_ix = RejectCounterOfferBuilder::new(takers[1].pubkey(), &counter_offer(&program, &takers[1])).instruction();
+        let message = Message::new(&[withdraw_ix], Some(&takers[1].pubkey()));
+        let transaction = Transaction::new(&[&takers[1]], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        assert_eq!(token_amount(&program, &taker_ata_b(&takers[1])), 1_000);
+        assert_eq!(token_amount(&program, &vault), 60);
+    }
+
+    #[test]
+    fn test_pair_and_maker_indexes() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let (mint_a, _) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, _) = funded_mint(&mut program, &taker_kp, &taker);
+
+        // Three escrows on the same pair; the first make creates both indexes
+        let clock: Clock = program.get_sysvar();
+        let escrows: Vec<Pubkey> = (58..61u64)
+            .map(|seed| {
+                let make = MakeBuilder::new(maker, seed)
+                    .offer(mint_a, 100)
+                    .request(mint_b, 50)
+                    .unlock_at(clock.unix_timestamp);
+                let message = Message::new(&[make.instruction()], Some(&maker));
+                let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+                program.send_transaction(transaction).unwrap();
+                make.escrow()
+            })
+            .collect();
+
+        let pair_index = client::fetch_pair_index(&fetcher(&program), &mint_a, &mint_b);
+        assert_eq!(pair_index, escrows);
+        assert_eq!(client::fetch_maker_index(&fetcher(&program), &maker), escrows);
+        assert!(client::fetch_pair_index(&fetcher(&program), &mint_b, &mint_a).is_empty());
+
+        // All three fit on the first page of the pair index; the next does not exist yet
+        let page = |page: u32| -> Vec<Pubkey> {
+            client::fetch_escrow_page(&fetcher(&program), &pda::pair_index(&mint_a, &mint_b, page))
+                .into_iter()
+                .map(|(address, escrow)| {
+                    assert_eq!((escrow.maker, escrow.mint_a, escrow.mint_b), (maker, mint_a, mint_b));
+                    address
+                })
+                .collect()
+        };
+        assert_eq!(page(0), escrows);
+        assert!(page(1).is_empty());
+        assert_eq!(
+            client::fetch_escrow(&fetcher(&program), &maker, 58).unwrap().index_pages,
+            client::state::IndexPages::default()
+        );
+
+        // Taking the first and refunding the second leaves only the third listed
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 58).unwrap();
+        let take_ix = TakeBuilder::new(taker, &escrow_data, &config).instruction();
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 59).unwrap();
+        let refund_ix = RefundBuilder::new(&escrow_data).instruction();
+        let message = Message::new(&[refund_ix], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        assert_eq!(client::fetch_pair_index(&fetcher(&program), &mint_a, &mint_b), escrows[2..]);
+        assert_eq!(client::fetch_maker_index(&fetcher(&program), &maker), escrows[2..]);
+
+        // Both indexes shrank back to a single entry and hold only its rent
+        for index in [pda::pair_index(&mint_a, &mint_b, 0), pda::maker_index(&maker, 0)] {
+            let account = program.get_account(&index).unwrap();
+            let space = client::state::EscrowIndex::space(1);
+            assert_eq!(account.data.len(), space);
+            assert_eq!(account.lamports, program.minimum_balance_for_rent_exemption(space));
+        }
+    }
+
+    #[test]
+    fn test_take_requires_the_escrow_index_pages() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let (mint_a, _) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, _) = funded_mint(&mut program, &taker_kp, &taker);
+
+        let clock: Clock = program.get_sysvar();
+        let make = MakeBuilder::new(maker, 63)
+            .offer(mint_a, 100)
+            .request(mint_b, 50)
+            .unlock_at(clock.unix_timestamp);
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 63).unwrap();
+        let pair_index = pda::pair_index(&mint_a, &mint_b, 0);
+        let maker_index = pda::maker_index(&maker, 0);
+        let blockhash = program.latest_blockhash();
+        let take = |pair: Pubkey, maker_page: Pubkey| {
+            let mut take_ix = TakeBuilder::new(taker, &escrow_data, &config).instruction();
+            for meta in take_ix.accounts.iter_mut() {
+                if meta.pubkey == pair_index {
+                    meta.pubkey = pair;
+                } else if meta.pubkey == maker_index {
+                    meta.pubkey = maker_page;
+                }
+            }
+            let message = Message::new(&[take_ix], Some(&taker));
+            Transaction::new(&[&taker_kp], message, blockhash)
+        };
+
+        // Leaving the indexes out, as optional accounts are, would keep the escrow listed
+        let result = program.send_transaction(take(PROGRAM_ID, PROGRAM_ID));
+        assert_anchor_error(result, "AccountOwnedByWrongProgram");
+
+        // Index pages other than the ones the escrow was listed on are rejected too
+        let result = program.send_transaction(take(maker_index, pair_index));
+        assert_anchor_error(result, "ConstraintSeeds");
+
+        program.send_transaction(take(pair_index, maker_index)).unwrap();
+        assert!(client::fetch_pair_index(&fetcher(&program), &mint_a, &mint_b).is_empty());
+        assert!(client::fetch_maker_index(&fetcher(&program), &maker).is_empty());
+    }
+
+    #[test]
+    fn test_full_index_page_moves_to_the_next() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let (mint_a, _) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, _) = funded_mint(&mut program, &payer, &maker);
+        let clock: Clock = program.get_sysvar();
+        let make = |seed: u64| {
+            MakeBuilder::new(maker, seed)
+                .offer(mint_a, 100)
+                .request(mint_b, 50)
+                .unlock_at(clock.unix_timestamp)
+        };
+
+        // Fill the first page of both indexes
+        for seed in 0..client::state::ESCROWS_PER_PAGE as u64 {
+            let message = Message::new(&[make(seed).instruction()], Some(&maker));
+            let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+            program.send_transaction(transaction).unwrap();
+        }
+        let seed = client::state::ESCROWS_PER_PAGE as u64;
+
+        let message = Message::new(&[make(seed).instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "IndexPageFull");
+
+        // The client points the next escrow at the second pages
+        let pages = client::open_index_pages(&fetcher(&program), &maker, &mint_a, &mint_b);
+        assert_eq!(pages, client::state::IndexPages { pair: 1, maker: 1 });
+
+        let message = Message::new(&[make(seed).index_pages(pages).instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let escrow = make(seed).escrow();
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+        assert_eq!(escrow_data.index_pages, pages);
+        let second_page = client::fetch_escrow_page(&fetcher(&program), &pda::pair_index(&mint_a, &mint_b, 1));
+        assert_eq!(second_page.iter().map(|(address, _)| *address).collect::<Vec<_>>(), [escrow]);
+        assert_eq!(client::fetch_pair_index(&fetcher(&program), &mint_a, &mint_b).len(), seed as usize + 1);
+        assert_eq!(client::fetch_maker_index(&fetcher(&program), &maker).last(), Some(&escrow));
+
+        // Refunding it clears its entry from the second pages
+        let refund_ix = RefundBuilder::new(&escrow_data).instruction();
+        let message = Message::new(&[refund_ix], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        assert!(client::fetch_escrow_page(&fetcher(&program), &pda::pair_index(&mint_a, &mint_b, 1)).is_empty());
+        assert!(client::fetch_escrow_page(&fetcher(&program), &pda::maker_index(&maker, 1)).is_empty());
+
+        // Refunding one from the first pages frees a slot there again
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 0).unwrap();
+        let refund_ix = RefundBuilder::new(&escrow_data).instruction();
+        let message = Message::new(&[refund_ix], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        assert_eq!(
+            client::open_index_pages(&fetcher(&program), &maker, &mint_a, &mint_b),
+            client::state::IndexPages::default()
+        );
+    }
+
+    #[test]
+    fn test_pause_blocks_make_but_not_refund() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let (mint_a, maker_ata_a) = funded_mint(&mut program, &payer, &maker);
+        let mint_b = CreateMint::new(&mut program, &payer)
+            .decimals(6)
+            .authority(&maker)
+            .send()
+            .unwrap();
+        let clock: Clock = program.get_sysvar();
+        let make = |seed: u64| {
+            MakeBuilder::new(maker, seed)
+                .offer(mint_a, 100)
+                .request(mint_b, 50)
+                .unlock_at(clock.unix_timestamp)
+                .instruction()
+        };
+
+        let message = Message::new(&[make(61)], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        update_config(&mut program, &payer, None, None, Some(true)).unwrap();
+
+        let message = Message::new(&[make(62)], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "ProtocolPaused");
+
+        // Makers can always get their deposits back
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 61).unwrap();
+        let message = Message::new(&[RefundBuilder::new(&escrow_data).instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+        assert_eq!(token_amount(&program, &maker_ata_a), 1_000_000);
+
+        update_config(&mut program, &payer, None, None, Some(false)).unwrap();
+        program.expire_blockhash();
+
+        let message = Message::new(&[make(62)], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+    }
+
+    #[test]
+    fn test_two_step_admin_transfer() {
+        let (mut program, payer) = setup();
+
+        let new_admin = Keypair::new();
+        let stranger = Keypair::new();
+        for signer in [&new_admin, &stranger] {
+            program.airdrop(&signer.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        }
+
+        let send = |program: &mut LiteSVM, ix: Instruction, signer: &Keypair| {
+            let message = Message::new(&[ix], Some(&signer.pubkey()));
+            let transaction = Transaction::new(&[signer], message, program.latest_blockhash());
+            program.send_transaction(transaction)
+        };
+
+        // Only the admin can propose a successor
+        let result = send(&mut program, client::transfer_admin(stranger.pubkey(), Some(stranger.pubkey())), &stranger);
+        assert_anchor_error(result, "Unauthorized");
+        send(&mut program, client::transfer_admin(payer.pubkey(), Some(new_admin.pubkey())), &payer).unwrap();
+
+        // The proposal alone changes nothing: the old admin is still in charge
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        assert_eq!((config.admin, config.pending_admin), (payer.pubkey(), Some(new_admin.pubkey())));
+        update_config(&mut program, &payer, Some(100), None, None).unwrap();
+
+        // and only the proposed admin can accept
+        let result = send(&mut program, client::accept_admin(stranger.pubkey()), &stranger);
+        assert_anchor_error(result, "NotPendingAdmin");
+        send(&mut program, client::accept_admin(new_admin.pubkey()), &new_admin).unwrap();
+
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        assert_eq!((config.admin, config.pending_admin), (new_admin.pubkey(), None));
+
+        assert_anchor_error(update_config(&mut program, &payer, None, None, Some(true)), "Unauthorized");
+        update_config(&mut program, &new_admin, None, None, Some(true)).unwrap();
+        assert!(client::fetch_config(&fetcher(&program)).unwrap().paused);
+
+        // A withdrawn proposal cannot be accepted
+        send(&mut program, client::transfer_admin(new_admin.pubkey(), Some(stranger.pubkey())), &new_admin).unwrap();
+        send(&mut program, client::transfer_admin(new_admin.pubkey(), None), &new_admin).unwrap();
+        program.expire_blockhash();
+        let result = send(&mut program, client::accept_admin(stranger.pubkey()), &stranger);
+        assert_anchor_error(result, "NotPendingAdmin");
+    }
+
+    // Compute unit and rent baselines checked by test_compute_and_rent_baselines,
+    // relative to the crate root; re-record them with scripts/update-baselines.sh
+    const BASELINES: &str = "baselines.json";
+    // Compute units may grow this many percent over their baseline; rent may not grow at all
+    const CU_TOLERANCE_PCT: u64 = 5;
+
+    // Lists every `kind` measurement above its baseline plus `tolerance_pct`, and every
+    // measurement without a baseline, so a new instruction fails until it is recorded
+    fn check_baselines(
+        baselines: &serde_json::Value,
+        kind: &str,
+        measured: &BTreeMap<&str, u64>,
+        tolerance_pct: u64,
+    ) -> Vec<String> {
+        measured
+            .iter()
+            .filter_map(|(name, &value)| {
+                let Some(baseline) = baselines[kind][*name].as_u64() else {
+                    return Some(format!("{} {}: {} has no baseline", name, kind, value));
+                };
+                let allowed = baseline + baseline * tolerance_pct / 100;
+                (value > allowed).then(|| format!("{} {}: {} over baseline {} (+{}%)", name, kind, value, baseline, tolerance_pct))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compute_and_rent_baselines() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let (mint_a, _) = funded_mint(&mut program, &payer, &maker);
+        let (mint_b, _) = funded_mint(&mut program, &taker_kp, &taker);
+        let task_queue = Pubkey::new_unique();
+        let clock: Clock = program.get_sysvar();
+        let now = clock.unix_timestamp;
+
+        let send = |program: &mut LiteSVM, ix: Instruction, signer: &Keypair| -> u64 {
+            let message = Message::new(&[ix], Some(&signer.pubkey()));
+            let transaction = Transaction::new(&[signer], message, program.latest_blockhash());
+            program.send_transaction(transaction).unwrap().compute_units_consumed
+        };
+        let lamports = |program: &LiteSVM, account: &Pubkey| program.get_account(account).unwrap().lamports;
+        let make = |seed: u64| MakeBuilder::new(maker, seed).offer(mint_a, 100).request(mint_b, 50).unlock_at(now);
+
+        let mut compute_units = BTreeMap::new();
+        let mut rent = BTreeMap::new();
+
+        // A non-zero fee so take and accept_counter_offer pay into treasuries
+        compute_units.insert("update_config", send(&mut program, client::update_config(maker, Some(100), Some(maker), None), &payer));
+        let ix = client::update_scheduling(maker, None, None, Some(vec![task_queue]));
+        compute_units.insert("update_scheduling", send(&mut program, ix, &payer));
+        rent.insert("config", lamports(&program, &pda::config()));
+
+        // Escrow 1 creates both indexes, is countered, and is taken once the counter-offer is rejected
+        let first = make(1);
+        compute_units.insert("make", send(&mut program, first.instruction(), &payer));
+        rent.insert("escrow", lamports(&program, &first.escrow()));
+        rent.insert("vault", lamports(&program, &first.vault().unwrap()));
+        rent.insert("escrow_index", lamports(&program, &pda::pair_index(&mint_a, &mint_b, 0)));
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 1).unwrap();
+        let counter = MakeCounterOfferBuilder::new(taker, &escrow_data, 40);
+        compute_units.insert("make_counter_offer", send(&mut program, counter.instruction(), &taker_kp));
+        rent.insert("counter_offer", lamports(&program, &counter.counter_offer()));
+        rent.insert("counter_vault", lamports(&program, &counter.counter_vault().unwrap()));
+
+        let counter_data = client::fetch_counter_offer(&fetcher(&program), &first.escrow(), &taker).unwrap();
+        let ix = RejectCounterOfferBuilder::new(maker, &counter_data).instruction();
+        compute_units.insert("reject_counter_offer", send(&mut program, ix, &payer));
+
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let ix = TakeBuilder::new(taker, &escrow_data, &config).instruction();
+        compute_units.insert("take", send(&mut program, ix, &taker_kp));
+
+        // Escrow 2 reuses the indexes, schedules its auto-refund and is refunded by the maker
+        compute_units.insert("make_existing_indexes", send(&mut program, make(2).instruction(), &payer));
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 2).unwrap();
+        let ix = ScheduleRefundBuilder::new(&escrow_data, task_queue, 0, now + 60).instruction();
+        compute_units.insert("schedule_refund", send(&mut program, ix, &payer));
+
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 2).unwrap();
+        let ix = RefundBuilder::new(&escrow_data).instruction();
+        compute_units.insert("refund", send(&mut program, ix, &payer));
+
+        // Escrow 3 queues its auto-refund on creation; once it expires the refund is sent
+        // directly rather than cranked
+        let ix = make(3).expires_at(now + 60).auto_refund(task_queue, 1, 0).instruction();
+        compute_units.insert("make_with_expiry", send(&mut program, ix, &payer));
+        let mut clock: Clock = program.get_sysvar();
+        clock.unix_timestamp = now + 60;
+        program.set_sysvar(&clock);
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 3).unwrap();
+        let ix = AutoRefundBuilder::new(&escrow_data).instruction();
+        compute_units.insert("auto_refund", send(&mut program, ix, &payer));
+
+        // Escrow 4 settles at a counter-offer
+        send(&mut program, make(4).instruction(), &payer);
+        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, 4).unwrap();
+        send(&mut program, MakeCounterOfferBuilder::new(taker, &escrow_data, 40).instruction(), &taker_kp);
+        let counter_data = client::fetch_counter_offer(&fetcher(&program), &pda::escrow(&maker, 4), &taker).unwrap();
+        let ix = AcceptCounterOfferBuilder::new(&escrow_data, &counter_data, &config).instruction();
+        compute_units.insert("accept_counter_offer", send(&mut program, ix, &payer));
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(BASELINES);
+        if std::env::var_os("UPDATE_BASELINES").is_some() {
+            let baselines = serde_json::json!({ "compute_units": compute_units, "rent": rent });
+            std::fs::write(&path, serde_json::to_string_pretty(&baselines).unwrap() + "\n").unwrap();
+            return;
+        }
+
+        let contents = std::fs::read_to_string(&path)
+            .unwrap_or_else(|_| panic!("Missing {}, record it with scripts/update-baselines.sh", path.display()));
+        let baselines: serde_json::Value = serde_json::from_str(&contents).expect("Invalid baselines JSON");
+
+        let mut regressions = check_baselines(&baselines, "compute_units", &compute_units, CU_TOLERANCE_PCT);
+        regressions.extend(check_baselines(&baselines, "rent", &rent, 0));
+        assert!(
+            regressions.is_empty(),
+            "regressions or missing entries in {}; re-record with scripts/update-baselines.sh if intended:\n{}",
+            BASELINES,
+            regressions.join("\n")
+        );
+    }
+
+    // xorshift64* stream driving test_randomized_escrow_invariants, so any run can be
+    // replayed from its seed
+    struct Rng(u64);
+
+    impl Rng {
+        fn new(seed: u64) -> Self {
+            Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next_u64() % n
+        }
+
+        fn index(&mut self, len: usize) -> usize {
+            self.below(len as u64) as usize
+        }
+    }
+
+    // An escrow a randomized run expects to be open, with the amounts it should hold
+    struct ModelEscrow {
+        maker: usize,
+        seed: u64,
+        mint_a: Pubkey,
+        receive: u64,
+        deposited: u64,
+        unlock_at: i64,
+        expires_at: Option<i64>,
+    }
+
+    // Amount of a token account, or 0 once it has been closed
+    fn token_balance(program: &LiteSVM, account: &Pubkey) -> u64 {
+        program
+            .get_account(account)
+            .filter(|account| !account.data.is_empty())
+            .map_or(0, |_| token_amount(program, account))
+    }
+
+    // Interleaves `steps` random makes, takes, refunds, auto-refunds and clock warps across
+    // three makers, two takers and three mints, checking the invariants after every step
+    fn run_escrow_invariants(run: u64, steps: usize) {
+        const SUPPLY_PER_HOLDER: u64 = 1_000_000;
+
+        let (mut program, payer) = setup();
+        let mut rng = Rng::new(run);
+
+        let treasury = Pubkey::new_unique();
+        update_config(&mut program, &payer, Some(100), Some(treasury), None).unwrap();
+
+        let makers: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let takers: Vec<Keypair> = (0..2).map(|_| Keypair::new()).collect();
+        let mints: Vec<Pubkey> = (0..3)
+            .map(|_| CreateMint::new(&mut program, &payer).decimals(6).authority(&payer.pubkey()).send().unwrap())
+            .collect();
+        let holders: Vec<Pubkey> = makers.iter().chain(&takers).map(|owner| owner.pubkey()).chain([treasury]).collect();
+        let supply = SUPPLY_PER_HOLDER * (makers.len() + takers.len()) as u64;
+
+        // Every account lamports can move between; the payer only pays transaction fees
+        let mut tracked: BTreeSet<Pubkey> = BTreeSet::from([pda::config(), treasury]);
+        for owner in makers.iter().chain(&takers) {
+            program.airdrop(&owner.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+            tracked.insert(owner.pubkey());
+            for mint in &mints {
+                let ata = CreateAssociatedTokenAccount::new(&mut program, &payer, mint)
+                    .owner(&owner.pubkey()).send().unwrap();
+                MintTo::new(&mut program, &payer, mint, &ata, SUPPLY_PER_HOLDER).send().unwrap();
+            }
+            tracked.insert(pda::maker_index(&owner.pubkey(), 0));
+        }
+        for mint in &mints {
+            tracked.extend(holders.iter().map(|owner| pda::ata(owner, mint, &TOKEN_PROGRAM_ID)));
+            tracked.extend(mints.iter().map(|other| pda::pair_index(mint, other, 0)));
+        }
+
+        let lamports = |program: &LiteSVM, tracked: &BTreeSet<Pubkey>| -> u64 {
+            tracked.iter().filter_map(|account| program.get_account(account)).map(|account| account.lamports).sum()
+        };
+        let send = |program: &mut LiteSVM, ix: Instruction, signer: Option<&Keypair>| {
+            program.expire_blockhash();
+            let message = Message::new(&[ix], Some(&payer.pubkey()));
+            let transaction = match signer {
+                Some(signer) => Transaction::new(&[&payer, signer], message, program.latest_blockhash()),
+                None => Transaction::new(&[&payer], message, program.latest_blockhash()),
+            };
+            program.send_transaction(transaction)
+        };
+        let total_lamports = lamports(&program, &tracked);
+
+        let mut open: Vec<ModelEscrow> = vec![];
+        // Escrow, vault and a replayable auto_refund of every closed escrow
+        let mut closed: Vec<(Pubkey, Pubkey, Instruction)> = vec![];
+        // Mint and vault of every escrow ever made
+        let mut vaults: Vec<(Pubkey, Pubkey)> = vec![];
+        let mut next_seed = 0;
+
+        for step in 0..steps {
+            let context = format!("run {} step {}", run, step);
+            let now = program.get_sysvar::<Clock>().unix_timestamp;
+
+            match rng.below(100) {
+                0..=34 => {
+                    let maker = rng.index(makers.len());
+                    let a = rng.index(mints.len());
+                    let b = (a + 1 + rng.index(mints.len() - 1)) % mints.len();
+                    let (deposit, receive) = (1 + rng.below(1_000), 1 + rng.below(1_000));
+                    let unlock_at = now + rng.below(3) as i64 * 3_600;
+                    let expires_at = (rng.below(2) == 0).then(|| unlock_at + (1 + rng.below(3)) as i64 * 3_600);
+                    next_seed += 1;
+
+                    // Later pages once a maker or pair fills its first
+                    let pages = client::open_index_pages(&fetcher(&program), &makers[maker].pubkey(), &mints[a], &mints[b]);
+                    tracked.extend([
+                        pda::pair_index(&mints[a], &mints[b], pages.pair),
+                        pda::maker_index(&makers[maker].pubkey(), pages.maker),
+                    ]);
+                    let mut make = MakeBuilder::new(makers[maker].pubkey(), next_seed)
+                        .offer(mints[a], deposit)
+                        .request(mints[b], receive)
+                        .unlock_at(unlock_at)
+                        .index_pages(pages);
+                    if let Some(expires_at) = expires_at {
+                        make = make.expires_at(expires_at);
+                    }
+                    let vault = make.vault().unwrap();
+                    tracked.extend([make.escrow(), vault]);
+                    vaults.push((mints[a], vault));
+
+                    send(&mut program, make.instruction(), Some(&makers[maker]))
+                        .unwrap_or_else(|failed| panic!("{}: make failed: {:?}", context, failed.err));
+                    open.push(ModelEscrow {
+                        maker,
+                        seed: next_seed,
+                        mint_a: mints[a],
+                        receive,
+                        deposited: deposit,
+                        unlock_at,
+                        expires_at,
+                    });
+                }
+                35..=64 if !open.is_empty() => {
+                    let index = rng.index(open.len());
+                    let taker = &takers[rng.index(takers.len())];
+                    let (maker, seed, receive, deposited, unlock_at, expires_at) = {
+                        let entry = &open[index];
+                        (makers[entry.maker].pubkey(), entry.seed, entry.receive, entry.deposited, entry.unlock_at, entry.expires_at)
+                    };
+                    let amount = if rng.below(2) == 0 { receive } else { 1 + rng.below(receive) };
+                    let payout = if amount == receive { deposited } else { deposited * amount / receive };
+
+                    let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, seed).unwrap();
+                    let config = client::fetch_config(&fetcher(&program)).unwrap();
+                    let ix = TakeBuilder::new(taker.pubkey(), &escrow_data, &config).amount(amount).instruction();
+                    let result = send(&mut program, ix, Some(taker));
+
+                    if now < unlock_at {
+                        assert_anchor_error(result, "TimeLockNotExpired");
+                    } else if expires_at.is_some_and(|expires_at| now >= expires_at) {
+                        assert_anchor_error(result, "EscrowExpired");
+                    } else if payout == 0 {
+                        assert_anchor_error(result, "FillTooSmall");
+                    } else {
+                        result.unwrap_or_else(|failed| panic!("{}: take failed: {:?}", context, failed.err));
+                        if amount == receive {
+                            let entry = open.swap_remove(index);
+                            let vault = pda::ata(&pda::escrow(&maker, seed), &entry.mint_a, &TOKEN_PROGRAM_ID);
+                            closed.push((pda::escrow(&maker, seed), vault, AutoRefundBuilder::new(&escrow_data).instruction()));
+                        } else {
+                            open[index].receive -= amount;
+                            open[index].deposited -= payout;
+                        }
+                    }
+                }
+                65..=74 if !open.is_empty() => {
+                    let entry = open.swap_remove(rng.index(open.len()));
+                    let maker = &makers[entry.maker];
+                    let escrow = pda::escrow(&maker.pubkey(), entry.seed);
+                    let escrow_data = client::fetch_escrow(&fetcher(&program), &maker.pubkey(), entry.seed).unwrap();
+
+                    send(&mut program, RefundBuilder::new(&escrow_data).instruction(), Some(maker))
+                        .unwrap_or_else(|failed| panic!("{}: refund failed: {:?}", context, failed.err));
+                    let vault = pda::ata(&escrow, &entry.mint_a, &TOKEN_PROGRAM_ID);
+                    closed.push((escrow, vault, AutoRefundBuilder::new(&escrow_data).instruction()));
+                }
+                75..=84 if !open.is_empty() || !closed.is_empty() => {
+                    // Crank an open escrow, which only succeeds once it has expired, or replay
+                    // the crank of a closed one, which must be a no-op
+                    if open.is_empty() || (!closed.is_empty() && rng.below(3) == 0) {
+                        let (_, _, ix) = &closed[rng.index(closed.len())];
+                        send(&mut program, ix.clone(), None)
+                            .unwrap_or_else(|failed| panic!("{}: replayed auto_refund failed: {:?}", context, failed.err));
+                    } else {
+                        let index = rng.index(open.len());
+                        let maker = makers[open[index].maker].pubkey();
+                        let escrow = pda::escrow(&maker, open[index].seed);
+                        let escrow_data = client::fetch_escrow(&fetcher(&program), &maker, open[index].seed).unwrap();
+                        let ix = AutoRefundBuilder::new(&escrow_data).instruction();
+                        let result = send(&mut program, ix.clone(), None);
+
+                        if open[index].expires_at.is_some_and(|expires_at| now >= expires_at) {
+                            result.unwrap_or_else(|failed| panic!("{}: auto_refund failed: {:?}", context, failed.err));
+                            let entry = open.swap_remove(index);
+                            closed.push((escrow, pda::ata(&escrow, &entry.mint_a, &TOKEN_PROGRAM_ID), ix));
+                        } else {
+                            assert_anchor_error(result, "EscrowNotExpired");
+                        }
+                    }
+                }
+                _ => {
+                    let mut clock: Clock = program.get_sysvar();
+                    clock.unix_timestamp += rng.below(4) as i64 * 1_800;
+                    program.set_sysvar(&clock);
+                }
+            }
+
+            // Every unit of each mint sits in a holder's or the treasury's ATA, or in a vault
+            for mint in &mints {
+                let held: u64 = holders
+                    .iter()
+                    .map(|owner| pda::ata(owner, mint, &TOKEN_PROGRAM_ID))
+                    .chain(vaults.iter().filter(|(vault_mint, _)| vault_mint == mint).map(|(_, vault)| *vault))
+                    .map(|account| token_balance(&program, &account))
+                    .sum();
+                assert_eq!(held, supply, "{}: supply of mint {} not conserved", context, mint);
+            }
+
+            // Lamports only move between tracked accounts
+            assert_eq!(lamports(&program, &tracked), total_lamports, "{}: lamports leaked", context);
+
+            // Open escrows hold what the model expects, and their vaults back them exactly
+            for entry in &open {
+                let maker = makers[entry.maker].pubkey();
+                let escrow = client::fetch_escrow(&fetcher(&program), &maker, entry.seed)
+                    .unwrap_or_else(|| panic!("{}: open escrow {} missing", context, entry.seed));
+                assert_eq!((escrow.receive, escrow.deposited), (entry.receive, entry.deposited), "{}: escrow {}", context, entry.seed);
+
+                let vault = pda::ata(&pda::escrow(&maker, entry.seed), &entry.mint_a, &TOKEN_PROGRAM_ID);
+                assert_eq!(token_balance(&program, &vault), entry.deposited, "{}: vault of escrow {}", context, entry.seed);
+            }
+
+            for (escrow, vault, _) in &closed {
+                for account in [escrow, vault] {
+                    let after = program.get_account(account);
+                    assert!(after.is_none() || after.unwrap().lamports == 0, "{}: closed account {} reopened", context, account);
+                }
+            }
+
+            // Maker indexes list exactly the open escrows
+            for (index, maker) in makers.iter().enumerate() {
+                let mut listed = client::fetch_maker_index(&fetcher(&program), &maker.pubkey());
+                let mut expected: Vec<Pubkey> = open
+                    .iter()
+                    .filter(|entry| entry.maker == index)
+                    .map(|entry| pda::escrow(&maker.pubkey(), entry.seed))
+                    .collect();
+                listed.sort();
+                expected.sort();
+                assert_eq!(listed, expected, "{}: index of maker {}", context, index);
+            }
+        }
+    }
+
+    #[test]
+    fn test_randomized_escrow_invariants() {
+        // ESCROW_FUZZ_SEED replays a single run; otherwise a fixed set keeps the suite deterministic
+        let runs: Vec<u64> = match std::env::var("ESCROW_FUZZ_SEED") {
+            Ok(seed) => vec![seed.parse().expect("ESCROW_FUZZ_SEED must be a u64")],
+            Err(_) => (1..=8).collect(),
+        };
+
+        for run in runs {
+            msg!("Randomized escrow run {}", run);
+            run_escrow_invariants(run, 60);
+        }
+    }
+
+    // Borsh string, as Core's instruction arguments encode them
+    fn borsh_string(data: &mut Vec<u8>, value: &str) {
+        data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        data.extend_from_slice(value.as_bytes());
+    }
+
+    // Creates a Core collection with `authority` as its update authority
+    fn create_core_collection(program: &mut LiteSVM, authority: &Keypair) -> Pubkey {
+        let collection = Keypair::new();
+
+        // CreateCollectionV1 { name, uri, plugins: None }
+        let mut data = vec![1];
+        borsh_string(&mut data, "Escrow Collection");
+        borsh_string(&mut data, "");
+        data.push(0);
+
+        // Core takes its own id in place of an optional account that is not passed
+        let ix = Instruction {
+            program_id: MPL_CORE_ID,
+            accounts: vec![
+                AccountMeta::new(collection.pubkey(), true),
+                AccountMeta::new_readonly(MPL_CORE_ID, false),
+                AccountMeta::new(authority.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+            ],
+            data,
+        };
+
+        let message = Message::new(&[ix], Some(&authority.pubkey()));
+        let transaction = Transaction::new(&[authority, &collection], message, program.latest_blockhash());
+        program.send_transaction(transaction).expect("Failed to create Core collection");
+        collection.pubkey()
+    }
+
+    // Creates a Core asset owned by `owner`, in `collection` if given; `authority` must be
+    // the collection's update authority
+    fn create_core_asset(program: &mut LiteSVM, authority: &Keypair, owner: &Pubkey, collection: Option<Pubkey>) -> Pubkey {
+        let asset = Keypair::new();
+
+        // CreateV1 { data_state: AccountState, name, uri, plugins: None }
+        let mut data = vec![0, 0];
+        borsh_string(&mut data, "Escrow Asset");
+        borsh_string(&mut data, "");
+        data.push(0);
+
+        let ix = Instruction {
+            program_id: MPL_CORE_ID,
+            accounts: vec![
+                AccountMeta::new(asset.pubkey(), true),
+                AccountMeta::new(collection.unwrap_or(MPL_CORE_ID), false),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+                AccountMeta::new(authority.pubkey(), true),
+                AccountMeta::new_readonly(*owner, false),
+                AccountMeta::new_readonly(MPL_CORE_ID, false),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(MPL_CORE_ID, false),
+            ],
+            data,
+        };
+
+        let message = Message::new(&[ix], Some(&authority.pubkey()));
+        let transaction = Transaction::new(&[authority, &asset], message, program.latest_blockhash());
+        program.send_transaction(transaction).expect("Failed to create Core asset");
+        asset.pubkey()
+    }
+
+    // Owner of a Core asset, which follows its one-byte `Key::AssetV1`
+    fn core_owner(program: &LiteSVM, asset: &Pubkey) -> Pubkey {
+        let data = program.get_account(asset).unwrap().data;
+        assert_eq!(data[0], 1, "{} is not a Core asset", asset);
+        Pubkey::try_from(&data[1..33]).unwrap()
+    }
+
+    // Creates a 0-decimal mint with a single token held by `owner`
+    fn spl_nft(program: &mut LiteSVM, authority: &Keypair, owner: &Pubkey) -> (Pubkey, Pubkey) {
+        let mint = CreateMint::new(program, authority)
+            .decimals(0)
+            .authority(&authority.pubkey())
+            .send()
+            .unwrap();
+
+        let ata = CreateAssociatedTokenAccount::new(program, authority, &mint)
+            .owner(owner).send().unwrap();
+
+        MintTo::new(program, authority, &mint, &ata, 1)
+            .send()
+            .unwrap();
+
+        revoke_mint_authority(program, authority, &mint);
+        (mint, ata)
+    }
+
+    // Revokes the mint authority of `mint`, fixing its supply
+    fn revoke_mint_authority(program: &mut LiteSVM, authority: &Keypair, mint: &Pubkey) {
+        let revoke = spl_token::instruction::set_authority(
+            &TOKEN_PROGRAM_ID,
+            mint,
+            None,
+            spl_token::instruction::AuthorityType::MintTokens,
+            &authority.pubkey(),
+            &[],
+        )
+        .unwrap();
+        let message = Message::new(&[revoke], Some(&authority.pubkey()));
+        let transaction = Transaction::new(&[authority], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+    }
+
+    // Appends Core's plugin header and an empty plugin registry to a Core asset or
+    // collection, where Core records the plugins added to it; the mock stores none
+    fn add_core_plugin_registry(program: &mut LiteSVM, address: &Pubkey) {
+        let mut account = program.get_account(address).unwrap();
+        let registry_offset = account.data.len() as u64 + 9;
+        // PluginHeaderV1 { key, plugin_registry_offset }
+        account.data.push(3);
+        account.data.extend_from_slice(&registry_offset.to_le_bytes());
+        // PluginRegistryV1 { key, registry: [], external_registry: [] }
+        account.data.push(4);
+        account.data.extend_from_slice(&[0; 8]);
+        account.lamports = program.minimum_balance_for_rent_exemption(account.data.len());
+        program.set_account(*address, account).unwrap();
+    }
+
+    // Writes a Token Metadata account for `mint` in `collection`, as the real program
+    // lays it out; there is no Token Metadata program in LiteSVM to create one
+    fn set_nft_metadata(program: &mut LiteSVM, mint: &Pubkey, collection: &Pubkey, verified: bool) {
+        // key (MetadataV1), update authority, mint
+        let mut data = vec![4];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(mint.as_ref());
+        for field in ["Escrow NFT", "ENFT", ""] {
+            borsh_string(&mut data, field);
+        }
+        // seller fee, then no creators, not sold, mutable, no edition nonce, NonFungible
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&[0, 0, 1, 0, 1, 0]);
+        data.extend_from_slice(&[1, verified as u8]);
+        data.extend_from_slice(collection.as_ref());
+
+        let account = Account {
+            lamports: program.minimum_balance_for_rent_exemption(data.len()),
+            data,
+            owner: TOKEN_METADATA_ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        program.set_account(pda::nft_metadata(mint), account).unwrap();
+    }
+
+    #[test]
+    fn test_core_asset_offered_take_and_refund() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let treasury = Pubkey::new_unique();
+        update_config(&mut program, &payer, Some(250), Some(treasury), None).unwrap();
+
+        let (mint, taker_ata) = funded_mint(&mut program, &taker_kp, &taker);
+        let asset = create_core_asset(&mut program, &payer, &maker, None);
+
+        // The offered asset moves into the escrow's custody
+        let make = MakeAssetBuilder::new(maker, 63)
+            .price(mint, 400)
+            .offer_asset(AssetKind::Core, asset, None);
+        let escrow = make.escrow();
+
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+        assert_eq!(core_owner(&program, &asset), escrow);
+
+        let escrow_data = client::fetch_asset_escrow(&fetcher(&program), &maker, 63).unwrap();
+        assert_eq!((escrow_data.asset, escrow_data.collection, escrow_data.amount), (Some(asset), None, 400));
+
+        // Taking pays the price, minus the 2.5% fee, and hands over the asset
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take_ix = TakeAssetBuilder::new(taker, &escrow_data, &config).instruction();
+
+        let message = Message::new(&[take_ix], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let ata = |owner: &Pubkey| associated_token::get_associated_token_address(owner, &mint);
+        assert_eq!(core_owner(&program, &asset), taker);
+        assert_eq!(token_amount(&program, &taker_ata), 1_000_000 - 400);
+        assert_eq!(token_amount(&program, &ata(&maker)), 390);
+        assert_eq!(token_amount(&program, &ata(&treasury)), 10);
+        let after = program.get_account(&escrow);
+        assert!(after.is_none() || after.unwrap().lamports == 0);
+
+        // An asset in a collection records it, and refunding hands the asset back
+        let collection = create_core_collection(&mut program, &payer);
+        let asset = create_core_asset(&mut program, &payer, &maker, Some(collection));
+        let make = MakeAssetBuilder::new(maker, 64)
+            .price(mint, 400)
+            .offer_asset(AssetKind::Core, asset, Some(collection));
+
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let escrow_data = client::fetch_asset_escrow(&fetcher(&program), &maker, 64).unwrap();
+        assert_eq!(escrow_data.collection, Some(collection));
+
+        let message = Message::new(&[RefundAssetBuilder::new(&escrow_data).instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        assert_eq!(core_owner(&program, &asset), maker);
+        let after = program.get_account(&make.escrow());
+        assert!(after.is_none() || after.unwrap().lamports == 0);
+    }
+
+    #[test]
+    fn test_core_asset_requested_by_collection() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let collection = create_core_collection(&mut program, &payer);
+        let in_collection = create_core_asset(&mut program, &payer, &taker, Some(collection));
+        let outside = create_core_asset(&mut program, &payer, &taker, None);
+        let (mint, maker_ata) = funded_mint(&mut program, &payer, &maker);
+
+        // The maker deposits the price for any asset of the collection
+        let make = MakeAssetBuilder::new(maker, 65)
+            .price(mint, 500)
+            .request_asset(AssetKind::Core, None, Some(collection));
+        let escrow = make.escrow();
+        let vault = associated_token::get_associated_token_address(&escrow, &mint);
+
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+        assert_eq!(token_amount(&program, &vault), 500);
+        assert_eq!(token_amount(&program, &maker_ata), 1_000_000 - 500);
+
+        let escrow_data = client::fetch_asset_escrow(&fetcher(&program), &maker, 65).unwrap();
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+
+        let take = |asset: Pubkey, collection: Option<Pubkey>| {
+            TakeAssetBuilder::new(taker, &escrow_data, &config)
+                .asset(asset, collection)
+                .instruction()
+        };
+
+        let message = Message::new(&[take(outside, None)], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "InvalidAsset");
+        assert_eq!(core_owner(&program, &outside), taker);
+
+        let message = Message::new(&[take(in_collection, Some(collection))], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        assert_eq!(core_owner(&program, &in_collection), maker);
+        let taker_ata = associated_token::get_associated_token_address(&taker, &mint);
+        assert_eq!(token_amount(&program, &taker_ata), 500);
+        for account in [vault, escrow] {
+            let after = program.get_account(&account);
+            assert!(after.is_none() || after.unwrap().lamports == 0);
+        }
+    }
+
+    #[test]
+    fn test_spl_nft_requested_by_verified_collection() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+
+        let taker_kp = Keypair::new();
+        program.airdrop(&taker_kp.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        let taker = taker_kp.pubkey();
+
+        let collection = Pubkey::new_unique();
+        let (verified, verified_ata) = spl_nft(&mut program, &taker_kp, &taker);
+        set_nft_metadata(&mut program, &verified, &collection, true);
+        let (unverified, _) = spl_nft(&mut program, &taker_kp, &taker);
+        set_nft_metadata(&mut program, &unverified, &collection, false);
+        let (mint, _) = funded_mint(&mut program, &payer, &maker);
+
+        let make = MakeAssetBuilder::new(maker, 66)
+            .price(mint, 300)
+            .request_asset(AssetKind::SplNft, None, Some(collection));
+
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let escrow_data = client::fetch_asset_escrow(&fetcher(&program), &maker, 66).unwrap();
+        let config = client::fetch_config(&fetcher(&program)).unwrap();
+        let take = |nft: Pubkey| {
+            TakeAssetBuilder::new(taker, &escrow_data, &config)
+                .asset(nft, Some(collection))
+                .instruction()
+        };
+
+        // Claiming a collection is not enough; Token Metadata must have verified it
+        let message = Message::new(&[take(unverified)], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "InvalidAsset");
+
+        let message = Message::new(&[take(verified)], Some(&taker));
+        let transaction = Transaction::new(&[&taker_kp], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+
+        let ata = |owner: &Pubkey, mint: &Pubkey| associated_token::get_associated_token_address(owner, mint);
+        assert_eq!(token_amount(&program, &verified_ata), 0);
+        assert_eq!(token_amount(&program, &ata(&maker, &verified)), 1);
+        assert_eq!(token_amount(&program, &ata(&taker, &mint)), 300);
+        let after = program.get_account(&make.escrow());
+        assert!(after.is_none() || after.unwrap().lamports == 0);
+    }
+
+    #[test]
+    fn test_core_asset_with_plugins_is_rejected() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+        let (mint, _) = funded_mint(&mut program, &payer, &maker);
+
+        let offer = |program: &mut LiteSVM, seed: u64, asset: Pubkey, collection: Option<Pubkey>| {
+            let make = MakeAssetBuilder::new(maker, seed)
+                .price(mint, 400)
+                .offer_asset(AssetKind::Core, asset, collection);
+            let message = Message::new(&[make.instruction()], Some(&maker));
+            let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+            program.send_transaction(transaction)
+        };
+
+        // A plugin on the asset, such as a permanent transfer delegate, could move it out of escrow
+        let asset = create_core_asset(&mut program, &payer, &maker, None);
+        add_core_plugin_registry(&mut program, &asset);
+        assert_anchor_error(offer(&mut program, 70, asset, None), "InvalidAsset");
+        assert_eq!(core_owner(&program, &asset), maker);
+
+        // Plugins on its collection apply to the asset too, so the collection is checked as well
+        let collection = create_core_collection(&mut program, &payer);
+        let asset = create_core_asset(&mut program, &payer, &maker, Some(collection));
+        add_core_plugin_registry(&mut program, &collection);
+        assert_anchor_error(offer(&mut program, 71, asset, Some(collection)), "InvalidAsset");
+
+        // Without plugins it is accepted, once the collection it belongs to is passed
+        let collection = create_core_collection(&mut program, &payer);
+        let asset = create_core_asset(&mut program, &payer, &maker, Some(collection));
+        assert_anchor_error(offer(&mut program, 72, asset, None), "InvalidAsset");
+        offer(&mut program, 72, asset, Some(collection)).unwrap();
+        assert_eq!(core_owner(&program, &asset), pda::asset_escrow(&maker, 72));
+    }
+
+    #[test]
+    fn test_spl_nft_needs_a_fixed_supply() {
+        let (mut program, payer) = setup();
+        let maker = payer.pubkey();
+        let (mint, _) = funded_mint(&mut program, &payer, &maker);
+
+        // A single token whose mint authority can still mint more is not an NFT
+        let nft = CreateMint::new(&mut program, &payer)
+            .decimals(0)
+            .authority(&maker)
+            .send()
+            .unwrap();
+        let nft_ata = CreateAssociatedTokenAccount::new(&mut program, &payer, &nft)
+            .owner(&maker).send().unwrap();
+        MintTo::new(&mut program, &payer, &nft, &nft_ata, 1).send().unwrap();
+
+        let make = MakeAssetBuilder::new(maker, 73)
+            .price(mint, 400)
+            .offer_asset(AssetKind::SplNft, nft, None);
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        assert_anchor_error(program.send_transaction(transaction), "InvalidAsset");
+
+        // Once the mint authority is revoked it can be offered
+        revoke_mint_authority(&mut program, &payer, &nft);
+        program.expire_blockhash();
+        let message = Message::new(&[make.instruction()], Some(&maker));
+        let transaction = Transaction::new(&[&payer], message, program.latest_blockhash());
+        program.send_transaction(transaction).unwrap();
+        assert_eq!(token_amount(&program, &nft_ata), 0);
+    }
 }
@@ -0,0 +1,452 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{instruction::Instruction, program::invoke_signed, program_option::COption};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{
+        transfer_fee::TransferFeeAmount, BaseStateWithExtensions, ExtensionType,
+        StateWithExtensions,
+    },
+    onchain::invoke_transfer_checked,
+};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token_2022_extensions::{harvest_withheld_tokens_to_mint, HarvestWithheldTokensToMint};
+use anchor_spl::token_interface::{close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use tuktuk_program::tuktuk::{
+    cpi::{accounts::DequeueTaskV0, dequeue_task_v0},
+    program::Tuktuk,
+};
+
+use crate::state::{EscrowIndex, RefundTask, MPL_CORE_ID, NATIVE_SOL, TOKEN_METADATA_ID};
+use crate::EscrowError;
+
+/// Token-2022 mint extensions the escrow knows how to handle. Anything else
+/// (non-transferable, permanent delegate, default frozen state, confidential
+/// transfers, ...) could strand or drain the vault, so it is rejected at `make`.
+const SUPPORTED_MINT_EXTENSIONS: &[ExtensionType] = &[
+    ExtensionType::TransferFeeConfig,
+    ExtensionType::TransferHook,
+    ExtensionType::MintCloseAuthority,
+    ExtensionType::InterestBearingConfig,
+    ExtensionType::MetadataPointer,
+    ExtensionType::TokenMetadata,
+    ExtensionType::GroupPointer,
+    ExtensionType::TokenGroup,
+    ExtensionType::GroupMemberPointer,
+    ExtensionType::TokenGroupMember,
+];
+
+/// Core's `Key::AssetV1`, the first byte of every asset account.
+const CORE_ASSET_V1: u8 = 1;
+/// Core's `Key::CollectionV1`, the first byte of every collection account.
+const CORE_COLLECTION_V1: u8 = 5;
+/// Core's `TransferV1` instruction discriminator.
+const CORE_TRANSFER_V1: u8 = 14;
+
+/// Written over the discriminator of manually closed accounts so they can
+/// never deserialize again if refunded within the same transaction.
+pub const CLOSED_ACCOUNT_DISCRIMINATOR: [u8; 8] = [255; 8];
+
+/// Key recorded on the escrow for an optional mint account: the mint itself,
+/// or [`NATIVE_SOL`] when the leg is native lamports.
+pub fn mint_key(mint: &Option<InterfaceAccount<Mint>>) -> Pubkey {
+    mint.as_ref().map_or(NATIVE_SOL, |mint| mint.key())
+}
+
+/// Unwraps an optional account that must be present because its leg is an SPL token.
+pub fn required<T>(account: &Option<T>) -> Result<&T> {
+    account
+        .as_ref()
+        .ok_or_else(|| error!(EscrowError::MissingTokenAccount))
+}
+
+pub fn check_mint_extensions(mint: &InterfaceAccount<Mint>) -> Result<()> {
+    let info = mint.to_account_info();
+    if *info.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+
+    let data = info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    for extension in state.get_extension_types()? {
+        if !SUPPORTED_MINT_EXTENSIONS.contains(&extension) {
+            msg!("Unsupported mint extension: {:?}", extension);
+            return err!(EscrowError::UnsupportedMintExtension);
+        }
+    }
+
+    Ok(())
+}
+
+/// Drop-in for `token_interface::transfer_checked` that resolves transfer-hook
+/// extra accounts from the context's remaining accounts.
+pub fn transfer_checked<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>,
+    amount: u64,
+    decimals: u8,
+) -> Result<()> {
+    invoke_transfer_checked(
+        ctx.program.key,
+        ctx.accounts.from,
+        ctx.accounts.mint,
+        ctx.accounts.to,
+        ctx.accounts.authority,
+        &ctx.remaining_accounts,
+        amount,
+        decimals,
+        ctx.signer_seeds,
+    )
+    .map_err(Into::into)
+}
+
+/// Moves transfer fees withheld in `account` to its mint so the account can be
+/// closed. No-op for accounts without withheld fees.
+pub fn harvest_withheld_fees<'info>(
+    token_program: AccountInfo<'info>,
+    mint: AccountInfo<'info>,
+    account: AccountInfo<'info>,
+) -> Result<()> {
+    if *account.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+
+    let withheld = {
+        let data = account.try_borrow_data()?;
+        let state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)?;
+        state
+            .get_extension::<TransferFeeAmount>()
+            .map_or(0, |fee| u64::from(fee.withheld_amount))
+    };
+    if withheld == 0 {
+        return Ok(());
+    }
+    require!(mint.is_writable, EscrowError::MintNotWritable);
+
+    let cpi_accounts = HarvestWithheldTokensToMint {
+        token_program_id: token_program.clone(),
+        mint,
+    };
+    harvest_withheld_tokens_to_mint(CpiContext::new(token_program, cpi_accounts), vec![account])
+}
+
+/// Checks a native `fee` leaves the fee recipient rent-exempt, so an unfunded
+/// recipient fails with a clear error instead of the runtime's rent check.
+pub fn check_native_fee(fee_recipient: &AccountInfo, fee: u64) -> Result<()> {
+    let balance = fee_recipient
+        .lamports()
+        .checked_add(fee)
+        .ok_or(EscrowError::MathOverflow)?;
+    require!(
+        Rent::get()?.is_exempt(balance, fee_recipient.data_len()),
+        EscrowError::FeeRecipientNotRentExempt
+    );
+    Ok(())
+}
+
+/// Manually close an AccountInfo by transferring lamports, zeroing data and
+/// invalidating the discriminator.
+pub fn close_account_info(account: &AccountInfo, destination: &AccountInfo) -> Result<()> {
+    let dest_starting_lamports = destination.lamports();
+    **destination.lamports.borrow_mut() = dest_starting_lamports
+        .checked_add(account.lamports())
+        .ok_or(EscrowError::MathOverflow)?;
+    **account.lamports.borrow_mut() = 0;
+
+    let mut data = account.try_borrow_mut_data()?;
+    data.fill(0);
+    if let Some(discriminator) = data.get_mut(..CLOSED_ACCOUNT_DISCRIMINATOR.len()) {
+        discriminator.copy_from_slice(&CLOSED_ACCOUNT_DISCRIMINATOR);
+    }
+
+    Ok(())
+}
+
+/// Unpacks a Token or Token-2022 account owned by `token_program`, checking it
+/// holds `mint` for `owner`, and returns its balance.
+pub fn token_account_amount(
+    account: &AccountInfo,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<u64> {
+    require_keys_eq!(*account.owner, *token_program, EscrowError::InvalidTokenAccount);
+
+    let data = account.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)?;
+    require_keys_eq!(state.base.mint, *mint, EscrowError::InvalidTokenAccount);
+    require_keys_eq!(state.base.owner, *owner, EscrowError::InvalidTokenAccount);
+
+    Ok(state.base.amount)
+}
+
+pub fn ata_address(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
+
+/// Loads a mint passed through remaining accounts, checking it is `expected`
+/// and owned by `token_program`.
+pub fn load_mint<'info>(
+    info: &'info AccountInfo<'info>,
+    expected: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<InterfaceAccount<'info, Mint>> {
+    require_keys_eq!(info.key(), *expected, EscrowError::InvalidMint);
+    require_keys_eq!(*info.owner, *token_program, EscrowError::InvalidMint);
+    InterfaceAccount::try_from(info)
+}
+
+/// Loads a bundle leg's mint along with the token program that owns it, so
+/// each leg may be a legacy SPL or a Token-2022 mint.
+pub fn load_leg<'info>(
+    token_program: &'info AccountInfo<'info>,
+    mint: &'info AccountInfo<'info>,
+    expected: &Pubkey,
+) -> Result<(Interface<'info, TokenInterface>, InterfaceAccount<'info, Mint>)> {
+    let token_program = Interface::<TokenInterface>::try_from(token_program)?;
+    let mint = load_mint(mint, expected, token_program.key)?;
+    Ok((token_program, mint))
+}
+
+/// Sends the whole balance of `vault` to `to`, then closes the vault and
+/// returns its rent to `rent_to`. `authority` must be the vault owner PDA.
+#[allow(clippy::too_many_arguments)]
+pub fn drain_vault<'info>(
+    token_program: &AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    vault: &InterfaceAccount<'info, TokenAccount>,
+    to: AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    rent_to: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    hook_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    let cpi_accounts = TransferChecked {
+        from: vault.to_account_info(),
+        to,
+        mint: mint.to_account_info(),
+        authority: authority.clone(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program.clone(), cpi_accounts, signer_seeds)
+        .with_remaining_accounts(hook_accounts.to_vec());
+    transfer_checked(cpi_ctx, vault.amount, mint.decimals)?;
+
+    harvest_withheld_fees(token_program.clone(), mint.to_account_info(), vault.to_account_info())?;
+
+    let cpi_accounts = CloseAccount {
+        account: vault.to_account_info(),
+        destination: rent_to,
+        authority,
+    };
+    close_account(CpiContext::new_with_signer(token_program.clone(), cpi_accounts, signer_seeds))
+}
+
+/// Dequeues the TukTuk task recorded in `refund_task`, returning its rent and
+/// crank reward to the maker who paid for it. Does nothing when no task was
+/// scheduled or the caller left the task accounts out.
+pub fn cancel_refund_task<'info>(
+    refund_task: Option<RefundTask>,
+    maker: AccountInfo<'info>,
+    task_queue: &Option<UncheckedAccount<'info>>,
+    task_queue_authority: &Option<UncheckedAccount<'info>>,
+    task: &Option<UncheckedAccount<'info>>,
+    queue_authority: &Option<UncheckedAccount<'info>>,
+    tuktuk_program: &Option<Program<'info, Tuktuk>>,
+) -> Result<()> {
+    let (Some(refund_task), Some(task)) = (refund_task, task) else {
+        return Ok(());
+    };
+    let missing = || error!(EscrowError::InvalidRefundTask);
+    let task_queue = task_queue.as_ref().ok_or_else(missing)?;
+    let task_queue_authority = task_queue_authority.as_ref().ok_or_else(missing)?;
+    let queue_authority = queue_authority.as_ref().ok_or_else(missing)?;
+    let tuktuk_program = tuktuk_program.as_ref().ok_or_else(missing)?;
+
+    require_keys_eq!(task.key(), refund_task.task, EscrowError::InvalidRefundTask);
+    require_keys_eq!(task_queue.key(), refund_task.task_queue, EscrowError::InvalidRefundTask);
+
+    let (expected_authority, bump) = Pubkey::find_program_address(&[b"queue_authority"], &crate::ID);
+    require_keys_eq!(queue_authority.key(), expected_authority, EscrowError::InvalidRefundTask);
+
+    let signer_seeds: &[&[&[u8]]] = &[&[b"queue_authority", &[bump]]];
+
+    let cpi_accounts = DequeueTaskV0 {
+        queue_authority: queue_authority.to_account_info(),
+        rent_refund: maker,
+        task_queue_authority: task_queue_authority.to_account_info(),
+        task_queue: task_queue.to_account_info(),
+        task: task.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(tuktuk_program.to_account_info(), cpi_accounts, signer_seeds);
+
+    dequeue_task_v0(cpi_ctx)
+}
+
+/// Drops the closing escrow at `address` from its mint-pair and maker index
+/// pages, returning their freed rent to the maker who paid for it.
+pub fn unindex_escrow(
+    address: &Pubkey,
+    maker: &AccountInfo,
+    pair_index: &mut Account<EscrowIndex>,
+    maker_index: &mut Account<EscrowIndex>,
+) -> Result<()> {
+    EscrowIndex::remove(pair_index, address, maker)?;
+    EscrowIndex::remove(maker_index, address, maker)
+}
+
+/// Core's `AssetV1` without plugins. Core appends the plugin registry and any
+/// plugins after these fields, so an account holding more bytes has plugins.
+#[derive(AnchorDeserialize)]
+struct CoreAsset {
+    key: u8,
+    owner: Pubkey,
+    update_authority: CoreUpdateAuthority,
+    _name: String,
+    _uri: String,
+    _seq: Option<u64>,
+}
+
+#[derive(AnchorDeserialize)]
+enum CoreUpdateAuthority {
+    None,
+    Address { _address: Pubkey },
+    Collection(Pubkey),
+}
+
+/// Core's `CollectionV1` without plugins, laid out like [`CoreAsset`].
+#[derive(AnchorDeserialize)]
+struct CoreCollection {
+    key: u8,
+    _update_authority: Pubkey,
+    _name: String,
+    _uri: String,
+    _num_minted: u32,
+    _current_size: u32,
+}
+
+/// Owner and collection of a Core asset. The asset, and its collection, which
+/// must then be passed as `collection`, may carry no plugins: plugins such as a
+/// permanent transfer delegate or a freeze would let someone else move or lock
+/// the asset once it is escrowed or delivered.
+pub fn core_asset(asset: &AccountInfo, collection: Option<&AccountInfo>) -> Result<(Pubkey, Option<Pubkey>)> {
+    require_keys_eq!(*asset.owner, MPL_CORE_ID, EscrowError::InvalidAsset);
+    let data = CoreAsset::try_from_slice(&asset.try_borrow_data()?).map_err(|_| error!(EscrowError::InvalidAsset))?;
+    require!(data.key == CORE_ASSET_V1, EscrowError::InvalidAsset);
+
+    let CoreUpdateAuthority::Collection(address) = data.update_authority else {
+        return Ok((data.owner, None));
+    };
+
+    let collection = collection.ok_or(EscrowError::InvalidAsset)?;
+    require_keys_eq!(collection.key(), address, EscrowError::InvalidAsset);
+    require_keys_eq!(*collection.owner, MPL_CORE_ID, EscrowError::InvalidAsset);
+    let collection_data = CoreCollection::try_from_slice(&collection.try_borrow_data()?)
+        .map_err(|_| error!(EscrowError::InvalidAsset))?;
+    require!(collection_data.key == CORE_COLLECTION_V1, EscrowError::InvalidAsset);
+
+    Ok((data.owner, Some(address)))
+}
+
+/// Moves a Core asset to `new_owner` with a `TransferV1` CPI signed by its
+/// owner `authority`. Core requires `collection` for assets in a collection.
+pub fn transfer_core_asset<'info>(
+    core_program: &AccountInfo<'info>,
+    asset: &AccountInfo<'info>,
+    collection: Option<&AccountInfo<'info>>,
+    payer: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    new_owner: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    require_keys_eq!(core_program.key(), MPL_CORE_ID, EscrowError::InvalidAsset);
+    // Core takes its own program id in place of an omitted optional account
+    let collection = collection.unwrap_or(core_program);
+
+    let ix = Instruction {
+        program_id: MPL_CORE_ID,
+        accounts: vec![
+            AccountMeta::new(asset.key(), false),
+            AccountMeta::new_readonly(collection.key(), false),
+            AccountMeta::new(payer.key(), true),
+            AccountMeta::new_readonly(authority.key(), true),
+            AccountMeta::new_readonly(new_owner.key(), false),
+            // system_program and log_wrapper, both omitted
+            AccountMeta::new_readonly(MPL_CORE_ID, false),
+            AccountMeta::new_readonly(MPL_CORE_ID, false),
+        ],
+        // No compression proof: the asset is stored in its account
+        data: vec![CORE_TRANSFER_V1, 0],
+    };
+    let accounts = [
+        asset.clone(),
+        collection.clone(),
+        payer.clone(),
+        authority.clone(),
+        new_owner.clone(),
+        core_program.clone(),
+    ];
+
+    invoke_signed(&ix, &accounts, signer_seeds).map_err(Into::into)
+}
+
+/// Token Metadata `Metadata` fields up to the collection, all fixed by the
+/// Borsh layout the program has kept since collections were added.
+#[derive(AnchorDeserialize)]
+struct MetadataPrefix {
+    _key: u8,
+    _update_authority: Pubkey,
+    mint: Pubkey,
+    _name: String,
+    _symbol: String,
+    _uri: String,
+    _seller_fee_basis_points: u16,
+    _creators: Option<Vec<MetadataCreator>>,
+    _primary_sale_happened: bool,
+    _is_mutable: bool,
+    _edition_nonce: Option<u8>,
+    _token_standard: Option<u8>,
+    collection: Option<MetadataCollection>,
+}
+
+#[derive(AnchorDeserialize)]
+struct MetadataCreator {
+    _address: Pubkey,
+    _verified: bool,
+    _share: u8,
+}
+
+#[derive(AnchorDeserialize)]
+struct MetadataCollection {
+    verified: bool,
+    key: Pubkey,
+}
+
+/// Checks `mint` is an NFT, with no decimals, a supply of one and no way to mint
+/// more, and returns its verified Metaplex collection when its Token Metadata
+/// account is passed. The mint authority must be revoked or held by the mint's
+/// Token Metadata master edition, which never mints another token.
+pub fn spl_nft_collection(mint: &InterfaceAccount<Mint>, metadata: Option<&AccountInfo>) -> Result<Option<Pubkey>> {
+    require!(mint.decimals == 0 && mint.supply == 1, EscrowError::InvalidAsset);
+    if let COption::Some(mint_authority) = mint.mint_authority {
+        let (master_edition, _) = Pubkey::find_program_address(
+            &[b"metadata", TOKEN_METADATA_ID.as_ref(), mint.key().as_ref(), b"edition"],
+            &TOKEN_METADATA_ID,
+        );
+        require_keys_eq!(mint_authority, master_edition, EscrowError::InvalidAsset);
+    }
+    let Some(metadata) = metadata else {
+        return Ok(None);
+    };
+
+    let (address, _) = Pubkey::find_program_address(
+        &[b"metadata", TOKEN_METADATA_ID.as_ref(), mint.key().as_ref()],
+        &TOKEN_METADATA_ID,
+    );
+    require_keys_eq!(metadata.key(), address, EscrowError::InvalidAsset);
+    require_keys_eq!(*metadata.owner, TOKEN_METADATA_ID, EscrowError::InvalidAsset);
+
+    let prefix = MetadataPrefix::deserialize(&mut metadata.try_borrow_data()?.as_ref())
+        .map_err(|_| error!(EscrowError::InvalidAsset))?;
+    require_keys_eq!(prefix.mint, mint.key(), EscrowError::InvalidAsset);
+
+    Ok(prefix.collection.filter(|collection| collection.verified).map(|collection| collection.key))
+}
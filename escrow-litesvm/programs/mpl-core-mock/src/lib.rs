@@ -0,0 +1,183 @@
+#![allow(unexpected_cfgs)]
+#![allow(deprecated)]
+
+//! Minimal stand-in for Metaplex Core, deployed at Core's program id in
+//! LiteSVM tests.
+//!
+//! `create_collection_v1`, `create_v1` and `transfer_v1` match the real
+//! instructions' one-byte discriminators and account order, and assets and
+//! collections are stored in Core's base account layout. Plugins are not
+//! supported: trailing plugin arguments are ignored and never stored.
+
+use anchor_lang::prelude::*;
+
+declare_id!("CoREENxT6tW1HoK8ypY1SxRMZTcVPm7R94rH4PZNhX7d");
+
+#[program]
+pub mod mpl_core_mock {
+    use super::*;
+
+    #[instruction(discriminator = [0])]
+    pub fn create_v1(ctx: Context<CreateV1>, data_state: DataState, name: String, uri: String) -> Result<()> {
+        require!(data_state == DataState::AccountState, MockError::Unsupported);
+
+        let authority = ctx.accounts.authority.as_ref().map_or(ctx.accounts.payer.key(), |authority| authority.key());
+        let update_authority = match &mut ctx.accounts.collection {
+            Some(collection) => {
+                require_keys_eq!(collection.update_authority, authority, MockError::InvalidAuthority);
+                collection.num_minted += 1;
+                collection.current_size += 1;
+                UpdateAuthority::Collection(collection.key())
+            }
+            None => UpdateAuthority::Address(
+                ctx.accounts.update_authority.as_ref().map_or(authority, |update_authority| update_authority.key()),
+            ),
+        };
+
+        ctx.accounts.asset.set_inner(BaseAssetV1 {
+            owner: ctx.accounts.owner.as_ref().map_or(authority, |owner| owner.key()),
+            update_authority,
+            name,
+            uri,
+            seq: None,
+        });
+
+        Ok(())
+    }
+
+    #[instruction(discriminator = [1])]
+    pub fn create_collection_v1(ctx: Context<CreateCollectionV1>, name: String, uri: String) -> Result<()> {
+        ctx.accounts.collection.set_inner(BaseCollectionV1 {
+            update_authority: ctx
+                .accounts
+                .update_authority
+                .as_ref()
+                .map_or(ctx.accounts.payer.key(), |update_authority| update_authority.key()),
+            name,
+            uri,
+            num_minted: 0,
+            current_size: 0,
+        });
+
+        Ok(())
+    }
+
+    #[instruction(discriminator = [14])]
+    pub fn transfer_v1(ctx: Context<TransferV1>) -> Result<()> {
+        let authority = ctx.accounts.authority.as_ref().map_or(ctx.accounts.payer.key(), |authority| authority.key());
+        require_keys_eq!(ctx.accounts.asset.owner, authority, MockError::InvalidAuthority);
+
+        // Core needs the collection of an asset that belongs to one
+        if let UpdateAuthority::Collection(collection) = ctx.accounts.asset.update_authority {
+            let passed = ctx.accounts.collection.as_ref().map(|collection| collection.key());
+            require!(passed == Some(collection), MockError::InvalidCollection);
+        }
+
+        ctx.accounts.asset.owner = ctx.accounts.new_owner.key();
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(data_state: DataState, name: String, uri: String)]
+pub struct CreateV1<'info> {
+    #[account(init, payer = payer, space = BaseAssetV1::space(&name, &uri))]
+    pub asset: Account<'info, BaseAssetV1>,
+    #[account(mut)]
+    pub collection: Option<Account<'info, BaseCollectionV1>>,
+    pub authority: Option<Signer<'info>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: any account can own an asset
+    pub owner: Option<UncheckedAccount<'info>>,
+    /// CHECK: any account can be the update authority
+    pub update_authority: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: not used by the mock
+    pub log_wrapper: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+#[instruction(name: String, uri: String)]
+pub struct CreateCollectionV1<'info> {
+    #[account(init, payer = payer, space = BaseCollectionV1::space(&name, &uri))]
+    pub collection: Account<'info, BaseCollectionV1>,
+    /// CHECK: any account can be the update authority
+    pub update_authority: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TransferV1<'info> {
+    #[account(mut)]
+    pub asset: Account<'info, BaseAssetV1>,
+    pub collection: Option<Account<'info, BaseCollectionV1>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub authority: Option<Signer<'info>>,
+    /// CHECK: any account can own an asset
+    pub new_owner: UncheckedAccount<'info>,
+    pub system_program: Option<Program<'info, System>>,
+    /// CHECK: not used by the mock
+    pub log_wrapper: Option<UncheckedAccount<'info>>,
+}
+
+/// Core's `AssetV1` without plugins; the one-byte discriminator is Core's `Key::AssetV1`.
+#[account(discriminator = [1])]
+pub struct BaseAssetV1 {
+    pub owner: Pubkey,
+    pub update_authority: UpdateAuthority,
+    pub name: String,
+    pub uri: String,
+    pub seq: Option<u64>,
+}
+
+impl BaseAssetV1 {
+    /// Exact size with `seq` left `None`, as Core allocates assets without plugins.
+    fn space(name: &str, uri: &str) -> usize {
+        1 + 32 + 33 + 4 + name.len() + 4 + uri.len() + 1
+    }
+}
+
+/// Core's `CollectionV1` without plugins; the one-byte discriminator is Core's `Key::CollectionV1`.
+#[account(discriminator = [5])]
+pub struct BaseCollectionV1 {
+    pub update_authority: Pubkey,
+    pub name: String,
+    pub uri: String,
+    pub num_minted: u32,
+    pub current_size: u32,
+}
+
+impl BaseCollectionV1 {
+    fn space(name: &str, uri: &str) -> usize {
+        1 + 32 + 4 + name.len() + 4 + uri.len() + 4 + 4
+    }
+}
+
+// Mirrors of Core's types; their Borsh layout must match the real program's.
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub enum UpdateAuthority {
+    None,
+    Address(Pubkey),
+    Collection(Pubkey),
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub enum DataState {
+    AccountState,
+    LedgerState,
+}
+
+#[error_code]
+pub enum MockError {
+    #[msg("Only account-state assets without plugins are supported")]
+    Unsupported,
+    #[msg("Signer is not the asset owner or collection update authority")]
+    InvalidAuthority,
+    #[msg("The asset's collection must be passed")]
+    InvalidCollection,
+}
@@ -0,0 +1,179 @@
+#![allow(unexpected_cfgs)]
+#![allow(deprecated)]
+
+//! Minimal stand-in for the TukTuk scheduler, deployed at TukTuk's program id
+//! in LiteSVM tests.
+//!
+//! `queue_task_v0` matches the real instruction's discriminator, accounts and
+//! argument layout, and stores the queued transaction in the task account.
+//! Nothing is executed on-chain: a test crank replays the stored transaction
+//! in the same transaction as `run_task_v0`, which only checks the trigger,
+//! pays the crank reward and closes the task. `dequeue_task_v0` closes a task
+//! without running it, refunding everything it holds.
+
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+declare_id!("tuktukUrfhXT6ZT77QTU8RQtvgL967uRuVagWF57zVA");
+
+#[program]
+pub mod tuktuk_mock {
+    use super::*;
+
+    pub fn queue_task_v0(ctx: Context<QueueTaskV0>, args: QueueTaskArgsV0) -> Result<()> {
+        let crank_reward = args.crank_reward.unwrap_or_default();
+        if crank_reward > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.payer.to_account_info(),
+                to: ctx.accounts.task.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+            transfer(cpi_ctx, crank_reward)?;
+        }
+
+        ctx.accounts.task.set_inner(TaskV0 {
+            task_queue: ctx.accounts.task_queue.key(),
+            rent_refund: ctx.accounts.payer.key(),
+            id: args.id,
+            trigger: args.trigger,
+            transaction: args.transaction,
+            crank_reward,
+            queued_at: Clock::get()?.unix_timestamp,
+            description: args.description,
+        });
+
+        Ok(())
+    }
+
+    pub fn dequeue_task_v0(_ctx: Context<DequeueTaskV0>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn run_task_v0(ctx: Context<RunTaskV0>) -> Result<()> {
+        if let TriggerV0::Timestamp(timestamp) = ctx.accounts.task.trigger {
+            require!(Clock::get()?.unix_timestamp >= timestamp, MockError::TaskNotReady);
+        }
+
+        let crank_reward = ctx.accounts.task.crank_reward;
+        ctx.accounts.task.sub_lamports(crank_reward)?;
+        ctx.accounts.crank_turner.add_lamports(crank_reward)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(args: QueueTaskArgsV0)]
+pub struct QueueTaskV0<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub queue_authority: Signer<'info>,
+    /// CHECK: not validated by the mock
+    pub task_queue_authority: UncheckedAccount<'info>,
+    /// CHECK: not validated by the mock
+    #[account(mut)]
+    pub task_queue: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = TaskV0::space(&args),
+        seeds = [b"task", task_queue.key().as_ref(), &args.id.to_le_bytes()],
+        bump,
+    )]
+    pub task: Account<'info, TaskV0>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DequeueTaskV0<'info> {
+    pub queue_authority: Signer<'info>,
+    /// CHECK: receives the task's rent and unpaid crank reward
+    #[account(mut, address = task.rent_refund)]
+    pub rent_refund: UncheckedAccount<'info>,
+    /// CHECK: not validated by the mock
+    pub task_queue_authority: UncheckedAccount<'info>,
+    /// CHECK: must be the queue the task was added to
+    #[account(mut, address = task.task_queue)]
+    pub task_queue: UncheckedAccount<'info>,
+    #[account(mut, close = rent_refund)]
+    pub task: Account<'info, TaskV0>,
+}
+
+#[derive(Accounts)]
+pub struct RunTaskV0<'info> {
+    #[account(mut)]
+    pub crank_turner: Signer<'info>,
+    /// CHECK: receives the task's rent
+    #[account(mut, address = task.rent_refund)]
+    pub rent_refund: UncheckedAccount<'info>,
+    #[account(mut, close = rent_refund)]
+    pub task: Account<'info, TaskV0>,
+}
+
+/// A queued task: the arguments of `queue_task_v0` plus who gets its rent back.
+#[account]
+pub struct TaskV0 {
+    pub task_queue: Pubkey,
+    pub rent_refund: Pubkey,
+    pub id: u16,
+    pub trigger: TriggerV0,
+    pub transaction: TransactionSourceV0,
+    /// Lamports held by the task on top of its rent, paid to whoever runs it.
+    pub crank_reward: u64,
+    pub queued_at: i64,
+    pub description: String,
+}
+
+impl TaskV0 {
+    fn space(args: &QueueTaskArgsV0) -> usize {
+        let transaction = args.transaction.try_to_vec().map_or(0, |bytes| bytes.len());
+        8 + 32 + 32 + 2 + 9 + transaction + 8 + 8 + 4 + args.description.len()
+    }
+}
+
+// Mirrors of TukTuk's argument types; their Borsh layout must match the real program's.
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub enum TriggerV0 {
+    Now,
+    Timestamp(i64),
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct CompiledInstructionV0 {
+    pub program_id_index: u8,
+    pub accounts: Vec<u8>,
+    pub data: Vec<u8>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct CompiledTransactionV0 {
+    pub num_rw_signers: u8,
+    pub num_ro_signers: u8,
+    pub num_rw: u8,
+    pub accounts: Vec<Pubkey>,
+    pub instructions: Vec<CompiledInstructionV0>,
+    pub signer_seeds: Vec<Vec<Vec<u8>>>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub enum TransactionSourceV0 {
+    CompiledV0(CompiledTransactionV0),
+    RemoteV0 { url: String, signer: Pubkey },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct QueueTaskArgsV0 {
+    pub id: u16,
+    pub trigger: TriggerV0,
+    pub transaction: TransactionSourceV0,
+    pub crank_reward: Option<u64>,
+    pub free_tasks: u8,
+    pub description: String,
+}
+
+#[error_code]
+pub enum MockError {
+    #[msg("Task trigger has not been reached yet")]
+    TaskNotReady,
+}
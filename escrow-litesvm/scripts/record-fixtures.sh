@@ -0,0 +1,31 @@
+#!/usr/bin/env bash
+# Records account snapshots for the LiteSVM tests into
+# programs/anchor-escrow/fixtures/<address>.json.
+#
+# Usage: scripts/record-fixtures.sh [-u <cluster or url>] [address...]
+# Without addresses, every fixture already in the directory is refreshed.
+set -euo pipefail
+
+cd "$(dirname "$0")/.."
+fixtures=programs/anchor-escrow/fixtures
+url=devnet
+
+while getopts "u:" opt; do
+  case "$opt" in
+    u) url="$OPTARG" ;;
+    *) echo "usage: $0 [-u <cluster or url>] [address...]" >&2; exit 1 ;;
+  esac
+done
+shift $((OPTIND - 1))
+
+addresses=("$@")
+if [ ${#addresses[@]} -eq 0 ]; then
+  for file in "$fixtures"/*.json; do
+    addresses+=("$(basename "$file" .json)")
+  done
+fi
+
+for address in "${addresses[@]}"; do
+  solana account "$address" --url "$url" --output json --output-file "$fixtures/$address.json" > /dev/null
+  echo "recorded $address"
+done
@@ -0,0 +1,13 @@
+#!/usr/bin/env bash
+# Re-records the compute unit and rent baselines that the LiteSVM suite checks
+# into programs/anchor-escrow/baselines.json.
+#
+# Usage: scripts/update-baselines.sh
+# Run it after a change that is meant to cost more, and commit the result with it.
+set -euo pipefail
+
+cd "$(dirname "$0")/.."
+
+anchor build
+UPDATE_BASELINES=1 cargo test -p anchor-escrow test_compute_and_rent_baselines -- --nocapture
+echo "updated programs/anchor-escrow/baselines.json"